@@ -1,10 +1,14 @@
 use std::cmp::{max, min, Ord, Ordering};
+use std::hash::{Hash, Hasher};
+use std::ops::{Add, Div, Mul, Neg, Rem, Sub};
 
-use crate::{cfg_test, Vec3};
+use crate::{
+	cfg_test, Bounded, Coordinate, Frac, PointDistance, SurfaceArea, Vec3,
+};
 
 /// AABBRelation shows the relationship between the AABB and the user
 /// requested query body.
-#[derive(PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub enum AABBRelation {
 	/// Interleave means the query body is disjoint from the AABB, and
 	/// no further intersection test will be performed.
@@ -26,24 +30,112 @@ pub trait AABBQuery<B> {
 	fn check(&self, bound: &B) -> AABBRelation;
 }
 
+/// The reference to a query body is also a query body, so that the
+/// query body can either be borrowed or owned by the traversal.
+impl<B, Q: AABBQuery<B> + ?Sized> AABBQuery<B> for &Q {
+	#[inline(always)]
+	fn check(&self, bound: &B) -> AABBRelation {
+		(**self).check(bound)
+	}
+}
+
+/// AABBQueryExplain is a query body which also tells the reason of
+/// the relationship with the AABB, for debugging the queries.
+///
+/// The reason is a short human readable description, and it is
+/// evaluated separately from check, so the queries pay nothing.
+pub trait AABBQueryExplain<B>: AABBQuery<B> {
+	fn explain(&self, bound: &B) -> (AABBRelation, &'static str);
+}
+
+/// AABBDetailedRelation is the richer version of AABBRelation, which
+/// also tells whether the query body merely touches the AABB.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum AABBDetailedRelation {
+	/// Interleave means the query body is disjoint from the AABB.
+	Interleave,
+
+	/// Touch means the query body meets the AABB only on boundaries,
+	/// without crossing the surface of the query body.
+	Touch,
+
+	/// Intersect means the query body crosses the AABB.
+	Intersect,
+
+	/// Include means the query body includes the AABB completely.
+	Include,
+}
+
+impl AABBDetailedRelation {
+	/// coarse maps the relation back to AABBRelation, with Touch taken
+	/// as Intersect, which is the conservative choice.
+	///
+	/// Please notice the query bodies might be more precise on Touch,
+	/// e.g. AABB3 reports the boxes merely touching it as Interleave.
+	pub fn coarse(self) -> AABBRelation {
+		match self {
+			AABBDetailedRelation::Interleave => AABBRelation::Interleave,
+			AABBDetailedRelation::Touch => AABBRelation::Intersect,
+			AABBDetailedRelation::Intersect => AABBRelation::Intersect,
+			AABBDetailedRelation::Include => AABBRelation::Include,
+		}
+	}
+}
+
+/// AABBQueryDetailed is a query body which also tells whether it
+/// touches the AABB, while check keeps the coarse relation.
+///
+/// The relations other than Touch must be the same of check, that
+/// is, check_detailed(bound).coarse() == check(bound) unless it is
+/// Touch. Each query body decides which side the touching bounds
+/// are in check: Plane3 reports them as Intersect, so coarse always
+/// agrees with check, while AABB3 reports them as Interleave.
+pub trait AABBQueryDetailed<B>: AABBQuery<B> {
+	fn check_detailed(&self, bound: &B) -> AABBDetailedRelation;
+}
+
 /// AABB3 represents a 3-dimensional axis-aligned bounding box with
 /// various spatial operations defined upon it.
+///
+/// The intervals are reordered on deserializing, so that a malformed
+/// AABB3 with reversed interval can never be constructed.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(
+	feature = "serde",
+	derive(serde::Serialize, serde::Deserialize),
+	serde(
+		from = "Vec3<(T, T)>",
+		bound(deserialize = "T: Ord + Copy + serde::Deserialize<'de>")
+	)
+)]
 pub struct AABB3<T>(Vec3<(T, T)>);
 
-fn intersect_intervals<T: Ord + Copy>(
-	a: (T, T), b: (T, T),
+pub(crate) fn intersect_intervals<T: Ord + Copy>(
+	(a, b): ((T, T), (T, T)),
 ) -> Option<(T, T)> {
-	if (&a.0).cmp(&b.0).is_le() && (&b.0).cmp(&a.1).is_le() {
-		return Some((b.0, min(a.1, b.1)));
-	}
-	if (&a.0).cmp(&b.1).is_le() && (&b.1).cmp(&a.1).is_le() {
-		return Some((max(a.0, b.0), b.1));
-	}
-	None
+	let (lo, hi) = (max(a.0, b.0), min(a.1, b.1));
+	lo.cmp(&hi).is_le().then_some((lo, hi))
+}
+
+fn contains_interval<T: PartialOrd>(
+	(a, b): ((T, T), (T, T)),
+) -> Option<()> {
+	(a.0 <= b.0 && b.1 <= a.1).then_some(())
+}
+
+fn inside_interval<T: Ord>((a, b): ((T, T), (T, T))) -> Option<()> {
+	(a.0.cmp(&b.0).is_lt() && b.1.cmp(&a.1).is_lt()).then_some(())
+}
+
+fn contains_value<T: PartialOrd>((a, x): ((T, T), T)) -> Option<()> {
+	(a.0 <= x && x <= a.1).then_some(())
 }
 
-fn order_pair<T: Ord>(a: T, b: T) -> (T, T) {
+fn overlaps_interval<T: Ord>((a, b): ((T, T), (T, T))) -> Option<()> {
+	(a.0.cmp(&b.1).is_lt() && b.0.cmp(&a.1).is_lt()).then_some(())
+}
+
+pub(crate) fn order_pair<T: PartialOrd>((a, b): (T, T)) -> (T, T) {
 	if a > b {
 		(b, a)
 	} else {
@@ -51,26 +143,333 @@ fn order_pair<T: Ord>(a: T, b: T) -> (T, T) {
 	}
 }
 
-fn reorder_pair<T>(pair: (T, T), ord: Ordering) -> (T, T) {
+pub(crate) fn reorder_pair<T>((pair, ord): ((T, T), Ordering)) -> (T, T) {
 	match ord {
 		Ordering::Greater => (pair.1, pair.0),
 		_ => (pair.0, pair.1),
 	}
 }
 
-fn is_ne_pair<T: Ord>(a: T, b: T) -> Option<()> {
-	(&a).cmp(&b).is_ne().then_some(())
+fn is_ne_pair<T: Ord>((a, b): (T, T)) -> Option<()> {
+	a.cmp(&b).is_ne().then_some(())
 }
 
-impl<T: Ord + Copy> AABB3<T> {
+impl<T: PartialOrd + Copy> AABB3<T> {
 	/// new creates an AABB instance.
 	pub fn new(p0: Vec3<T>, p1: Vec3<T>) -> Self {
 		Self(p0 / p1 | order_pair)
 	}
 
+	/// from_ordering reorders the vertex of AABB according to the
+	/// given ordering.
+	///
+	/// The returned point pairs will always be on a body diagonal of
+	/// the original AABB, with the one on outgoing direction as the
+	/// first component.
+	#[inline(always)]
+	pub fn from_ordering(&self, v: Vec3<Ordering>) -> Vec3<(T, T)> {
+		self.0 / v | reorder_pair
+	}
+
+	/// support_points returns the corners of the AABB at the negative
+	/// and the positive extremes along the direction, which minimize
+	/// and maximize the projection onto it respectively. They are the
+	/// swapped components of from_ordering, and either corner is at
+	/// both extremes on the axes of Ordering::Equal.
+	#[inline(always)]
+	pub fn support_points(
+		&self, dir: Vec3<Ordering>,
+	) -> (Vec3<T>, Vec3<T>) {
+		let (p, n) = self.from_ordering(dir).unzip();
+		(n, p)
+	}
+
+	/// min retrieves the corner with the lowest components.
+	#[inline(always)]
+	pub fn min(&self) -> Vec3<T> {
+		self.0.unzip().0
+	}
+
+	/// max retrieves the corner with the highest components.
+	#[inline(always)]
+	pub fn max(&self) -> Vec3<T> {
+		self.0.unzip().1
+	}
+
+	/// corners retrieves the eight corners of the AABB.
+	///
+	/// The i-th corner takes the upper bound of x, y and z if the bit
+	/// 2, 1 and 0 of i is set respectively, so the first corner is min
+	/// and the last corner is max.
+	pub fn corners(&self) -> [Vec3<T>; 8] {
+		let ((x0, x1), (y0, y1), (z0, z1)) = self.0.into();
+		[
+			Vec3::new(x0, y0, z0),
+			Vec3::new(x0, y0, z1),
+			Vec3::new(x0, y1, z0),
+			Vec3::new(x0, y1, z1),
+			Vec3::new(x1, y0, z0),
+			Vec3::new(x1, y0, z1),
+			Vec3::new(x1, y1, z0),
+			Vec3::new(x1, y1, z1),
+		]
+	}
+
+	/// contains_point checks whether the point is inside the AABB,
+	/// including those on the surfaces.
+	#[inline(always)]
+	pub fn contains_point(&self, p: Vec3<T>) -> bool {
+		(self.0 / p & contains_value).is_some()
+	}
+
+	/// contains checks whether another AABB is inside the AABB, that
+	/// is, every interval of it lies within the one of this AABB.
+	#[inline(always)]
+	pub fn contains(&self, a: &Self) -> bool {
+		(self.0 / a.0 & contains_interval).is_some()
+	}
+
+	/// longest_axis returns the axis with the longest interval, which is
+	/// either 0, 1 or 2 for x, y and z, and the lower axis is preferred
+	/// when there's a tie, e.g. 0 for a cube.
+	pub fn longest_axis(&self) -> usize
+	where
+		T: Sub<Output = T>,
+	{
+		let (dx, dy, dz) = (self.0 | (|(lo, hi)| hi - lo)).into();
+		let mut axis = 0;
+		let mut longest = dx;
+		for (i, d) in [(1, dy), (2, dz)] {
+			if d > longest {
+				axis = i;
+				longest = d;
+			}
+		}
+		axis
+	}
+}
+
+cfg_test! {
+	#[test] fn test_aabb3_i64_support_points() {
+		let aabb = AABB3::new(Vec3::new(-1, 2, -3), Vec3::new(4, 5, 6));
+		let corners = aabb.corners();
+		let orderings =
+			[Ordering::Less, Ordering::Equal, Ordering::Greater];
+		for x in orderings {
+			for y in orderings {
+				for z in orderings {
+					let dir = Vec3::new(x, y, z);
+					let s = dir | (|o: Ordering| o as i64);
+					let (n, p) = aabb.support_points(dir);
+					assert!(corners.contains(&n) && corners.contains(&p));
+					let dots = corners.map(|c| c ^ s);
+					assert_eq!(n ^ s, *dots.iter().min().unwrap());
+					assert_eq!(p ^ s, *dots.iter().max().unwrap());
+				}
+			}
+		}
+		let (gt, lt) = (Ordering::Greater, Ordering::Less);
+		let dir = Vec3::new(gt, lt, gt);
+		assert_eq!(
+			aabb.support_points(dir),
+			(Vec3::new(-1, 5, -3), Vec3::new(4, 2, 6)),
+		);
+	}
+
+	#[test] fn test_aabb3_i64_longest_axis() {
+		let aabb = |p0: (i64, i64, i64), p1: (i64, i64, i64)| {
+			AABB3::new(p0.into(), p1.into())
+		};
+		assert_eq!(aabb((0, 0, 0), (5, 1, 1)).longest_axis(), 0);
+		assert_eq!(aabb((-3, -3, 0), (1, 6, 1)).longest_axis(), 1);
+		assert_eq!(aabb((0, 9, 0), (1, 1, -9)).longest_axis(), 2);
+		assert_eq!(aabb((0, 0, 0), (4, 4, 4)).longest_axis(), 0);
+		assert_eq!(aabb((0, 0, 0), (1, 4, 4)).longest_axis(), 1);
+		assert_eq!(aabb((7, 7, 7), (7, 7, 7)).longest_axis(), 0);
+	}
+}
+
+impl<T> AABB3<T>
+where
+	T: Copy + From<u8>,
+	T: Add<Output = T> + Sub<Output = T> + Div<Output = T>,
+{
+	/// center evaluates the central point of the AABB.
+	///
+	/// For integers, the division truncates towards zero, so the
+	/// center might be off by one from the real one towards the
+	/// origin. Please also notice the sum of corners might overflow.
+	pub fn center(&self) -> Vec3<T> {
+		let two = T::from(2);
+		self.0 | (|(x, y): (T, T)| (x + y) / two)
+	}
+
+	/// extents evaluates the half-size of the AABB on each axis.
+	///
+	/// For integers, the division truncates towards zero, so it is
+	/// the floor of the real half-size and the center plus or minus
+	/// the extents might not reach the corners.
+	pub fn extents(&self) -> Vec3<T> {
+		let two = T::from(2);
+		self.0 | (|(x, y): (T, T)| (y - x) / two)
+	}
+}
+
+impl<T> AABB3<T>
+where
+	T: PartialOrd + Copy + From<u8>,
+	T: Add<Output = T> + Sub<Output = T> + Div<Output = T>,
+{
+	/// expand grows the box by the margin on each side of every axis,
+	/// see also expand_vec.
+	pub fn expand(&self, margin: T) -> Self {
+		self.expand_vec(Vec3::new(margin, margin, margin))
+	}
+
+	/// expand_vec grows the box by the margin of each axis on both
+	/// sides of the axis.
+	///
+	/// A negative margin shrinks the box instead, and the intervals
+	/// shrunk by half of their lengths or more are clamped to their
+	/// midpoints, so the box degenerates rather than growing again.
+	/// For integers, the midpoints are rounded down. Please notice
+	/// that the bounds might overflow, see also grow_saturating.
+	pub fn expand_vec(&self, margin: Vec3<T>) -> Self {
+		let two = T::from(2);
+		Self(
+			self.0 / margin
+				| (|((lo, hi), m): ((T, T), T)| {
+					let (a, b) = (lo - m, hi + m);
+					if a > b {
+						let mid = lo + (hi - lo) / two;
+						return (mid, mid);
+					}
+					(a, b)
+				}),
+		)
+	}
+}
+
+cfg_test! {
+	#[test] fn test_aabb3_i64_expand() {
+		let a = AABB3::new(Vec3::new(0, -2, 4), Vec3::new(4, 2, 10));
+		let b = a.expand(3);
+		assert_eq!(b.min(), Vec3::new(-3, -5, 1));
+		assert_eq!(b.max(), Vec3::new(7, 5, 13));
+		assert_eq!(b.center(), a.center());
+
+		let c = a.expand_vec(Vec3::new(1, 0, 2));
+		assert_eq!(c.min(), Vec3::new(-1, -2, 2));
+		assert_eq!(c.max(), Vec3::new(5, 2, 12));
+
+		// Negative margins shrink the box, and the intervals shrunk
+		// by half of their lengths degenerate.
+		let d = a.expand(-2);
+		assert_eq!(d.min(), Vec3::new(2, 0, 6));
+		assert_eq!(d.max(), Vec3::new(2, 0, 8));
+		assert!(d.is_degraded());
+
+		// Shrinking further clamps the intervals to the midpoints.
+		let e = a.expand_vec(Vec3::new(-3, 0, -5));
+		assert_eq!(e.min(), Vec3::new(2, -2, 7));
+		assert_eq!(e.max(), Vec3::new(2, 2, 7));
+		assert!(e.is_degraded());
+		let f = a.expand(-100);
+		assert_eq!(f.min(), f.max());
+		assert_eq!(f.min(), a.center());
+		let g = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(3, 3, 3));
+		assert_eq!(g.expand(-2).min(), Vec3::new(1, 1, 1));
+		assert_eq!(g.expand(-2).max(), Vec3::new(1, 1, 1));
+	}
+}
+
+impl<T> AABB3<T>
+where
+	T: Ord + Copy + Add<Output = T> + Mul<Output = T>,
+{
+	/// transform maps the AABB by the matrix followed by the
+	/// translation, and returns the AABB enclosing the mapped body.
+	///
+	/// The matrix is in row-major order, so each row is dotted with
+	/// the point to evaluate a component. All eight corners are
+	/// mapped and enclosed, so the result is conservative when the
+	/// matrix is not a permutation of axes with scaling.
+	pub fn transform(
+		&self, matrix: [[T; 3]; 3], translation: Vec3<T>,
+	) -> Self {
+		let rows = matrix.map(Vec3::from);
+		let map = |p: Vec3<T>| {
+			Vec3::new(rows[0] ^ p, rows[1] ^ p, rows[2] ^ p) + translation
+		};
+		let [p, rest @ ..] = self.corners().map(map);
+		rest.into_iter()
+			.fold(AABB3::new(p, p), |a, p| a.extends(&AABB3::new(p, p)))
+	}
+}
+
+cfg_test! {
+	#[test] fn test_aabb3_i64_transform() {
+		let a = AABB3::new(Vec3::new(1, 2, 3), Vec3::new(4, 6, 8));
+		let identity = [[1, 0, 0], [0, 1, 0], [0, 0, 1]];
+		let b = a.transform(identity, Vec3::new(10, -20, 0));
+		assert_eq!(b.min(), Vec3::new(11, -18, 3));
+		assert_eq!(b.max(), Vec3::new(14, -14, 8));
+		assert_eq!(a.transform(identity, Vec3::default()), a);
+
+		// Rotating by 90 degrees around the z axis maps (x, y, z) to
+		// (-y, x, z), which swaps the x and y intervals.
+		let rotate = [[0, -1, 0], [1, 0, 0], [0, 0, 1]];
+		let c = a.transform(rotate, Vec3::default());
+		assert_eq!(c.min(), Vec3::new(-6, 1, 3));
+		assert_eq!(c.max(), Vec3::new(-2, 4, 8));
+		let d = c.transform(rotate, Vec3::new(0, 0, 1));
+		assert_eq!(d.min(), Vec3::new(-4, -6, 4));
+		assert_eq!(d.max(), Vec3::new(-1, -2, 9));
+
+		// The bound of a 45 degree shear encloses all of the corners.
+		let shear = [[1, 1, 0], [0, 1, 0], [0, 0, 2]];
+		let e = a.transform(shear, Vec3::default());
+		assert_eq!(e.min(), Vec3::new(3, 2, 6));
+		assert_eq!(e.max(), Vec3::new(10, 6, 16));
+	}
+}
+
+impl<T: Ord + Copy> AABB3<T> {
 	/// extends the current AABB with another specified bounding body.
 	pub fn extends(&self, a: &Self) -> Self {
-		Self(self.0 / a.0 | (|x, y| (min(x.0, y.0), max(x.1, y.1))))
+		Self(self.min().min(a.min()) / self.max().max(a.max()))
+	}
+
+	/// from_points creates the smallest AABB enclosing all points,
+	/// or None if there's no point.
+	pub fn from_points(
+		points: impl IntoIterator<Item = Vec3<T>>,
+	) -> Option<Self> {
+		let mut points = points.into_iter();
+		let p = points.next()?;
+		Some(
+			points.fold(AABB3::new(p, p), |a, p| {
+				a.extends(&AABB3::new(p, p))
+			}),
+		)
+	}
+
+	/// merge creates the smallest AABB enclosing all boxes, or None
+	/// if there's no box.
+	pub fn merge(
+		boxes: impl IntoIterator<Item = AABB3<T>>,
+	) -> Option<Self> {
+		boxes.into_iter().reduce(|a, b| a.extends(&b))
+	}
+
+	/// merge_refs is merge over the borrowed boxes.
+	pub fn merge_refs<'a>(
+		boxes: impl IntoIterator<Item = &'a AABB3<T>>,
+	) -> Option<Self>
+	where
+		T: 'a,
+	{
+		Self::merge(boxes.into_iter().copied())
 	}
 
 	/// intersects the current AABB with another bounding body.
@@ -82,9 +481,155 @@ impl<T: Ord + Copy> AABB3<T> {
 		Some(Self((self.0 / a.0 & intersect_intervals)?))
 	}
 
-	/// is_degraded checks whether the AABB is degraded.
+	/// intersection is a richer version of intersects, which also
+	/// reports the axes collapsed in the intersected body.
+	pub fn intersection(&self, a: &Self) -> Option<Intersection<T>> {
+		let bound = self.intersects(a)?;
+		Some(Intersection {
+			bound: bound,
+			degenerate_axes: bound.0 | (|(x, y)| x == y),
+		})
+	}
+
+	/// difference subtracts another AABB from the AABB, and returns
+	/// the boxes covering the part of the AABB outside the other one.
+	///
+	/// The AABB is cut into at most six slabs around the intersected
+	/// body, two on each axis, so the boxes only share their surfaces
+	/// with each other. Nothing is returned if the AABB is inside the
+	/// other one, and the AABB itself is returned if it is disjoint
+	/// from or only touching the other one.
+	pub fn difference(&self, a: &Self) -> Vec<Self> {
+		let inter = match self.intersects(a) {
+			Some(inter) => inter,
+			None => return vec![*self],
+		};
+		for axis in 0..3 {
+			let ((lo, hi), (x, y)) = (self.0[axis], inter.0[axis]);
+			if lo != hi && x == y {
+				return vec![*self];
+			}
+		}
+		let mut result = Vec::new();
+		let mut rest = *self;
+		for axis in 0..3 {
+			let ((lo, hi), (x, y)) = (rest.0[axis], inter.0[axis]);
+			if lo < x {
+				let (lower, upper) = rest.split(axis, x);
+				result.push(lower);
+				rest = upper;
+			}
+			if y < hi {
+				let (lower, upper) = rest.split(axis, y);
+				result.push(upper);
+				rest = lower;
+			}
+		}
+		result
+	}
+
+	/// split cuts the AABB on the axis at the coordinate, and returns
+	/// the lower and the upper part.
+	///
+	/// The coordinate is clamped into the interval of the axis, so
+	/// both parts are always valid, and one of them degenerates when
+	/// the coordinate is out of the interval. The axis must be 0, 1
+	/// or 2, otherwise it panics as indexing Vec3 does.
+	pub fn split(&self, axis: usize, at: T) -> (Self, Self) {
+		let (lo, hi) = self.0[axis];
+		let at = at.clamp(lo, hi);
+		let (mut lower, mut upper) = (self.0, self.0);
+		lower[axis].1 = at;
+		upper[axis].0 = at;
+		(Self(lower), Self(upper))
+	}
+
+	/// mirror reflects the AABB across the plane through the origin
+	/// perpendicular to the axis, by negating the interval of the
+	/// axis and swapping its ends. The axis must be 0, 1 or 2, see
+	/// also split.
+	pub fn mirror(&self, axis: usize) -> Self
+	where
+		T: Neg<Output = T>,
+	{
+		let mut v = self.0;
+		let (lo, hi) = v[axis];
+		v[axis] = (-hi, -lo);
+		Self(v)
+	}
+
+	/// is_flat checks whether any of the intervals of the AABB is a
+	/// single point, so that it has zero volume, which is either a
+	/// point, a segment or a rectangle.
+	///
+	/// The flat AABB is still a valid bound, and it might still be
+	/// hit by the other AABB through the surface, e.g. the intersects
+	/// of the AABBs sharing only a face is a flat AABB of the face.
+	/// Please notice there's no empty AABB, since the intervals are
+	/// always ordered on creation.
+	pub fn is_flat(&self) -> bool {
+		(self.0 & is_ne_pair).is_none()
+	}
+
+	/// is_point checks whether all intervals of the AABB are single
+	/// points, so that it is a single point, which is also flat.
+	pub fn is_point(&self) -> bool {
+		(self.0 & (|(lo, hi): (T, T)| (lo == hi).then_some(()))).is_some()
+	}
+
+	/// is_degraded is the alias of is_flat.
+	#[inline(always)]
 	pub fn is_degraded(&self) -> bool {
-		(self.0 & is_ne_pair).is_some()
+		self.is_flat()
+	}
+
+	/// closest_point returns the point of the AABB closest to the
+	/// specified point, by clamping it into each interval, which is
+	/// the point itself if it is inside.
+	pub fn closest_point(&self, p: Vec3<T>) -> Vec3<T> {
+		p / self.0 | (|(x, (lo, hi)): (T, (T, T))| x.clamp(lo, hi))
+	}
+
+	/// clamp_point clamps each component of the point into the
+	/// interval of the axis, which is the same as closest_point, and
+	/// the points inside are left untouched.
+	#[inline(always)]
+	pub fn clamp_point(&self, p: Vec3<T>) -> Vec3<T> {
+		self.closest_point(p)
+	}
+
+	/// wrap_point wraps each component of the point into the interval
+	/// of the axis modulo its length, for the toroidal worlds.
+	///
+	/// The intervals are taken as half-open, so the points inside are
+	/// left untouched, except that those on the upper bounds are
+	/// wrapped onto the lower bounds. The components on degraded axes
+	/// are always wrapped onto the single point of the interval.
+	///
+	/// Only the differences within the intervals and between the
+	/// points and the lower bounds are evaluated, so the unsigned
+	/// points below the lower bounds are also wrapped, but both the
+	/// length of each axis and the distance from the point to its
+	/// lower bound must not overflow T, e.g. i8 is not able to wrap
+	/// -100 into [0, 100).
+	pub fn wrap_point(&self, p: Vec3<T>) -> Vec3<T>
+	where
+		T: Default + Add<Output = T> + Sub<Output = T> + Rem<Output = T>,
+	{
+		p / self.0
+			| (|(x, (lo, hi)): (T, (T, T))| {
+				let len = hi - lo;
+				if len == T::default() {
+					return lo;
+				}
+				if x >= lo {
+					return lo + (x - lo) % len;
+				}
+				match (lo - x) % len {
+					r if r == T::default() => lo,
+					r => hi - r,
+				}
+			})
 	}
 
 	/// does_intersects_with checks whether two AABB intersects.
@@ -97,16 +642,198 @@ impl<T: Ord + Copy> AABB3<T> {
 		}
 		false
 	}
+}
 
-	/// from_ordering reorders the vertex of AABB according to the
-	/// given ordering.
+// Motion is the intervals of two AABBs on an axis, with the axial
+// component of the relative velocity between them.
+type Motion = (((f64, f64), (f64, f64)), f64);
+
+impl AABB3<f64> {
+	/// time_of_impact evaluates the earliest time in [0, 1] that the
+	/// current AABB moving with the relative velocity to another AABB
+	/// touches it, with 0 returned if they are already overlapping.
 	///
-	/// The returned point pairs will always be on a body diagonal of
-	/// the original AABB, with the one on outgoing direction as the
-	/// first component.
+	/// Two moving AABBs touches each other if and only if the motion
+	/// reaches the Minkowski difference of them, so this is the slab
+	/// test of the segment against the difference.
+	pub fn time_of_impact(
+		&self, other: &AABB3<f64>, rel_velocity: Vec3<f64>,
+	) -> Option<f64> {
+		let slabs = self.0 / other.0 / rel_velocity
+			& (|((a, b), v): Motion| {
+				let (lo, hi) = (b.0 - a.1, b.1 - a.0);
+				if v == 0.0 {
+					return (lo <= 0.0 && 0.0 <= hi)
+						.then_some((f64::NEG_INFINITY, f64::INFINITY));
+				}
+				let (t0, t1) = (lo / v, hi / v);
+				Some(if v > 0.0 { (t0, t1) } else { (t1, t0) })
+			});
+		let (x, y, z) = slabs?.into();
+		let enter = x.0.max(y.0).max(z.0).max(0.0);
+		let exit = x.1.min(y.1).min(z.1).min(1.0);
+		(enter <= exit).then_some(enter)
+	}
+
+	/// approx_eq checks whether the corners of the AABBs are equal
+	/// within epsilon, see also Vec3::approx_eq.
+	pub fn approx_eq(&self, other: &AABB3<f64>, epsilon: f64) -> bool {
+		let (lo0, hi0) = self.0.unzip();
+		let (lo1, hi1) = other.0.unzip();
+		lo0.approx_eq(lo1, epsilon) && hi0.approx_eq(hi1, epsilon)
+	}
+}
+
+impl AABB3<i64> {
+	/// align_pow2 snaps the box outward to the boundaries of cells,
+	/// whose size is the power of two specified by log2_cell, by
+	/// flooring the lower bounds and ceiling the upper bounds to the
+	/// multiples of the cell size.
+	///
+	/// The bounds must not overflow after ceiling to the cell size,
+	/// and log2_cell must be less than 63, since the cell size of
+	/// 2^63 or larger is not representable in i64.
+	pub fn align_pow2(&self, log2_cell: u32) -> AABB3<i64> {
+		assert!(
+			log2_cell < 63,
+			"log2_cell {} is not less than 63",
+			log2_cell,
+		);
+		let mask = (1i64 << log2_cell) - 1;
+		Self(
+			self.0
+				| (|(lo, hi): (i64, i64)| {
+					(lo & !mask, (hi + mask) & !mask)
+				}),
+		)
+	}
+}
+
+macro_rules! impl_aabb3_saturating {
+	($($t:ty),*) => {
+		$(
+			impl AABB3<$t> {
+				/// grow_saturating grows the box by the margin on
+				/// each side, and clamps the bounds to the limits of
+				/// the type instead of overflowing.
+				///
+				/// A bound saturated at the limit can be treated as
+				/// an infinite bound conservatively. The margin is
+				/// expected to be non-negative, and the intervals
+				/// shrunk across by negative margin are reordered.
+				pub fn grow_saturating(&self, margin: $t) -> Self {
+					Self(self.0 | (|(lo, hi): ($t, $t)| order_pair((
+						lo.saturating_sub(margin),
+						hi.saturating_add(margin),
+					))))
+				}
+			}
+		)*
+	};
+}
+
+impl_aabb3_saturating!(i8, i16, i32, i64, i128, isize);
+impl_aabb3_saturating!(u8, u16, u32, u64, u128, usize);
+
+/// AABB3 can also be a query body, for picking up the objects that
+/// overlaps with the box.
+///
+/// Following the semantic of does_intersects_with, the bounds merely
+/// touching the surface of the query box are disjoint from it. But a
+/// degraded bound inside is still considered to be intersecting when
+/// it passes through the interiors of the query box, although their
+/// intersection is zero volume, which avoids missing flat leaves.
+///
+/// Only the bounds strictly inside the query box are included, since
+/// a flat leaf lying on the surface is disjoint, while any bound
+/// enclosing it and reaching the interior is intersecting, and they
+/// would be pruned when the branches above them are included.
+impl<T: Ord + Copy> AABBQuery<AABB3<T>> for AABB3<T> {
 	#[inline(always)]
-	pub fn from_ordering(&self, v: Vec3<Ordering>) -> Vec3<(T, T)> {
-		self.0 / v | reorder_pair
+	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
+		if (self.0 / bound.0 & inside_interval).is_some() {
+			return AABBRelation::Include;
+		}
+		if (self.0 / bound.0 & overlaps_interval).is_some() {
+			return AABBRelation::Intersect;
+		}
+		AABBRelation::Interleave
+	}
+}
+
+/// The boxes merely touching the surface of the query box, which are
+/// Interleave in check, are reported as Touch.
+impl<T: Ord + Copy> AABBQueryDetailed<AABB3<T>> for AABB3<T> {
+	fn check_detailed(&self, bound: &AABB3<T>) -> AABBDetailedRelation {
+		match self.check(bound) {
+			AABBRelation::Include => AABBDetailedRelation::Include,
+			AABBRelation::Intersect => AABBDetailedRelation::Intersect,
+			AABBRelation::Interleave => match self.intersects(bound) {
+				Some(_) => AABBDetailedRelation::Touch,
+				None => AABBDetailedRelation::Interleave,
+			},
+		}
+	}
+}
+
+cfg_test! {
+	#[test] fn test_aabb3_i64_check_detailed() {
+		let query = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(4, 4, 4));
+		let check = |p0: (i64, i64, i64), p1: (i64, i64, i64)| {
+			let aabb = AABB3::new(p0.into(), p1.into());
+			let detailed = query.check_detailed(&aabb);
+			if detailed != AABBDetailedRelation::Touch {
+				assert_eq!(detailed.coarse(), query.check(&aabb));
+			} else {
+				assert_eq!(query.check(&aabb), AABBRelation::Interleave);
+			}
+			detailed
+		};
+		assert_eq!(
+			check((1, 1, 1), (3, 3, 3)),
+			AABBDetailedRelation::Include,
+		);
+		assert_eq!(
+			check((2, 2, 2), (6, 6, 6)),
+			AABBDetailedRelation::Intersect,
+		);
+		assert_eq!(
+			check((4, 0, 0), (6, 4, 4)),
+			AABBDetailedRelation::Touch,
+		);
+		assert_eq!(
+			check((4, 4, 4), (6, 6, 6)),
+			AABBDetailedRelation::Touch,
+		);
+		assert_eq!(
+			check((-2, 1, 1), (0, 3, 3)),
+			AABBDetailedRelation::Touch,
+		);
+		assert_eq!(
+			check((5, 0, 0), (6, 4, 4)),
+			AABBDetailedRelation::Interleave,
+		);
+	}
+}
+
+/// Intersection is the intersected body of two bounding boxes, with
+/// the axes that their intervals merely touch each other.
+///
+/// When any of the axes is degenerate, the two boxes are in contact
+/// only on their surfaces, otherwise they are penetrating.
+#[derive(Copy, Clone, Debug)]
+pub struct Intersection<T> {
+	pub bound: AABB3<T>,
+	pub degenerate_axes: Vec3<bool>,
+}
+
+impl<T> Intersection<T> {
+	/// is_contact checks whether the intersection is a surface
+	/// contact instead of a penetration.
+	pub fn is_contact(&self) -> bool {
+		self.degenerate_axes.0
+			|| self.degenerate_axes.1
+			|| self.degenerate_axes.2
 	}
 }
 
@@ -123,7 +850,551 @@ impl<T: Ord + Copy> From<Vec3<(T, T)>> for AABB3<T> {
 	}
 }
 
+/// AABB3 can be collected from points, see also AABB3::from_points.
+///
+/// There's no sensible AABB enclosing nothing, so collecting from no
+/// point panics, and from_points should be used instead when the
+/// points might be empty.
+impl<T: Ord + Copy> FromIterator<Vec3<T>> for AABB3<T> {
+	fn from_iter<I: IntoIterator<Item = Vec3<T>>>(points: I) -> Self {
+		AABB3::from_points(points).expect("no point to enclose")
+	}
+}
+
+cfg_test! {
+	#[test] fn test_aabb3_i64_from_points() {
+		let points = [
+			Vec3::new(1, -2, 3),
+			Vec3::new(-4, 5, 0),
+			Vec3::new(2, 2, -6),
+			Vec3::new(0, 7, 1),
+		];
+		let a = AABB3::from_points(points).unwrap();
+		assert_eq!(a.min(), Vec3::new(-4, -2, -6));
+		assert_eq!(a.max(), Vec3::new(2, 7, 3));
+		assert_eq!(points.into_iter().collect::<AABB3<i64>>(), a);
+
+		let p = AABB3::from_points([Vec3::new(1, 2, 3)]).unwrap();
+		assert_eq!(p.min(), p.max());
+		assert!(AABB3::<i64>::from_points([]).is_none());
+	}
+
+	#[test]
+	#[should_panic(expected = "no point to enclose")]
+	fn test_aabb3_i64_collect_empty() {
+		let _: AABB3<i64> = Vec::new().into_iter().collect();
+	}
+}
+
+/// AABB3 are equal when all of their intervals are equal, which are
+/// always ordered, so the boxes created from different corners of
+/// the same box are equal.
+impl<T: Copy + Eq> PartialEq for AABB3<T> {
+	fn eq(&self, a: &Self) -> bool {
+		self.0 == a.0
+	}
+}
+
+impl<T: Copy + Eq> Eq for AABB3<T> {}
+
+impl<T: Hash + Ord + Copy> Hash for AABB3<T> {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.0.hash(state);
+	}
+}
+
+cfg_test! {
+	#[test] fn test_aabb3_i64_is_flat() {
+		let p = Vec3::new(1, 2, 3);
+		let point = AABB3::new(p, p);
+		assert!(point.is_point());
+		assert!(point.is_flat());
+		assert!(point.is_degraded());
+
+		let line = AABB3::new(p, p + Vec3::new(0, 4, 0));
+		assert!(!line.is_point());
+		assert!(line.is_flat());
+
+		let rect = AABB3::new(p, p + Vec3::new(2, 0, 5));
+		assert!(!rect.is_point());
+		assert!(rect.is_flat());
+		assert!(rect.is_degraded());
+
+		let solid = AABB3::new(p, p + Vec3::new(2, 4, 5));
+		assert!(!solid.is_point());
+		assert!(!solid.is_flat());
+		assert!(!solid.is_degraded());
+
+		// The solids sharing a face intersect by the flat face.
+		let next =
+			AABB3::new(p + Vec3::new(2, 0, 0), p + Vec3::new(3, 1, 1));
+		let face = solid.intersects(&next).unwrap();
+		assert!(face.is_flat() && !face.is_point());
+		let corner = AABB3::new(p - Vec3::new(1, 1, 1), p);
+		assert!(solid.intersects(&corner).unwrap().is_point());
+	}
+
+	#[test] fn test_aabb3_i64_split() {
+		let a = AABB3::new(Vec3::new(0, -2, 4), Vec3::new(4, 2, 10));
+		let (lower, upper) = a.split(2, 7);
+		assert_eq!(
+			lower,
+			AABB3::new(Vec3::new(0, -2, 4), Vec3::new(4, 2, 7)),
+		);
+		assert_eq!(
+			upper,
+			AABB3::new(Vec3::new(0, -2, 7), Vec3::new(4, 2, 10)),
+		);
+		assert_eq!(lower.extends(&upper), a);
+
+		// Coordinates out of the interval are clamped.
+		let (lower, upper) = a.split(0, -5);
+		assert_eq!(
+			lower,
+			AABB3::new(Vec3::new(0, -2, 4), Vec3::new(0, 2, 10)),
+		);
+		assert_eq!(upper, a);
+		let (lower, upper) = a.split(1, 9);
+		assert_eq!(lower, a);
+		assert!(upper.is_degraded());
+	}
+
+	// assert_aabb3_i64_difference checks the boxes of the difference
+	// by the unit cells within [-16, 16], that each cell covered by
+	// the difference is covered by exactly one of the boxes.
+	fn assert_aabb3_i64_difference(a: &AABB3<i64>, b: &AABB3<i64>) {
+		let diff = a.difference(b);
+		assert!(diff.len() <= 6);
+		for d in &diff {
+			assert!(a.contains(d));
+			assert!(!d.is_degraded() || a.is_degraded());
+		}
+		for x in -16..16 {
+			for y in -16..16 {
+				for z in -16..16 {
+					let p = Vec3::new(x, y, z);
+					let cell = AABB3::new(p, p + Vec3::new(1, 1, 1));
+					let covered = a.contains(&cell)
+						&& b.intersects(&cell).is_none_or(|x| {
+							x.is_degraded()
+						});
+					let count =
+						diff.iter().filter(|d| d.contains(&cell)).count();
+					assert_eq!(
+						count,
+						covered as usize,
+						"a = {:?}, b = {:?}, cell = {:?}",
+						a, b, cell,
+					);
+				}
+			}
+		}
+	}
+
+	#[test] fn test_aabb3_i64_difference() {
+		let a = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(4, 4, 4));
+
+		// The disjoint and touching boxes leave the AABB untouched.
+		let p = Vec3::new(5, 0, 0);
+		let b = AABB3::new(p, p + Vec3::new(2, 2, 2));
+		assert_eq!(a.difference(&b), vec![a]);
+		let p = Vec3::new(4, 1, 1);
+		let b = AABB3::new(p, p + Vec3::new(2, 2, 2));
+		assert_eq!(a.difference(&b), vec![a]);
+
+		// Nothing is left when it is contained.
+		let b = AABB3::new(Vec3::new(-1, 0, 0), Vec3::new(4, 5, 4));
+		assert_eq!(a.difference(&b), vec![]);
+		assert_eq!(a.difference(&a), vec![]);
+
+		// The corner overlap leaves three slabs.
+		let b = AABB3::new(Vec3::new(2, 2, 2), Vec3::new(6, 6, 6));
+		assert_eq!(
+			a.difference(&b),
+			vec![
+				AABB3::new(Vec3::new(0, 0, 0), Vec3::new(2, 4, 4)),
+				AABB3::new(Vec3::new(2, 0, 0), Vec3::new(4, 2, 4)),
+				AABB3::new(Vec3::new(2, 2, 0), Vec3::new(4, 4, 2)),
+			],
+		);
+		assert_aabb3_i64_difference(&a, &b);
+
+		// The hole in the middle leaves six slabs.
+		let b = AABB3::new(Vec3::new(1, 1, 1), Vec3::new(3, 3, 3));
+		assert_eq!(a.difference(&b).len(), 6);
+		assert_aabb3_i64_difference(&a, &b);
+
+		let rng = &mut prng_seeded().0;
+		let mut gen = || {
+			Vec3::new(
+				rng.gen_range(-16, 17),
+				rng.gen_range(-16, 17),
+				rng.gen_range(-16, 17),
+			)
+		};
+		for _ in 0..100 {
+			let a = AABB3::new(gen(), gen());
+			let b = AABB3::new(gen(), gen());
+			assert_aabb3_i64_difference(&a, &b);
+		}
+	}
+
+	#[test] fn test_aabb3_i64_mirror() {
+		let a = AABB3::new(Vec3::new(1, -2, 4), Vec3::new(5, 2, 10));
+		assert_eq!(
+			a.mirror(0),
+			AABB3::new(Vec3::new(-5, -2, 4), Vec3::new(-1, 2, 10)),
+		);
+		assert_eq!(a.mirror(1), a);
+		assert_eq!(
+			a.mirror(2),
+			AABB3::new(Vec3::new(1, -2, -10), Vec3::new(5, 2, -4)),
+		);
+		for axis in 0..3 {
+			assert_eq!(a.mirror(axis).mirror(axis), a);
+		}
+
+		// The mirrored box contains the mirrored points.
+		let b = AABB3::new(Vec3::new(-3, 0, 0), Vec3::new(2, 1, 1));
+		assert!(a.mirror(0).contains_point(Vec3::new(-3, 0, 5)));
+		assert!(b.mirror(0).contains_point(Vec3::new(3, 0, 0)));
+		assert!(!b.mirror(0).contains_point(Vec3::new(-3, 0, 0)));
+		assert_eq!(
+			b.mirror(0).intersects(&a.mirror(0)),
+			a.intersects(&b).map(|x| x.mirror(0)),
+		);
+	}
+
+	#[test]
+	#[should_panic(expected = "axis 3 out of range for Vec3")]
+	fn test_aabb3_i64_split_out_of_range() {
+		let a = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(1, 1, 1));
+		a.split(3, 0);
+	}
+
+	#[test] fn test_aabb3_i64_corners() {
+		let a = AABB3::new(Vec3::new(4, -2, 6), Vec3::new(1, 5, 3));
+		let corners = a.corners();
+		assert_eq!(
+			corners.map(<(i64, i64, i64)>::from),
+			[
+				(1, -2, 3),
+				(1, -2, 6),
+				(1, 5, 3),
+				(1, 5, 6),
+				(4, -2, 3),
+				(4, -2, 6),
+				(4, 5, 3),
+				(4, 5, 6),
+			],
+		);
+		assert_eq!(corners[0], a.min());
+		assert_eq!(corners[7], a.max());
+		assert_eq!(AABB3::from_points(corners), Some(a));
+		let p = Vec3::new(1, 1, 1);
+		assert_eq!(AABB3::new(p, p).corners(), [p; 8]);
+	}
+
+	#[test] fn test_aabb3_i64_merge() {
+		let boxes = [
+			AABB3::new(Vec3::new(0, 0, 0), Vec3::new(1, 1, 1)),
+			AABB3::new(Vec3::new(-3, 2, 5), Vec3::new(-1, 4, 6)),
+			AABB3::new(Vec3::new(2, -7, 0), Vec3::new(3, 0, 2)),
+		];
+		let expected = boxes[0].extends(&boxes[1]).extends(&boxes[2]);
+		assert_eq!(AABB3::merge(boxes), Some(expected));
+		assert_eq!(AABB3::merge_refs(&boxes), Some(expected));
+		assert_eq!(expected.min(), Vec3::new(-3, -7, 0));
+		assert_eq!(expected.max(), Vec3::new(3, 4, 6));
+		assert_eq!(AABB3::merge_refs(&boxes[..1]), Some(boxes[0]));
+		assert_eq!(AABB3::<i64>::merge(Vec::new()), None);
+		assert_eq!(AABB3::<i64>::merge_refs(&[]), None);
+	}
+
+	#[test] fn test_aabb3_i64_hash() {
+		let mut set = HashSet::new();
+		let a = AABB3::new(Vec3::new(1, 2, 3), Vec3::new(4, 5, 6));
+		assert!(set.insert(a));
+		assert!(!set.insert(AABB3::new(
+			Vec3::new(4, 2, 6),
+			Vec3::new(1, 5, 3),
+		)));
+		assert!(set.insert(AABB3::new(
+			Vec3::new(1, 2, 3),
+			Vec3::new(4, 5, 7),
+		)));
+		assert_eq!(set.len(), 2);
+		assert!(set.contains(&a));
+	}
+
+	#[test] fn test_aabb_relation_hash() {
+		let query = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(10, 10, 10));
+		let mut counts = HashMap::new();
+		for i in -5..15 {
+			let p = Vec3::new(i, i, i);
+			let aabb = AABB3::new(p, p + Vec3::new(1, 1, 1));
+			let relation = query.check(&aabb);
+			*counts.entry(relation).or_insert(0) += 1;
+		}
+		assert_eq!(counts[&AABBRelation::Include], 8);
+		assert_eq!(counts[&AABBRelation::Intersect], 2);
+		assert_eq!(counts[&AABBRelation::Interleave], 10);
+	}
+}
+
+impl<T> Bounded for AABB3<T>
+where
+	T: Ord + Copy + Add<Output = T> + Sub<Output = T>,
+{
+	type Scalar = T;
+	const AXES: usize = 3;
+
+	fn union(&self, other: &Self) -> Self {
+		self.extends(other)
+	}
+
+	/// centroid of AABB3 is the sum of the interval's endpoints,
+	/// which is twice the actual centroid and division is avoided.
+	fn centroid(&self, axis: usize) -> T {
+		let v = self.0[axis];
+		v.0 + v.1
+	}
+}
+
+impl<T, U> AABB3<T>
+where
+	T: Copy + Sub<Output = T> + Mul<Output = U>,
+{
+	/// volume evaluates the product of the interval lengths.
+	///
+	/// The product is evaluated in U, which must be wide enough to
+	/// hold the cube of the lengths, e.g. the lengths of i64 intervals
+	/// should be below 2^21 to evaluate in i64.
+	pub fn volume(&self) -> U
+	where
+		U: Mul<T, Output = U>,
+	{
+		let (dx, dy, dz) = (self.0 | (|(lo, hi)| hi - lo)).into();
+		dx * dy * dz
+	}
+
+	/// surface_area evaluates 2*(dx*dy + dy*dz + dz*dx), which is the
+	/// surface area of the AABB.
+	///
+	/// The sum is evaluated in U, which must be wide enough to hold
+	/// six times the square of the longest length, e.g. the lengths
+	/// of i64 intervals should be below 2^30 to evaluate in i64.
+	pub fn surface_area(&self) -> U
+	where
+		U: Copy + Add<Output = U>,
+	{
+		let (dx, dy, dz) = (self.0 | (|(lo, hi)| hi - lo)).into();
+		let s = dx * dy + dy * dz + dz * dx;
+		s + s
+	}
+
+	/// squared_distance_to evaluates the squared length from the point
+	/// to the closest point of the AABB, which is zero for the points
+	/// inside, see also closest_point.
+	///
+	/// The squared length is evaluated in U, which must be wide enough
+	/// to hold three times the square of the differences.
+	pub fn squared_distance_to(&self, p: Vec3<T>) -> U
+	where
+		T: Ord,
+		U: Add<Output = U>,
+	{
+		let near = p - self.closest_point(p);
+		near ^ near
+	}
+
+	/// intersection_volume evaluates the volume of the intersection
+	/// with another AABB, or None if they are disjoint.
+	///
+	/// The AABBs sharing only the surface give Some(0), while they
+	/// are disjoint to does_intersects_with, so None is returned only
+	/// when they don't even touch each other.
+	pub fn intersection_volume(&self, a: &Self) -> Option<U>
+	where
+		T: Ord,
+		U: Mul<T, Output = U>,
+	{
+		Some(self.intersects(a)?.volume())
+	}
+
+	/// overlap_fraction evaluates the fraction of the volume of the
+	/// current AABB which is covered by another AABB, or None if they
+	/// are disjoint. The denominator is zero when the current AABB is
+	/// degraded.
+	pub fn overlap_fraction(&self, a: &Self) -> Option<Frac<U>>
+	where
+		T: Ord,
+		U: Mul<T, Output = U>,
+	{
+		Some(Frac {
+			num: self.intersection_volume(a)?,
+			den: self.volume(),
+		})
+	}
+}
+
+cfg_test! {
+	#[test] fn test_aabb3_i64_closest_point() {
+		let a = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(4, 6, 8));
+		let check = |p: (i64, i64, i64)| {
+			let p = Vec3::from(p);
+			(a.closest_point(p).into(), a.squared_distance_to(p))
+		};
+		assert_eq!(check((1, 2, 3)), ((1, 2, 3), 0));
+		assert_eq!(check((0, 0, 0)), ((0, 0, 0), 0));
+		assert_eq!(check((4, 3, 3)), ((4, 3, 3), 0));
+		assert_eq!(check((7, 3, 3)), ((4, 3, 3), 9));
+		assert_eq!(check((2, -2, 5)), ((2, 0, 5), 4));
+		assert_eq!(check((6, 9, 12)), ((4, 6, 8), 4 + 9 + 16));
+		assert_eq!(check((-1, -2, -3)), ((0, 0, 0), 1 + 4 + 9));
+		assert_eq!(a.distance2(&Vec3::new(6, 9, 12)), 29);
+	}
+
+	#[test] fn test_aabb3_i64_clamp_wrap_point() {
+		let a = AABB3::new(Vec3::new(0, 10, -5), Vec3::new(4, 20, 5));
+		type P = (i64, i64, i64);
+		let clamp = |p: P| -> P { a.clamp_point(p.into()).into() };
+		let wrap = |p: P| -> P { a.wrap_point(p.into()).into() };
+
+		// The points inside are untouched.
+		assert_eq!(clamp((1, 15, 0)), (1, 15, 0));
+		assert_eq!(wrap((1, 15, 0)), (1, 15, 0));
+		assert_eq!(clamp((4, 20, 5)), (4, 20, 5));
+		assert_eq!(wrap((0, 10, -5)), (0, 10, -5));
+
+		// The points outside each face are clamped onto the face.
+		assert_eq!(clamp((-3, 15, 0)), (0, 15, 0));
+		assert_eq!(clamp((9, 15, 0)), (4, 15, 0));
+		assert_eq!(clamp((1, 2, 0)), (1, 10, 0));
+		assert_eq!(clamp((1, 25, 0)), (1, 20, 0));
+		assert_eq!(clamp((1, 15, -9)), (1, 15, -5));
+		assert_eq!(clamp((1, 15, 9)), (1, 15, 5));
+
+		// The points are wrapped across the boundaries.
+		assert_eq!(wrap((4, 20, 5)), (0, 10, -5));
+		assert_eq!(wrap((5, 21, 6)), (1, 11, -4));
+		assert_eq!(wrap((-1, 9, -6)), (3, 19, 4));
+		assert_eq!(wrap((-9, 45, -26)), (3, 15, 4));
+		assert_eq!(wrap((13, -15, 17)), (1, 15, -3));
+
+		// The degraded axes wrap everything onto the single point.
+		let flat = AABB3::new(Vec3::new(0, 0, 3), Vec3::new(4, 4, 3));
+		let p = Vec3::new(1, 5, 9);
+		assert_eq!(flat.wrap_point(p), Vec3::new(1, 1, 3));
+		assert_eq!(flat.clamp_point(p), Vec3::new(1, 4, 3));
+
+		// The unsigned points below the lower bounds are wrapped.
+		let a = AABB3::new(Vec3::new(10u8, 10, 10), Vec3::new(20, 20, 20));
+		let p = Vec3::new(0u8, 5, 255);
+		assert_eq!(a.wrap_point(p), Vec3::new(10, 15, 15));
+		let p = Vec3::new(9u8, 10, 20);
+		assert_eq!(a.wrap_point(p), Vec3::new(19, 10, 10));
+		let a = AABB3::new(Vec3::new(-5i8, 0, 0), Vec3::new(5, 1, 1));
+		let p = Vec3::new(-128i8, 127, 0);
+		assert_eq!(a.wrap_point(p), Vec3::new(2, 0, 0));
+	}
+
+	#[test] fn test_aabb3_i64_volume() {
+		let cube = AABB3::new(
+			Vec3::new(0, 0, 0),
+			Vec3::new(1, 1, 1),
+		);
+		assert_eq!(cube.volume(), 1);
+		assert_eq!(cube.surface_area(), 6);
+
+		let a = AABB3::new(
+			Vec3::new(-1, 2, 3),
+			Vec3::new(1, 5, 7),
+		);
+		assert_eq!(a.volume(), 2 * 3 * 4);
+		assert_eq!(a.surface_area(), 2 * (6 + 12 + 8));
+
+		let flat = AABB3::new(
+			Vec3::new(-1, 2, 3),
+			Vec3::new(1, 5, 3),
+		);
+		assert_eq!(flat.volume(), 0);
+		assert_eq!(flat.surface_area(), 2 * 6);
+
+		let point = AABB3::new(
+			Vec3::new(7, 7, 7),
+			Vec3::new(7, 7, 7),
+		);
+		assert_eq!(point.volume(), 0);
+		assert_eq!(point.surface_area(), 0);
+	}
+
+	#[test] fn test_aabb3_i64_intersection_volume() {
+		let aabb = |p0: (i64, i64, i64), p1: (i64, i64, i64)| {
+			AABB3::new(p0.into(), p1.into())
+		};
+		let a = aabb((0, 0, 0), (4, 4, 4));
+
+		// Partial overlap of a 2x3x4 box.
+		let b = aabb((2, 1, -1), (6, 9, 4));
+		assert_eq!(a.intersection_volume(&b), Some(2 * 3 * 4));
+		assert_eq!(b.intersection_volume(&a), Some(2 * 3 * 4));
+		let f = a.overlap_fraction(&b).unwrap();
+		assert_eq!((f.num, f.den), (24, 64));
+
+		// Full containment covers the inner box entirely.
+		let c = aabb((1, 1, 1), (3, 2, 4));
+		assert_eq!(a.intersection_volume(&c), Some(c.volume()));
+		let f = c.overlap_fraction(&a).unwrap();
+		assert_eq!((f.num, f.den), (6, 6));
+
+		// Surface-only contact and disjoint boxes.
+		let d = aabb((4, 0, 0), (5, 4, 4));
+		assert!(!a.does_intersects_with(&d));
+		assert_eq!(a.intersection_volume(&d), Some(0));
+		let f = a.overlap_fraction(&d).unwrap();
+		assert_eq!((f.num, f.den), (0, 64));
+		let e = aabb((5, 0, 0), (6, 4, 4));
+		assert_eq!(a.intersection_volume(&e), None);
+		assert!(a.overlap_fraction(&e).is_none());
+	}
+}
+
+/// The surface area of AABB3 is 2*(dx*dy + dy*dz + dz*dx), which is
+/// evaluated in U, and U must be wide enough to hold it.
+impl<T, U> SurfaceArea for AABB3<T>
+where
+	T: Ord + Copy + Sub<Output = T> + Mul<Output = U>,
+	U: Ord + Copy + Add<Output = U>,
+{
+	type Area = U;
+
+	fn surface_area(&self) -> U {
+		AABB3::surface_area(self)
+	}
+}
+
+/// The squared distance from the point to AABB3 is evaluated by
+/// clamping the point into each interval, which is evaluated in U,
+/// and U must be wide enough to hold it.
+impl<T, U> PointDistance<Vec3<T>> for AABB3<T>
+where
+	T: Coordinate<Product = U>,
+	U: Ord + Add<Output = U>,
+{
+	type Distance = U;
+
+	fn distance2(&self, point: &Vec3<T>) -> U {
+		self.squared_distance_to(*point)
+	}
+}
+
 cfg_test! {
+	use std::collections::{HashMap, HashSet};
+	use rand::Rng;
+	use crate::{prng_seeded, gen_vec3_i64};
+	use crate::naive::AABBNaive3;
+
 	#[test] fn test_aabb3_i64_new() {
 		let a = AABB3::new(
 			Vec3::new(1, 2, 3),
@@ -149,6 +1420,71 @@ cfg_test! {
 		);
 	}
 
+	#[test] fn test_aabb3_i64_contains() {
+		let a = AABB3::new(
+			Vec3::new(0, 0, 0),
+			Vec3::new(4, 4, 4),
+		);
+		assert!(a.contains(&a));
+		assert!(a.contains_point(Vec3::new(2, 2, 2)));
+		assert!(a.contains_point(Vec3::new(0, 4, 2)));
+		assert!(a.contains_point(Vec3::new(4, 4, 4)));
+		assert!(!a.contains_point(Vec3::new(-1, 2, 2)));
+		assert!(!a.contains_point(Vec3::new(2, 2, 5)));
+
+		let inner = AABB3::new(
+			Vec3::new(1, 1, 1),
+			Vec3::new(2, 2, 2),
+		);
+		assert!(a.contains(&inner));
+		assert!(!inner.contains(&a));
+
+		let face = AABB3::new(
+			Vec3::new(3, 1, 1),
+			Vec3::new(4, 2, 2),
+		);
+		assert!(a.contains(&face));
+		let across = AABB3::new(
+			Vec3::new(3, 1, 1),
+			Vec3::new(5, 2, 2),
+		);
+		assert!(!a.contains(&across));
+		let apart = AABB3::new(
+			Vec3::new(5, 5, 5),
+			Vec3::new(6, 6, 6),
+		);
+		assert!(!a.contains(&apart));
+		assert!(!apart.contains(&a));
+	}
+
+	#[test] fn test_aabb3_i64_accessors() {
+		let a = AABB3::new(
+			Vec3::new(1, 2, 3),
+			Vec3::new(4, -5, 6),
+		);
+		assert_eq!(a.min(), Vec3::new(1, -5, 3));
+		assert_eq!(a.max(), Vec3::new(4, 2, 6));
+		assert_eq!(a.center(), Vec3::new(2, -1, 4));
+		assert_eq!(a.extents(), Vec3::new(1, 3, 1));
+
+		let b = AABB3::from(Vec3::new(
+			(-7, 10), (8, 11), (12, -9),
+		));
+		assert_eq!(b.min(), Vec3::new(-7, 8, -9));
+		assert_eq!(b.max(), Vec3::new(10, 11, 12));
+		assert_eq!(b.center(), Vec3::new(1, 9, 1));
+		assert_eq!(b.extents(), Vec3::new(8, 1, 10));
+
+		let c = AABB3::new(
+			Vec3::new(-4, -2, 0),
+			Vec3::new(4, 6, 8),
+		);
+		assert_eq!(c.center(), Vec3::new(0, 2, 4));
+		assert_eq!(c.extents(), Vec3::new(4, 4, 4));
+		assert_eq!(c.center() - c.extents(), c.min());
+		assert_eq!(c.center() + c.extents(), c.max());
+	}
+
 	#[test] fn test_aabb3_i64_intersects() {
 		let a = AABB3::new(
 			Vec3::new(1, -5, 3),
@@ -164,4 +1500,227 @@ cfg_test! {
 			Vec3::new((1, 2), (1, 2), (3, 5)),
 		);
 	}
+
+	#[test] fn test_aabb3_i64_intersection() {
+		let a = AABB3::new(
+			Vec3::new(0, 0, 0),
+			Vec3::new(2, 2, 2),
+		);
+		let b = AABB3::new(
+			Vec3::new(2, 1, -1),
+			Vec3::new(4, 3, 1),
+		);
+		let c = a.intersection(&b).unwrap();
+		assert_eq!(
+			Vec3::<(i64, i64)>::from(c.bound),
+			Vec3::new((2, 2), (1, 2), (0, 1)),
+		);
+		assert_eq!(c.degenerate_axes, Vec3::new(true, false, false));
+		assert!(c.is_contact());
+
+		let d = AABB3::new(
+			Vec3::new(1, 1, 1),
+			Vec3::new(3, 3, 3),
+		);
+		let e = a.intersection(&d).unwrap();
+		assert_eq!(e.degenerate_axes, Vec3::new(false, false, false));
+		assert!(!e.is_contact());
+
+		let f = AABB3::new(
+			Vec3::new(3, 3, 3),
+			Vec3::new(4, 4, 4),
+		);
+		assert!(a.intersection(&f).is_none());
+	}
+
+	#[test] fn test_aabb3_f64_time_of_impact() {
+		let a = AABB3::new(
+			Vec3::new(0.0, 0.0, 0.0),
+			Vec3::new(1.0, 1.0, 1.0),
+		);
+		let b = AABB3::new(
+			Vec3::new(3.0, 0.5, 0.0),
+			Vec3::new(4.0, 1.5, 1.0),
+		);
+		let toi = |a: &AABB3<f64>, b, v: (f64, f64, f64)| {
+			a.time_of_impact(b, v.into())
+		};
+		assert_eq!(toi(&a, &b, (4.0, 0.0, 0.0)), Some(0.5));
+		assert_eq!(toi(&b, &a, (-4.0, 0.0, 0.0)), Some(0.5));
+		assert_eq!(toi(&a, &b, (4.0, 1.0, 0.0)), Some(0.5));
+		assert_eq!(toi(&a, &b, (-4.0, 0.0, 0.0)), None);
+		assert_eq!(toi(&a, &b, (1.0, 0.0, 0.0)), None);
+		assert_eq!(toi(&a, &b, (4.0, 4.0, 0.0)), None);
+
+		let c = AABB3::new(
+			Vec3::new(0.5, 0.5, 0.5),
+			Vec3::new(1.5, 1.5, 1.5),
+		);
+		assert_eq!(toi(&a, &c, (4.0, 0.0, 0.0)), Some(0.0));
+		assert_eq!(toi(&a, &c, (0.0, 0.0, 0.0)), Some(0.0));
+	}
+
+	#[test] fn test_aabb3_i64_grow_saturating() {
+		let a = AABB3::new(
+			Vec3::new(i64::MIN + 10, -5, 0),
+			Vec3::new(10, 5, i64::MAX - 10),
+		);
+		let b = a.grow_saturating(100);
+		assert_eq!(
+			Vec3::<(i64, i64)>::from(b),
+			Vec3::new((i64::MIN, 110), (-105, 105), (-100, i64::MAX)),
+		);
+		let c = b.grow_saturating(i64::MAX);
+		assert_eq!(
+			Vec3::<(i64, i64)>::from(c),
+			Vec3::new(
+				(i64::MIN, i64::MAX),
+				(i64::MIN, i64::MAX),
+				(i64::MIN, i64::MAX),
+			),
+		);
+
+		let d = AABB3::new(Vec3::new(1u8, 2, 3), Vec3::new(250, 4, 6));
+		assert_eq!(
+			Vec3::<(u8, u8)>::from(d.grow_saturating(10)),
+			Vec3::new((0, 255), (0, 14), (0, 16)),
+		);
+	}
+
+	#[test] fn test_aabb3_i64_align_pow2() {
+		let a = AABB3::new(
+			Vec3::new(-5, 3, -8),
+			Vec3::new(3, 9, -4),
+		);
+		assert_eq!(
+			Vec3::<(i64, i64)>::from(a.align_pow2(2)),
+			Vec3::new((-8, 4), (0, 12), (-8, -4)),
+		);
+		assert_eq!(
+			Vec3::<(i64, i64)>::from(a.align_pow2(0)),
+			Vec3::<(i64, i64)>::from(a),
+		);
+
+		let rng = &mut prng_seeded().0;
+		for _ in 0..10000 {
+			let a = AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng));
+			let log2_cell = rng.gen_range(0, 24);
+			let b = a.align_pow2(log2_cell);
+			let v = Vec3::<(i64, i64)>::from(b);
+			assert_eq!(Vec3::<(i64, i64)>::from(b.extends(&a)), v);
+			let cell = 1i64 << log2_cell;
+			let aligned = |(lo, hi): (i64, i64)| {
+				(lo.rem_euclid(cell) == 0 && hi.rem_euclid(cell) == 0)
+					.then_some(())
+			};
+			assert!((v & aligned).is_some());
+		}
+	}
+
+	#[test]
+	#[should_panic(expected = "log2_cell 63 is not less than 63")]
+	fn test_aabb3_i64_align_pow2_overflow() {
+		let a = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(1, 1, 1));
+		a.align_pow2(63);
+	}
+
+	#[test] fn test_aabb3_f64_approx_eq() {
+		let a = AABB3::new(
+			Vec3::new(0.0, 0.0, 0.0),
+			Vec3::new(1.0, 1.0, 1.0),
+		);
+		let b = AABB3::new(
+			Vec3::new(1e-9, 0.0, 0.0),
+			Vec3::new(1.0, 1.0 - 1e-9, 1.0),
+		);
+		assert!(a.approx_eq(&b, 1e-6));
+		assert!(!a.approx_eq(&b, 1e-12));
+		let c = AABB3::new(
+			Vec3::new(0.0, 0.0, 0.0),
+			Vec3::new(1.0, 1.0, 1.5),
+		);
+		assert!(!a.approx_eq(&c, 1e-6));
+	}
+
+	#[test] fn test_aabb3_i64_does_intersects_with() {
+		let a = AABB3::new(
+			Vec3::new(0, 0, 0),
+			Vec3::new(4, 4, 4),
+		);
+		let inner = AABB3::new(
+			Vec3::new(1, 1, 1),
+			Vec3::new(2, 2, 2),
+		);
+		let c = a.intersects(&inner).unwrap();
+		assert_eq!(
+			Vec3::<(i64, i64)>::from(c),
+			Vec3::<(i64, i64)>::from(inner),
+		);
+		assert!(a.does_intersects_with(&inner));
+		assert!(inner.does_intersects_with(&a));
+		assert!(!a.is_degraded());
+
+		let face = AABB3::new(
+			Vec3::new(4, 1, 1),
+			Vec3::new(5, 2, 2),
+		);
+		assert!(a.intersects(&face).unwrap().is_degraded());
+		assert!(!a.does_intersects_with(&face));
+
+		let apart = AABB3::new(
+			Vec3::new(5, 1, 1),
+			Vec3::new(6, 2, 2),
+		);
+		assert!(a.intersects(&apart).is_none());
+		assert!(!a.does_intersects_with(&apart));
+	}
+
+	#[test] fn test_aabb3_i64_random_query() {
+		const NUM: usize = 1000000;
+		let rng = &mut prng_seeded().0;
+		let mut include = 0usize;
+		let mut intersect = 0usize;
+		let mut interleave = 0usize;
+		for _ in 0..NUM {
+			let (p0, p1) = (gen_vec3_i64(rng), gen_vec3_i64(rng));
+			let query = AABB3::new(p0, p1);
+			let naive = AABBNaive3::new(&query);
+			let aabb = AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng));
+			let actual = query.check(&aabb);
+			let expected = naive.check(&aabb);
+			assert_eq!(
+				actual, expected,
+				"query = {:?}, aabb = {:?}",
+				query, aabb,
+			);
+			match actual {
+				AABBRelation::Include => include += 1,
+				AABBRelation::Intersect => intersect += 1,
+				AABBRelation::Interleave => interleave += 1,
+			}
+		}
+		println!(
+			"include = {}, intersect = {}, interleave = {}",
+			include, intersect, interleave,
+		);
+	}
+
+	#[test] fn test_aabb3_i64_query_surface() {
+		let a = AABB3::new(
+			Vec3::new(0, 0, 0),
+			Vec3::new(4, 4, 4),
+		);
+		let check = |p0: (i64, i64, i64), p1: (i64, i64, i64)| {
+			a.check(&AABB3::new(p0.into(), p1.into()))
+		};
+		assert_eq!(check((4, 0, 0), (5, 4, 4)), AABBRelation::Interleave);
+		assert_eq!(check((-1, 4, 4), (0, 5, 5)), AABBRelation::Interleave);
+		assert_eq!(check((3, 0, 0), (5, 4, 4)), AABBRelation::Intersect);
+		assert_eq!(check((-1, 2, 1), (5, 2, 3)), AABBRelation::Intersect);
+		assert_eq!(check((1, 1, 1), (3, 3, 3)), AABBRelation::Include);
+		assert_eq!(check((0, 0, 0), (4, 4, 4)), AABBRelation::Intersect);
+		assert_eq!(check((4, 0, 0), (4, 4, 4)), AABBRelation::Interleave);
+		assert_eq!(check((2, 0, 0), (2, 4, 4)), AABBRelation::Intersect);
+		assert_eq!(check((5, 5, 5), (6, 6, 6)), AABBRelation::Interleave);
+	}
 }
@@ -0,0 +1,123 @@
+use std::cmp::{max, min, Ordering};
+use std::ops::{Add, Sub};
+
+use crate::aabb::{intersect_intervals, order_pair, reorder_pair};
+use crate::{cfg_test, Bounded, Vec2};
+
+/// AABB2 represents a 2-dimensional axis-aligned bounding box, the
+/// counterpart of AABB3 on the plane.
+#[derive(Copy, Clone, Debug)]
+pub struct AABB2<T>(Vec2<(T, T)>);
+
+impl<T: PartialOrd + Copy> AABB2<T> {
+	/// new creates an AABB instance.
+	pub fn new(p0: Vec2<T>, p1: Vec2<T>) -> Self {
+		Self(p0 / p1 | order_pair)
+	}
+
+	/// from_ordering reorders the vertex of AABB according to the
+	/// given ordering, see also AABB3::from_ordering.
+	#[inline(always)]
+	pub fn from_ordering(&self, v: Vec2<Ordering>) -> Vec2<(T, T)> {
+		self.0 / v | reorder_pair
+	}
+
+	/// min retrieves the corner with the lowest components.
+	#[inline(always)]
+	pub fn min(&self) -> Vec2<T> {
+		self.0.unzip().0
+	}
+
+	/// max retrieves the corner with the highest components.
+	#[inline(always)]
+	pub fn max(&self) -> Vec2<T> {
+		self.0.unzip().1
+	}
+}
+
+impl<T: Ord + Copy> AABB2<T> {
+	/// extends the current AABB with another specified bounding body.
+	pub fn extends(&self, a: &Self) -> Self {
+		Self(self.0 / a.0 | (|(x, y)| (min(x.0, y.0), max(x.1, y.1))))
+	}
+
+	/// intersects the current AABB with another bounding body.
+	///
+	/// Please notice that two bounding box shares the same edge is
+	/// also considered a case of intersection.
+	pub fn intersects(&self, a: &Self) -> Option<Self> {
+		Some(Self((self.0 / a.0 & intersect_intervals)?))
+	}
+}
+
+impl<T: Ord + Copy> From<AABB2<T>> for Vec2<(T, T)> {
+	fn from(v: AABB2<T>) -> Vec2<(T, T)> {
+		v.0
+	}
+}
+
+impl<T: Ord + Copy> From<Vec2<(T, T)>> for AABB2<T> {
+	fn from(v: Vec2<(T, T)>) -> AABB2<T> {
+		let (x, y) = v.unzip();
+		AABB2::new(x, y)
+	}
+}
+
+impl<T> Bounded for AABB2<T>
+where
+	T: Ord + Copy + Add<Output = T> + Sub<Output = T>,
+{
+	type Scalar = T;
+	const AXES: usize = 2;
+
+	fn union(&self, other: &Self) -> Self {
+		self.extends(other)
+	}
+
+	/// centroid of AABB2 is the sum of the interval's endpoints,
+	/// which is twice the actual centroid and division is avoided.
+	fn centroid(&self, axis: usize) -> T {
+		let v = match axis {
+			0 => self.0 .0,
+			_ => self.0 .1,
+		};
+		v.0 + v.1
+	}
+}
+
+cfg_test! {
+	#[test] fn test_aabb2_i64_new() {
+		let a = AABB2::new(Vec2::new(1, 2), Vec2::new(4, -5));
+		assert_eq!(
+			Vec2::<(i64, i64)>::from(a),
+			Vec2::new((1, 4), (-5, 2)),
+		);
+		assert_eq!(a.min(), Vec2::new(1, -5));
+		assert_eq!(a.max(), Vec2::new(4, 2));
+
+		let b = AABB2::from(Vec2::new((-7, 10), (12, -9)));
+		assert_eq!(
+			Vec2::<(i64, i64)>::from(b),
+			Vec2::new((-7, 10), (-9, 12)),
+		);
+		let c = a.extends(&b);
+		assert_eq!(
+			Vec2::<(i64, i64)>::from(c),
+			Vec2::new((-7, 10), (-9, 12)),
+		);
+	}
+
+	#[test] fn test_aabb2_i64_intersects() {
+		let a = AABB2::new(Vec2::new(1, -5), Vec2::new(4, 2));
+		let b = AABB2::new(Vec2::new(3, 0), Vec2::new(6, 8));
+		let c = a.intersects(&b).unwrap();
+		assert_eq!(Vec2::<(i64, i64)>::from(c), Vec2::new((3, 4), (0, 2)));
+
+		let d = AABB2::new(Vec2::new(4, 2), Vec2::new(5, 3));
+		let e = a.intersects(&d).unwrap();
+		assert_eq!(Vec2::<(i64, i64)>::from(e), Vec2::new((4, 4), (2, 2)));
+
+		let f = AABB2::new(Vec2::new(5, 0), Vec2::new(6, 1));
+		assert!(a.intersects(&f).is_none());
+	}
+}
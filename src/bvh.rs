@@ -1,14 +1,76 @@
-use crate::{AABBQuery, AABBRelation, Enumerator};
+use std::cell::RefCell;
+use std::cmp::{max, min, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::ops::{Add, Mul, Sub};
 
-struct BVHBranch<B> {
-	bound: B,
-	left: usize,
-	right: usize,
+use crate::{cfg_test, AABBQuery, AABBRelation, AABB3};
+
+cfg_test! {
+	extern crate test;
+
+	use rand::Rng;
+	use test::Bencher;
+	use crate::{prng, prng_seeded, gen_vec3_i64, Plane3, Vec3};
 }
 
-struct BVHLeaf<B, V> {
-	bound: B,
-	value: V,
+/// Bounded defines the operations required on the bounding bodies
+/// for constructing a BVH out of them.
+pub trait Bounded {
+	/// Scalar is the type of each component of the centroid.
+	type Scalar: Ord + Copy + Sub<Output = Self::Scalar>;
+
+	/// AXES is the number of axes the centroid has, and the axis
+	/// passed to centroid will always be less than it.
+	const AXES: usize;
+
+	/// union returns the body enclosing both bounding bodies.
+	fn union(&self, other: &Self) -> Self;
+
+	/// centroid returns the component of the centroid on the axis.
+	///
+	/// The result is only used for ordering and comparing spreads,
+	/// so any value proportional to the actual centroid will do.
+	fn centroid(&self, axis: usize) -> Self::Scalar;
+}
+
+/// SurfaceArea measures the surface area of bounding bodies, which
+/// is the probability of being hit by uniformly distributed rays, up
+/// to a constant factor. This is used for evaluating the surface area
+/// heuristic (SAH) cost of hierarchies.
+pub trait SurfaceArea {
+	type Area: Ord;
+
+	fn surface_area(&self) -> Self::Area;
+}
+
+/// PointDistance measures the squared distance from the point to the
+/// closest point of bounding bodies, which is zero for points inside.
+/// This is the lower bound of distances from the point to anything
+/// inside the body, and is used for searching the nearest leaves.
+pub trait PointDistance<P> {
+	type Distance: Ord;
+
+	fn distance2(&self, point: &P) -> Self::Distance;
+}
+
+#[cfg_attr(
+	feature = "serde",
+	derive(serde::Serialize, serde::Deserialize)
+)]
+pub(crate) struct BVHBranch<B> {
+	pub(crate) bound: B,
+	pub(crate) left: usize,
+	pub(crate) right: usize,
+}
+
+#[cfg_attr(
+	feature = "serde",
+	derive(serde::Serialize, serde::Deserialize)
+)]
+pub(crate) struct BVHLeaf<B, V> {
+	pub(crate) bound: B,
+	pub(crate) value: V,
 }
 
 /// BVH is an immutable BVH which is designed to be as compact in
@@ -19,7 +81,13 @@ struct BVHLeaf<B, V> {
 /// fine control over the granularities and depth of the finally
 /// constructed BVH.
 ///
-/// You may also distill constructed mutable BVH into this one.
+/// You may also distill a DynamicBVH constructed incrementally into
+/// this one.
+///
+/// With the serde feature, the nodes of BVH are serialized directly,
+/// and the structure is validated on deserializing, so that a BVH
+/// deserialized from malformed data will be rejected instead of
+/// panicking when it is queried.
 ///
 /// The memory compactness of BVH is based on the fact that all leaf
 /// nodes of the tree contains the value, and all branch nodes in the
@@ -40,21 +108,153 @@ struct BVHLeaf<B, V> {
 /// However, we'll always visit the branch nodes first, and then leaves,
 /// so we place branches and leaves into different lists instead of
 /// interleaving the nodes.
+#[cfg_attr(
+	feature = "serde",
+	derive(serde::Serialize, serde::Deserialize),
+	serde(try_from = "BVHData<B, V>")
+)]
 pub struct BVH<B, V> {
+	pub(crate) root: usize,
+	#[cfg_attr(feature = "serde", serde(skip_serializing))]
+	pub(crate) depth: usize,
+	pub(crate) branches: Vec<BVHBranch<B>>,
+	pub(crate) leaves: Vec<BVHLeaf<B, V>>,
+}
+
+// BVHData is the serialized form of BVH, which is validated before
+// being converted into the BVH.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct BVHData<B, V> {
 	root: usize,
 	branches: Vec<BVHBranch<B>>,
 	leaves: Vec<BVHLeaf<B, V>>,
 }
 
+// validate checks the structural invariants of the nodes, that there
+// are n-1 branches for n leaves, each node is referred exactly once,
+// and the leaves are visited in the order of the leaves' list. The
+// depth of the hierarchy is returned if they hold.
+fn validate<B, V>(
+	root: usize, branches: &[BVHBranch<B>], leaves: &[BVHLeaf<B, V>],
+) -> Result<usize, String> {
+	if leaves.is_empty() {
+		if root != 0 || !branches.is_empty() {
+			return Err("empty bvh with nodes".to_string());
+		}
+		return Ok(0);
+	}
+	if branches.len() + 1 != leaves.len() {
+		return Err(format!(
+			"{} branches for {} leaves",
+			branches.len(),
+			leaves.len(),
+		));
+	}
+	let mut referred =
+		(vec![false; branches.len()], vec![false; leaves.len()]);
+	let mut refer = |node: usize| {
+		let (id, is_branch) = decompose(node);
+		let seen = match is_branch {
+			true => referred.0.get_mut(id),
+			false => referred.1.get_mut(id),
+		};
+		match seen {
+			None => Err(format!("node {} out of range", node)),
+			Some(true) => Err(format!("node {} referred twice", node)),
+			Some(seen) => {
+				*seen = true;
+				Ok(())
+			},
+		}
+	};
+	refer(root)?;
+	for branch in branches {
+		refer(branch.left)?;
+		refer(branch.right)?;
+	}
+
+	// Every node is referred once and the root is not referred by any
+	// branch, so the walk will terminate.
+	let mut depth = 0;
+	let mut next = 0;
+	let mut stack = vec![(root, 1)];
+	while let Some((node, level)) = stack.pop() {
+		let (id, is_branch) = decompose(node);
+		if is_branch {
+			let branch = &branches[id];
+			stack.push((branch.right, level + 1));
+			stack.push((branch.left, level + 1));
+			continue;
+		}
+		if id != next {
+			return Err(format!("leaf {} visited out of order", id));
+		}
+		next += 1;
+		depth = max(depth, level);
+	}
+	if next != leaves.len() {
+		return Err("leaves not reachable from root".to_string());
+	}
+	Ok(depth)
+}
+
+impl<B, V> BVH<B, V> {
+	// from_nodes assembles the BVH from the nodes loaded elsewhere,
+	// and the nodes must pass the validation.
+	pub(crate) fn from_nodes(
+		root: usize, branches: Vec<BVHBranch<B>>,
+		leaves: Vec<BVHLeaf<B, V>>,
+	) -> Result<Self, String> {
+		let depth = validate(root, &branches, &leaves)?;
+		Ok(Self {
+			root: root,
+			depth: depth,
+			branches: branches,
+			leaves: leaves,
+		})
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<B, V> TryFrom<BVHData<B, V>> for BVH<B, V> {
+	type Error = String;
+
+	fn try_from(data: BVHData<B, V>) -> Result<Self, String> {
+		Self::from_nodes(data.root, data.branches, data.leaves)
+	}
+}
+
 // decompose bvhnode id attempt to decompose and check whether the id
 // specifies a branch or node.
 fn decompose(id: usize) -> (usize, bool) {
 	(id >> 1, (id & 1) != 0)
 }
 
-impl<B, V> BVH<B, V> {
+// BVHNodes is the borrowed nodes of a BVH, which might either be
+// owned by the BVH itself or reside in a shared BVHArena.
+//
+// The depth is the number of nodes on the longest path from the
+// root to a leaf, which bounds the size of the traversal stack.
+// It is zero when unknown, and the stack will grow on demand then.
+struct BVHNodes<'n, B, V> {
+	root: usize,
+	depth: usize,
+	branches: &'n [BVHBranch<B>],
+	leaves: &'n [BVHLeaf<B, V>],
+}
+
+impl<'n, B, V> Clone for BVHNodes<'n, B, V> {
+	fn clone(&self) -> Self {
+		*self
+	}
+}
+
+impl<'n, B, V> Copy for BVHNodes<'n, B, V> {}
+
+impl<'n, B, V> BVHNodes<'n, B, V> {
 	// leftmost index of leaf for specified branch index.
-	fn leftmost(&self, branch: usize) -> usize {
+	fn leftmost(self, branch: usize) -> usize {
 		let mut node = &self.branches[branch];
 		loop {
 			let (id, is_branch) = decompose(node.left);
@@ -66,7 +266,7 @@ impl<B, V> BVH<B, V> {
 	}
 
 	// rightmost index of leaf for specified branch index.
-	fn rightmost(&self, branch: usize) -> usize {
+	fn rightmost(self, branch: usize) -> usize {
 		let mut node = &self.branches[branch];
 		loop {
 			let (id, is_branch) = decompose(node.right);
@@ -76,6 +276,115 @@ impl<B, V> BVH<B, V> {
 			node = &self.branches[id];
 		}
 	}
+}
+
+// Step is the action taken by the traversal on visiting a node.
+enum Step {
+	// Prune the subtree of the node with the encoded id.
+	Prune(usize),
+
+	// Enter the branch with the id, and its children will be
+	// visited later.
+	Enter(usize),
+
+	// Include all leaves in the subtree of the branch with the id.
+	Include(usize),
+
+	// Leaf with the id is hit, with the relation which must either
+	// be AABBRelation::Include or AABBRelation::Intersect.
+	Leaf(usize, AABBRelation),
+}
+
+// Traversal walks the hierarchy in pre-order while checking the
+// nodes against the query body, and yields what is done on each
+// visited node.
+//
+// The left child is always visited before the right one, so the
+// leaves will be hit in the order of the leaves' list.
+struct Traversal<'n, B, V, Q> {
+	nodes: BVHNodes<'n, B, V>,
+	q: Q,
+	stack: Vec<usize>,
+}
+
+impl<'n, B, V> BVHNodes<'n, B, V> {
+	// traverse the hierarchy with the query, the stack is taken for
+	// holding the nodes to visit, which can be reused by taking it
+	// back from the traversal.
+	fn traverse<Q: AABBQuery<B>>(
+		self, q: Q, mut stack: Vec<usize>,
+	) -> Traversal<'n, B, V, Q> {
+		stack.clear();
+		if self.root != 0 || !self.leaves.is_empty() {
+			stack.push(self.root);
+		}
+		Traversal {
+			nodes: self,
+			q: q,
+			stack: stack,
+		}
+	}
+
+	// bound of the node with the encoded id.
+	fn bound(self, node: usize) -> &'n B {
+		match decompose(node) {
+			(id, true) => &self.branches[id].bound,
+			(id, false) => &self.leaves[id].bound,
+		}
+	}
+
+	// leaves_of returns the range of leaves in the subtree of the
+	// branch with the specified id.
+	fn leaves_of(self, branch: usize) -> &'n [BVHLeaf<B, V>] {
+		&self.leaves[self.leftmost(branch)..=self.rightmost(branch)]
+	}
+
+	// span returns the half open range of the leaves' indices in the
+	// subtree of the node with the encoded id.
+	fn span(self, node: usize) -> (usize, usize) {
+		match decompose(node) {
+			(id, true) => (self.leftmost(id), self.rightmost(id) + 1),
+			(id, false) => (id, id + 1),
+		}
+	}
+}
+
+impl<'n, B, V, Q: AABBQuery<B>> Iterator for Traversal<'n, B, V, Q> {
+	type Item = Step;
+
+	fn next(&mut self) -> Option<Step> {
+		let top = self.stack.pop()?;
+		let (id, is_branch) = decompose(top);
+		if is_branch {
+			let branch = &self.nodes.branches[id];
+			Some(match self.q.check(&branch.bound) {
+				AABBRelation::Interleave => Step::Prune(top),
+				AABBRelation::Intersect => {
+					self.stack.push(branch.right);
+					self.stack.push(branch.left);
+					Step::Enter(id)
+				},
+				AABBRelation::Include => Step::Include(id),
+			})
+		} else {
+			let leaf = &self.nodes.leaves[id];
+			Some(match self.q.check(&leaf.bound) {
+				AABBRelation::Interleave => Step::Prune(top),
+				relation => Step::Leaf(id, relation),
+			})
+		}
+	}
+}
+
+impl<B, V> BVH<B, V> {
+	fn nodes(&self) -> BVHNodes<'_, B, V> {
+		BVHNodes {
+			root: self.root,
+			depth: self.depth,
+			branches: &self.branches,
+			leaves: &self.leaves,
+		}
+	}
 
 	/// query for all items hit by the AABB query and return.
 	///
@@ -86,68 +395,2705 @@ impl<B, V> BVH<B, V> {
 	pub fn query<'a, 'b: 'a>(
 		&'b self, q: &'a impl AABBQuery<B>,
 	) -> impl 'a + Iterator<Item = &'b V> {
-		Enumerator::new(|| {
-			if self.root == 0 && self.leaves.len() == 0 {
-				return;
+		self.nodes().query(q)
+	}
+
+	/// query_bounds is query with the bound of each item hit by the
+	/// AABB query, in the same order of query.
+	pub fn query_bounds<'a, 'b: 'a>(
+		&'b self, q: &'a impl AABBQuery<B>,
+	) -> impl 'a + Iterator<Item = (&'b B, &'b V)> {
+		let mut query = self.nodes().query(q);
+		std::iter::from_fn(move || {
+			let leaf = query.next_leaf()?;
+			Some((&leaf.bound, &leaf.value))
+		})
+	}
+
+	/// query_filter is query with the items not satisfying the
+	/// predicate skipped, in the same order of query.
+	///
+	/// The leaves under an included subtree are still taken without
+	/// checking their bounds, and only the predicate is evaluated.
+	pub fn query_filter<'a, 'b: 'a>(
+		&'b self, q: &'a impl AABBQuery<B>, pred: impl 'a + Fn(&V) -> bool,
+	) -> impl 'a + Iterator<Item = &'b V> {
+		self.nodes().query(q).filter(move |x| pred(x))
+	}
+
+	/// query_owned is query with the query body moved into the
+	/// iterator, so that a temporary query body needs not to be
+	/// bound to a variable outliving the iterator.
+	pub fn query_owned<'b, Q: 'b + AABBQuery<B>>(
+		&'b self, q: Q,
+	) -> impl 'b + Iterator<Item = &'b V> {
+		self.nodes().query(q)
+	}
+
+	/// query_cloned is query with the items cloned, for the callers
+	/// who want the owned items, in the same order of query.
+	///
+	/// All other queries yield references only, and V is required to
+	/// be Clone only here.
+	pub fn query_cloned<'a, 'b: 'a>(
+		&'b self, q: &'a impl AABBQuery<B>,
+	) -> impl 'a + Iterator<Item = V>
+	where
+		V: Clone,
+	{
+		self.nodes().query(q).cloned()
+	}
+
+	/// query_partitioned returns the items hit by the AABB query,
+	/// partitioned into those included in the query body and those
+	/// intersecting with it, in a single traversal.
+	///
+	/// The included items need no more intersection tests, and all
+	/// items under an included subtree are placed in the included
+	/// part directly. Both parts are in the leaves' list order.
+	pub fn query_partitioned<Q: AABBQuery<B>>(
+		&self, q: &Q,
+	) -> (Vec<&V>, Vec<&V>) {
+		let nodes = self.nodes();
+		let mut included = Vec::new();
+		let mut intersecting = Vec::new();
+		let stack = Vec::with_capacity(nodes.depth);
+		for step in nodes.traverse(q, stack) {
+			match step {
+				Step::Include(id) => included
+					.extend(nodes.leaves_of(id).iter().map(|x| &x.value)),
+				Step::Leaf(id, AABBRelation::Include) => {
+					included.push(&self.leaves[id].value)
+				},
+				Step::Leaf(id, _) => {
+					intersecting.push(&self.leaves[id].value)
+				},
+				_ => {},
+			}
+		}
+		(included, intersecting)
+	}
+
+	/// query_contained is query with only the items included in the
+	/// AABB query body, in the same order of query, see also the
+	/// included part of query_partitioned.
+	///
+	/// The leaves intersecting with the query body are skipped, while
+	/// the branches are still pruned and descended in the same way.
+	pub fn query_contained<'a, 'b: 'a>(
+		&'b self, q: &'a impl AABBQuery<B>,
+	) -> impl 'a + Iterator<Item = &'b V> {
+		let nodes = self.nodes();
+		let mut steps = nodes.traverse(q, Vec::with_capacity(nodes.depth));
+		let mut pending: std::slice::Iter<'b, BVHLeaf<B, V>> = [].iter();
+		std::iter::from_fn(move || loop {
+			if let Some(leaf) = pending.next() {
+				return Some(&leaf.value);
+			}
+			match steps.next()? {
+				Step::Include(id) => pending = nodes.leaves_of(id).iter(),
+				Step::Leaf(id, AABBRelation::Include) => {
+					return Some(&nodes.leaves[id].value)
+				},
+				_ => {},
 			}
+		})
+	}
 
-			// TODO(haoran.luo): actually the depth of tree is bounded,
-			// we can also allocate the stack right at first.
-			let mut stack: Vec<usize> = Vec::new();
-			stack.push(self.root);
-			while stack.len() > 0 {
-				let top = stack[stack.len() - 1];
-				let (id, is_branch) = decompose(top);
-				let popstack;
-				if is_branch {
-					let branch = &self.branches[top];
-					match q.check(&branch.bound) {
-						AABBRelation::Interleave => popstack = true,
-						AABBRelation::Intersect => {
-							// XXX: we first put the left node onto the
-							// stack, and later the right node will be
-							// pushed when the left node is popped,
-							// following the post-order traversal.
-							stack.push(branch.left);
-							popstack = false;
-						},
-						AABBRelation::Include => {
-							// XXX: bingo 777! So lucky, we will just
-							// yield all leaves under current subtree.
-							let leftmost = self.leftmost(id);
-							let rightmost = self.rightmost(id);
-							for index in leftmost..=rightmost {
-								yield &self.leaves[index].value;
-							}
-							popstack = true;
-						},
-					}
-				} else {
-					let leaf = &self.leaves[id];
-					match q.check(&leaf.bound) {
-						AABBRelation::Interleave => {},
-						_ => yield &leaf.value,
-					}
-					popstack = true;
-				}
-				if popstack {
-					stack.pop();
-					let mut oldtop = top;
-					while stack.len() > 0 {
-						let newtop = stack[stack.len() - 1];
-						let (index, is_branch) = decompose(newtop);
-						assert!(is_branch);
-						let parent = &self.branches[index];
-						if oldtop == parent.left {
-							stack.push(parent.right);
-							break;
-						} else {
-							stack.pop();
-							oldtop = newtop;
-						}
-					}
-				}
+	/// query_ids is query with the LeafId of each item hit by the
+	/// AABB query, in the same order of query.
+	///
+	/// The ids might be kept after the query, for retrieving or
+	/// mutating the items later with get and get_mut.
+	pub fn query_ids<'a, 'b: 'a>(
+		&'b self, q: &'a impl AABBQuery<B>,
+	) -> impl 'a + Iterator<Item = (LeafId, &'b V)> {
+		let nodes = self.nodes();
+		let mut steps = nodes.traverse(q, Vec::with_capacity(nodes.depth));
+		let mut pending = 0..0;
+		std::iter::from_fn(move || loop {
+			if let Some(id) = pending.next() {
+				return Some((LeafId(id), &nodes.leaves[id].value));
+			}
+			match steps.next()? {
+				Step::Include(id) => {
+					pending = nodes.leftmost(id)..nodes.rightmost(id) + 1
+				},
+				Step::Leaf(id, _) => {
+					return Some((LeafId(id), &nodes.leaves[id].value))
+				},
+				_ => {},
+			}
+		})
+	}
+
+	/// query_sorted_by collects the items hit by the AABB query, and
+	/// sorts them by the key evaluated from their bounds, e.g. the
+	/// distance from the viewer to the bound.
+	///
+	/// The sort is stable, so the items with the same key are in the
+	/// same order of query.
+	pub fn query_sorted_by<'b, K: Ord>(
+		&'b self, q: &impl AABBQuery<B>, key: impl Fn(&B) -> K,
+	) -> Vec<&'b V> {
+		let mut hits: Vec<(K, &'b V)> = self
+			.query_bounds(q)
+			.map(|(bound, value)| (key(bound), value))
+			.collect();
+		hits.sort_by(|a, b| a.0.cmp(&b.0));
+		hits.into_iter().map(|x| x.1).collect()
+	}
+
+	/// query_with_depth is query with the depth of each item hit by
+	/// the AABB query, in the same order of query, which is the
+	/// number of branches on the path from the root to the leaf.
+	///
+	/// Tracking the depth requires walking down the included subtree
+	/// instead of taking its leaves at once, but the bounds under it
+	/// are still not checked.
+	pub fn query_with_depth<'a, 'b: 'a>(
+		&'b self, q: &'a impl AABBQuery<B>,
+	) -> impl 'a + Iterator<Item = (usize, &'b V)> {
+		let nodes = self.nodes();
+		let mut stack = Vec::with_capacity(nodes.depth);
+		if !nodes.leaves.is_empty() {
+			stack.push((nodes.root, 0, false));
+		}
+		std::iter::from_fn(move || loop {
+			let (node, level, included) = stack.pop()?;
+			let relation = match included {
+				true => AABBRelation::Include,
+				false => q.check(nodes.bound(node)),
+			};
+			if relation == AABBRelation::Interleave {
+				continue;
+			}
+			let (id, is_branch) = decompose(node);
+			if !is_branch {
+				return Some((level, &nodes.leaves[id].value));
 			}
+			let branch = &nodes.branches[id];
+			let included = relation == AABBRelation::Include;
+			stack.push((branch.right, level + 1, included));
+			stack.push((branch.left, level + 1, included));
 		})
 	}
 }
+
+/// LeafId is the handle of the leaf in the BVH, which is the index
+/// of the leaf in the leaves' list.
+///
+/// The topology of the BVH is never changed once built, so the id
+/// remains valid for the BVH it is retrieved from, while the value
+/// of the leaf is allowed to be mutated through the id.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LeafId(usize);
+
+impl LeafId {
+	/// index of the leaf in the leaves' list, which is the order of
+	/// BVH::iter.
+	pub fn index(self) -> usize {
+		self.0
+	}
+}
+
+impl<B, V> BVH<B, V> {
+	/// get the value of the leaf with the id, or None if the id is
+	/// out of range, e.g. it is retrieved from another BVH.
+	pub fn get(&self, id: LeafId) -> Option<&V> {
+		self.leaves.get(id.0).map(|leaf| &leaf.value)
+	}
+
+	/// get_mut is get with the value borrowed mutably, the bound of
+	/// the leaf is never mutated so the hierarchy remains valid.
+	pub fn get_mut(&mut self, id: LeafId) -> Option<&mut V> {
+		self.leaves.get_mut(id.0).map(|leaf| &mut leaf.value)
+	}
+}
+
+impl<B, V> BVH<B, V> {
+	/// empty creates the BVH without any leaf, which is also the
+	/// default BVH, and nothing will be hit by the queries on it.
+	pub fn empty() -> Self {
+		Self {
+			root: 0,
+			depth: 0,
+			branches: Vec::new(),
+			leaves: Vec::new(),
+		}
+	}
+
+	/// len returns the number of leaves in the BVH.
+	pub fn len(&self) -> usize {
+		self.leaves.len()
+	}
+
+	/// is_empty checks whether there's no leaf in the BVH.
+	pub fn is_empty(&self) -> bool {
+		self.leaves.is_empty()
+	}
+
+	/// branch_count returns the number of branches in the BVH, which
+	/// is always one less than the leaves unless it is empty.
+	pub fn branch_count(&self) -> usize {
+		self.branches.len()
+	}
+
+	/// depth returns the maximum number of branches on the path from
+	/// the root to a leaf, which is zero for a single leaf.
+	pub fn depth(&self) -> usize {
+		self.depth.saturating_sub(1)
+	}
+
+	/// iter walks all leaves with their bounds without a query, in
+	/// the order of the leaves' list, which is the same order that
+	/// the queries enumerate them.
+	///
+	/// Please notice the leaves are placed by their spatial order on
+	/// building, which is not the order they are specified.
+	pub fn iter(&self) -> impl Iterator<Item = (&B, &V)> {
+		self.leaves.iter().map(|leaf| (&leaf.bound, &leaf.value))
+	}
+
+	/// values walks all values of the leaves, see also iter.
+	pub fn values(&self) -> impl Iterator<Item = &V> {
+		self.leaves.iter().map(|leaf| &leaf.value)
+	}
+
+	/// max_leaf_depth_imbalance returns the difference between the
+	/// depth of the deepest and the shallowest leaves, which is zero
+	/// for a perfectly balanced BVH.
+	pub fn max_leaf_depth_imbalance(&self) -> usize {
+		if self.leaves.is_empty() {
+			return 0;
+		}
+		let mut shallowest = usize::MAX;
+		let mut deepest = 0;
+		let mut stack = Vec::with_capacity(self.depth);
+		stack.push((self.root, 0));
+		while let Some((node, level)) = stack.pop() {
+			let (id, is_branch) = decompose(node);
+			if is_branch {
+				let branch = &self.branches[id];
+				stack.push((branch.left, level + 1));
+				stack.push((branch.right, level + 1));
+			} else {
+				shallowest = min(shallowest, level);
+				deepest = max(deepest, level);
+			}
+		}
+		deepest - shallowest
+	}
+}
+
+impl<B, V> Default for BVH<B, V> {
+	fn default() -> Self {
+		Self::empty()
+	}
+}
+
+cfg_test! {
+	#[test] fn test_bvh_stats() {
+		let leaves = |n: usize| -> Vec<BVHLeaf<(), usize>> {
+			(0..n).map(|i| BVHLeaf { bound: (), value: i }).collect()
+		};
+		let branch = |left: usize, right: usize| BVHBranch {
+			bound: (),
+			left: left,
+			right: right,
+		};
+
+		// ((L0, L1), (L2, L3)) is perfectly balanced.
+		let balanced = BVH::from_nodes(
+			5,
+			vec![branch(0, 2), branch(4, 6), branch(1, 3)],
+			leaves(4),
+		)
+		.unwrap();
+		assert_eq!(balanced.len(), 4);
+		assert!(!balanced.is_empty());
+		assert_eq!(balanced.branch_count(), 3);
+		assert_eq!(balanced.depth(), 2);
+		assert_eq!(balanced.max_leaf_depth_imbalance(), 0);
+
+		// (L0, (L1, (L2, L3))) degenerates into a linked list.
+		let degenerate = BVH::from_nodes(
+			5,
+			vec![branch(4, 6), branch(2, 1), branch(0, 3)],
+			leaves(4),
+		)
+		.unwrap();
+		assert_eq!(degenerate.len(), 4);
+		assert_eq!(degenerate.branch_count(), 3);
+		assert_eq!(degenerate.depth(), 3);
+		assert_eq!(degenerate.max_leaf_depth_imbalance(), 2);
+
+		let single = BVH::from_nodes(0, Vec::new(), leaves(1)).unwrap();
+		assert_eq!(single.len(), 1);
+		assert_eq!(single.depth(), 0);
+		assert_eq!(single.max_leaf_depth_imbalance(), 0);
+
+		let empty = BVH::from_nodes(0, Vec::new(), leaves(0)).unwrap();
+		assert!(empty.is_empty());
+		assert_eq!(empty.branch_count(), 0);
+		assert_eq!(empty.depth(), 0);
+		assert_eq!(empty.max_leaf_depth_imbalance(), 0);
+
+		let bvh = BVH::build(testdata_bvh_i64(1000));
+		assert_eq!(bvh.len(), 1000);
+		assert_eq!(bvh.branch_count(), 999);
+		assert_eq!(bvh.depth(), 10);
+		assert!(bvh.max_leaf_depth_imbalance() <= 1);
+	}
+
+	#[test] fn test_bvh_i64_query_with_depth() {
+		// (L0, (L1, (L2, L3))) with the unit cubes placed along x.
+		let cube = |i: i64| {
+			let p = Vec3::new(i, 0, 0);
+			AABB3::new(p, p + Vec3::new(1, 1, 1))
+		};
+		let span = |lo: i64, hi: i64| cube(lo).extends(&cube(hi));
+		let leaves = (0..4)
+			.map(|i| BVHLeaf { bound: cube(i), value: i as usize })
+			.collect();
+		let branches = vec![
+			BVHBranch { bound: span(0, 3), left: 0, right: 3 },
+			BVHBranch { bound: span(1, 3), left: 2, right: 5 },
+			BVHBranch { bound: span(2, 3), left: 4, right: 6 },
+		];
+		let bvh = BVH::from_nodes(1, branches, leaves).unwrap();
+		let depths = |q: &AABB3<i64>| -> Vec<(usize, usize)> {
+			bvh.query_with_depth(q).map(|(d, v)| (d, *v)).collect()
+		};
+		let everything: Vec<_> = bvh
+			.query_with_depth(&Everything)
+			.map(|(d, v)| (d, *v))
+			.collect();
+		assert_eq!(everything, vec![(1, 0), (2, 1), (3, 2), (3, 3)]);
+
+		// The whole tree is included by the root.
+		let all = AABB3::new(Vec3::new(-1, -1, -1), Vec3::new(5, 2, 2));
+		assert_eq!(depths(&all), everything);
+		let p = Vec3::new(1, 0, 0);
+		let q = AABB3::new(p, p + Vec3::new(2, 1, 1));
+		assert_eq!(depths(&q), vec![(2, 1), (3, 2)]);
+		let p = Vec3::new(10, 0, 0);
+		assert_eq!(depths(&AABB3::new(p, p + p)), vec![]);
+
+		let single = BVH::build(vec![(cube(0), 0)]);
+		let hit: Vec<_> = single.query_with_depth(&Everything).collect();
+		assert_eq!(hit, vec![(0, &0)]);
+		let empty = BVH::<AABB3<i64>, usize>::empty();
+		assert_eq!(empty.query_with_depth(&Everything).count(), 0);
+
+		let bvh = BVH::build(testdata_bvh_i64(1000));
+		for plane in testdata_bvh_plane3_i64(10) {
+			let (d, v): (Vec<usize>, Vec<&usize>) =
+				bvh.query_with_depth(&plane).unzip();
+			assert_eq!(v, bvh.query(&plane).collect::<Vec<_>>());
+			assert!(d.iter().all(|x| *x <= bvh.depth()));
+		}
+	}
+
+	#[test] fn test_bvh_i64_try_build() {
+		let mut items = testdata_bvh_i64(100);
+		let bvh = BVH::try_build(items.clone()).unwrap();
+		assert_bvh_structure(&bvh);
+		assert_eq!(bvh.len(), items.len());
+
+		let p = Vec3::new(1, 2, 3);
+		items[42].0 = AABB3::new(p, p + Vec3::new(4, 0, 5));
+		items[57].0 = AABB3::new(p, p);
+		let err = BVH::try_build(items.clone()).err().unwrap();
+		assert_eq!(err, BuildError::DegenerateBound(42));
+		assert_eq!(err.to_string(), "degenerate bound of leaf 42");
+		assert_eq!(BVH::build(items).len(), 100);
+		assert!(BVH::<AABB3<i64>, usize>::try_build(Vec::new()).is_ok());
+	}
+
+	#[test] fn test_bvh_i64_empty() {
+		let empty = BVH::<AABB3<i64>, usize>::empty();
+		assert_bvh_structure(&empty);
+		assert_eq!(empty.len(), 0);
+		assert!(empty.is_empty());
+		assert_eq!(empty.query(&Everything).count(), 0);
+		assert_eq!(empty.count(&Everything), 0);
+		assert!(!empty.any(&Everything));
+		assert!(empty.find_first(&Everything).is_none());
+		assert_eq!(empty.iter().count(), 0);
+		let plane = Plane3::new(Vec3::new(0, 0, 0), Vec3::new(1, 1, 1));
+		assert_eq!(empty.query(&plane).count(), 0);
+
+		let default: BVH<AABB3<i64>, usize> = Default::default();
+		assert_bvh_structure(&default);
+		assert_eq!(default.root, 0);
+		assert_eq!(default.branch_count(), 0);
+		assert_eq!(default.query(&Everything).count(), 0);
+	}
+
+	#[test] fn test_bvh_i64_iter() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		assert_eq!(bvh.iter().count(), bvh.len());
+		assert!(bvh.values().eq(bvh.query(&Everything)));
+		assert!(bvh.iter().map(|x| x.1).eq(bvh.values()));
+		let mut values: Vec<usize> = bvh.values().copied().collect();
+		values.sort();
+		assert!(values.into_iter().eq(0..items.len()));
+		for (bound, value) in bvh.iter() {
+			assert_eq!(
+				Vec3::<(i64, i64)>::from(*bound),
+				Vec3::<(i64, i64)>::from(items[*value].0),
+			);
+		}
+
+		// The leaves are streamed in the order they are placed.
+		let leaves = (0..3).map(|i| BVHLeaf { bound: (), value: i });
+		let branches = vec![
+			BVHBranch { bound: (), left: 0, right: 2 },
+			BVHBranch { bound: (), left: 1, right: 4 },
+		];
+		let bvh = BVH::from_nodes(3, branches, leaves.collect()).unwrap();
+		assert!(bvh.values().copied().eq(0..3));
+		assert_eq!(bvh.iter().count(), bvh.len());
+	}
+}
+
+// split_median splits the leaves into halves along the axis of the
+// greatest centroid spread, and prefer the lower axis when there's a
+// tie. The order is sorted along the axis and the size of the lower
+// part is returned.
+fn split_median<B: Bounded>(bounds: &[B], order: &mut [usize]) -> usize {
+	let mut axis = 0;
+	let mut spread = None;
+	for i in 0..B::AXES {
+		let mut lo = bounds[order[0]].centroid(i);
+		let mut hi = lo;
+		for &j in order.iter() {
+			let c = bounds[j].centroid(i);
+			lo = min(lo, c);
+			hi = max(hi, c);
+		}
+		let d = hi - lo;
+		if spread.is_none_or(|s| d > s) {
+			axis = i;
+			spread = Some(d);
+		}
+	}
+	order.sort_by_key(|&j| bounds[j].centroid(axis));
+	order.len() / 2
+}
+
+// SAH_BINS is the number of bins that the leaves are divided into on
+// each axis, whose boundaries are the candidates of splitting.
+const SAH_BINS: usize = 16;
+
+// split_sah splits the leaves at the candidate minimizing the surface
+// area heuristic cost SA(left)*n_left + SA(right)*n_right.
+//
+// The leaves sorted along each axis are divided into bins of equal
+// counts, so no arithmetic is needed on the centroids. It falls back
+// to split_median when the centroids are all the same.
+fn split_sah<B>(bounds: &[B], order: &mut [usize]) -> usize
+where
+	B: Bounded + SurfaceArea + Clone,
+	B::Area:
+		Copy + Add<Output = B::Area> + Mul<Output = B::Area> + From<u32>,
+{
+	let n = order.len();
+	let mut best: Option<(B::Area, usize, usize)> = None;
+	let mut suffix = Vec::with_capacity(n);
+	for axis in 0..B::AXES {
+		order.sort_by_key(|&j| bounds[j].centroid(axis));
+		let first = bounds[order[0]].centroid(axis);
+		if bounds[order[n - 1]].centroid(axis) == first {
+			continue;
+		}
+
+		// suffix[i] is the union of the leaves from i to the end.
+		suffix.clear();
+		suffix.push(bounds[order[n - 1]].clone());
+		for &j in order[..n - 1].iter().rev() {
+			let bound = bounds[j].union(&suffix[suffix.len() - 1]);
+			suffix.push(bound);
+		}
+		suffix.reverse();
+
+		let mut prefix = bounds[order[0]].clone();
+		let mut next = 1;
+		for k in 1..min(SAH_BINS, n) {
+			let mid = k * n / min(SAH_BINS, n);
+			while next < mid {
+				prefix = prefix.union(&bounds[order[next]]);
+				next += 1;
+			}
+			let cost = prefix.surface_area() * B::Area::from(mid as u32)
+				+ suffix[mid].surface_area()
+					* B::Area::from((n - mid) as u32);
+			if best.is_none_or(|(c, _, _)| cost < c) {
+				best = Some((cost, axis, mid));
+			}
+		}
+	}
+	match best {
+		Some((_, axis, mid)) => {
+			order.sort_by_key(|&j| bounds[j].centroid(axis));
+			mid
+		},
+		None => split_median(bounds, order),
+	}
+}
+
+// Baker holds the states of partitioning leaves and appending the
+// branches, the split function partitions the leaves of each branch,
+// and the hook is called with the bound, depth and whether it is a
+// branch, on completing each node.
+struct Baker<'a, B, S, F> {
+	bounds: &'a [B],
+	branches: &'a mut Vec<BVHBranch<B>>,
+	base: usize,
+	split: S,
+	hook: F,
+}
+
+impl<'a, B, S, F> Baker<'a, B, S, F>
+where
+	B: Bounded + Clone,
+	S: FnMut(&[B], &mut [usize]) -> usize,
+	F: FnMut(&B, usize, bool),
+{
+	// node partitions the leaves specified by order recursively, and
+	// returns the encoded id of the root node and its bound.
+	//
+	// The leaves in order will be placed starting from offset, so the
+	// mid-order traversal of the subtree visits offset, offset+1, ...,
+	// which is required by leftmost and rightmost.
+	fn node(
+		&mut self, order: &mut [usize], offset: usize, depth: usize,
+	) -> (usize, B) {
+		let bounds = self.bounds;
+		if order.len() == 1 {
+			let bound = bounds[order[0]].clone();
+			(self.hook)(&bound, depth, false);
+			return (offset << 1, bound);
+		}
+
+		let mid = (self.split)(bounds, order);
+		let (lorder, rorder) = order.split_at_mut(mid);
+		let (left, lbound) = self.node(lorder, offset, depth + 1);
+		let (right, rbound) = self.node(rorder, offset + mid, depth + 1);
+		let bound = lbound.union(&rbound);
+		self.branches.push(BVHBranch {
+			bound: bound.clone(),
+			left: left,
+			right: right,
+		});
+		(self.hook)(&bound, depth, true);
+		(((self.branches.len() - 1 - self.base) << 1) | 1, bound)
+	}
+}
+
+// bake builds the leaves and appends the nodes to the lists, and the
+// nodes will be indexed relative to the original end of the lists.
+// The encoded id of the root node and the depth are returned.
+fn bake<B: Bounded + Clone, V>(
+	items: Vec<(B, V)>, branches: &mut Vec<BVHBranch<B>>,
+	leaves: &mut Vec<BVHLeaf<B, V>>,
+	split: impl FnMut(&[B], &mut [usize]) -> usize,
+	mut hook: impl FnMut(&B, usize, bool),
+) -> (usize, usize) {
+	if items.is_empty() {
+		return (0, 0);
+	}
+	let mut depth = 0;
+	let hook = |bound: &B, level: usize, is_branch: bool| {
+		if !is_branch {
+			depth = max(depth, level + 1);
+		}
+		hook(bound, level, is_branch)
+	};
+	let bounds: Vec<B> = items.iter().map(|x| x.0.clone()).collect();
+	let mut order: Vec<usize> = (0..items.len()).collect();
+	let mut baker = Baker {
+		bounds: &bounds,
+		base: branches.len(),
+		branches: branches,
+		split: split,
+		hook: hook,
+	};
+	let (root, _) = baker.node(&mut order, 0, 0);
+
+	// Place the items into the leaves' list by their ranks.
+	let mut rank = vec![0; items.len()];
+	for (i, &j) in order.iter().enumerate() {
+		rank[j] = i;
+	}
+	let mut ranked: Vec<(usize, (B, V))> =
+		rank.into_iter().zip(items).collect();
+	ranked.sort_by_key(|x| x.0);
+	leaves.extend(ranked.into_iter().map(|(_, (bound, value))| BVHLeaf {
+		bound: bound,
+		value: value,
+	}));
+	(root, depth)
+}
+
+impl<B: Bounded + Clone, V> BVH<B, V> {
+	/// build constructs the BVH from the specified leaves.
+	///
+	/// The leaves are partitioned top-down, by splitting them into
+	/// halves along the axis of greatest centroid spread, until there
+	/// remains only one leaf in each part.
+	pub fn build(items: Vec<(B, V)>) -> Self {
+		let mut branches = Vec::new();
+		let mut leaves = Vec::new();
+		let (root, depth) = bake(
+			items,
+			&mut branches,
+			&mut leaves,
+			split_median,
+			|_, _, _| {},
+		);
+		Self {
+			root: root,
+			depth: depth,
+			branches: branches,
+			leaves: leaves,
+		}
+	}
+
+	/// build_with_progress constructs the BVH with the same strategy
+	/// of build, while reporting the progress on completing each node
+	/// of the hierarchy, with the root node reported at last.
+	pub fn build_with_progress(
+		items: Vec<(B, V)>,
+		mut progress: impl FnMut(BuildProgress<B::Area>),
+	) -> Self
+	where
+		B: SurfaceArea,
+		B::Area: Copy + Default + Add<Output = B::Area>,
+	{
+		let mut branches = Vec::new();
+		let mut leaves = Vec::new();
+		let mut placed = 0;
+		let mut cost = B::Area::default();
+		let hook = |bound: &B, depth, is_branch| {
+			if is_branch {
+				cost = cost + bound.surface_area();
+			} else {
+				placed += 1;
+			}
+			progress(BuildProgress {
+				leaves: placed,
+				depth: depth,
+				cost: cost,
+			});
+		};
+		let (root, depth) =
+			bake(items, &mut branches, &mut leaves, split_median, hook);
+		Self {
+			root: root,
+			depth: depth,
+			branches: branches,
+			leaves: leaves,
+		}
+	}
+}
+
+impl<B: Bounded + SurfaceArea + Clone, V> BVH<B, V>
+where
+	B::Area:
+		Copy + Add<Output = B::Area> + Mul<Output = B::Area> + From<u32>,
+{
+	/// build_sah constructs the BVH with the surface area heuristic
+	/// (SAH), which yields hierarchies of higher quality than build,
+	/// at the cost of longer construction time.
+	///
+	/// At each branch, the leaves sorted along each axis are divided
+	/// into bins of equal counts, and the boundary of bins minimizing
+	/// SA(left)*n_left + SA(right)*n_right is chosen as the split.
+	/// The cost is evaluated in B::Area, which must be wide enough to
+	/// hold the surface areas times the number of leaves.
+	pub fn build_sah(items: Vec<(B, V)>) -> Self {
+		let mut branches = Vec::new();
+		let mut leaves = Vec::new();
+		let (root, depth) = bake(
+			items,
+			&mut branches,
+			&mut leaves,
+			split_sah,
+			|_, _, _| {},
+		);
+		Self {
+			root: root,
+			depth: depth,
+			branches: branches,
+			leaves: leaves,
+		}
+	}
+}
+
+/// BuildError is the reason of rejecting the leaves on building the
+/// BVH with validation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BuildError {
+	/// DegenerateBound means the bound of the leaf at the index of
+	/// the specified items has zero volume.
+	DegenerateBound(usize),
+}
+
+impl fmt::Display for BuildError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			BuildError::DegenerateBound(index) => {
+				write!(f, "degenerate bound of leaf {}", index)
+			},
+		}
+	}
+}
+
+impl std::error::Error for BuildError {}
+
+impl<T: Ord + Copy, V> BVH<AABB3<T>, V>
+where
+	AABB3<T>: Bounded + Clone,
+{
+	/// try_build constructs the BVH in the same way of build, after
+	/// validating that none of the leaves has a degenerate bound.
+	///
+	/// Zero-volume bounds are usually included by mistake, and they
+	/// lead to poor hierarchies silently. The error reports the index
+	/// of the first such leaf in the specified items.
+	pub fn try_build(
+		items: Vec<(AABB3<T>, V)>,
+	) -> Result<Self, BuildError> {
+		if let Some(index) = items.iter().position(|x| x.0.is_degraded()) {
+			return Err(BuildError::DegenerateBound(index));
+		}
+		Ok(Self::build(items))
+	}
+}
+
+/// Leaf is the object carrying both its bound and value, and the
+/// whole object can be stored in the BVH, so that the bound and the
+/// value are never desynchronized.
+pub trait Leaf {
+	type Bound;
+	type Value;
+
+	fn bound(&self) -> Self::Bound;
+
+	fn value(&self) -> &Self::Value;
+}
+
+impl<L: Leaf> BVH<L::Bound, L>
+where
+	L::Bound: Bounded + Clone,
+{
+	/// from_leaf_objects constructs the BVH with the same strategy of
+	/// build, extracting the bounds from leaf objects.
+	pub fn from_leaf_objects(items: Vec<L>) -> Self {
+		Self::build(items.into_iter().map(|x| (x.bound(), x)).collect())
+	}
+}
+
+/// BuildProgress is reported periodically while building the BVH.
+#[derive(Copy, Clone, Debug)]
+pub struct BuildProgress<C> {
+	/// leaves is the number of leaves that have been placed.
+	pub leaves: usize,
+
+	/// depth of the node just completed, with the root at depth 0.
+	pub depth: usize,
+
+	/// cost is the running SAH cost of the hierarchy, which is the
+	/// sum of surface areas of completed branches. This is the cost
+	/// of traversing the hierarchy with uniformly distributed rays,
+	/// up to a constant factor.
+	pub cost: C,
+}
+
+/// TraceEvent is the action taken while traversing the BVH, with
+/// the index of the node in the branches' or the leaves' list.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TraceEvent {
+	/// EnterBranch means the branch intersects with the query body,
+	/// and its children are going to be visited.
+	EnterBranch(usize),
+
+	/// VisitLeaf means the leaf is hit by the query body.
+	VisitLeaf(usize),
+
+	/// IncludeSubtree means the branch is included in the query body,
+	/// and all leaves under it are hit without being visited.
+	IncludeSubtree(usize),
+
+	/// PruneBranch means the branch is disjoint from the query body,
+	/// and its subtree is skipped.
+	PruneBranch(usize),
+
+	/// PruneLeaf means the leaf is disjoint from the query body.
+	PruneLeaf(usize),
+}
+
+/// Query is the iterator over the items hit by the query body, see
+/// also BVH::query for the order of items.
+///
+/// The leaves of the included subtree are flushed from the pending
+/// range, before walking further into the hierarchy.
+struct Query<'n, B, V, Q> {
+	steps: Traversal<'n, B, V, Q>,
+	pending: std::slice::Iter<'n, BVHLeaf<B, V>>,
+}
+
+impl<'n, B, V, Q: AABBQuery<B>> Query<'n, B, V, Q> {
+	// next_leaf advances to the next leaf hit by the query body.
+	fn next_leaf(&mut self) -> Option<&'n BVHLeaf<B, V>> {
+		if let Some(leaf) = self.pending.next() {
+			return Some(leaf);
+		}
+		loop {
+			match self.steps.next()? {
+				Step::Include(id) => {
+					// XXX: bingo 777! So lucky, we will just yield all
+					// leaves under current subtree.
+					let nodes = self.steps.nodes;
+					self.pending = nodes.leaves_of(id).iter();
+					if let Some(leaf) = self.pending.next() {
+						return Some(leaf);
+					}
+				},
+				Step::Leaf(id, _) => {
+					return Some(&self.steps.nodes.leaves[id])
+				},
+				_ => {},
+			}
+		}
+	}
+}
+
+impl<'n, B, V, Q: AABBQuery<B>> Iterator for Query<'n, B, V, Q> {
+	type Item = &'n V;
+
+	fn next(&mut self) -> Option<&'n V> {
+		Some(&self.next_leaf()?.value)
+	}
+}
+
+impl<'n, B, V> BVHNodes<'n, B, V> {
+	fn query<Q: AABBQuery<B>>(self, q: Q) -> Query<'n, B, V, Q> {
+		Query {
+			steps: self.traverse(q, Vec::with_capacity(self.depth)),
+			pending: [].iter(),
+		}
+	}
+
+	// visit invokes the callback with each item hit by the query, in
+	// the same order of query, and the stack is given back after the
+	// traversal for reusing.
+	fn visit<Q: AABBQuery<B> + ?Sized>(
+		self, q: &Q, stack: Vec<usize>, mut f: impl FnMut(&'n V),
+	) -> Vec<usize> {
+		let mut steps = self.traverse(q, stack);
+		for step in &mut steps {
+			match step {
+				Step::Include(id) => {
+					self.leaves_of(id).iter().for_each(|x| f(&x.value))
+				},
+				Step::Leaf(id, _) => f(&self.leaves[id].value),
+				_ => {},
+			}
+		}
+		steps.stack
+	}
+}
+
+impl<B, V> BVH<B, V> {
+	/// for_each invokes the callback with each item hit by the AABB
+	/// query, in the same order of query.
+	///
+	/// The items are pushed to the callback during the traversal,
+	/// which saves the states kept by the iterator of query.
+	pub fn for_each<'b>(
+		&'b self, q: &impl AABBQuery<B>, f: impl FnMut(&'b V),
+	) {
+		let nodes = self.nodes();
+		nodes.visit(q, Vec::with_capacity(nodes.depth), f);
+	}
+
+	/// count the items hit by the AABB query without collecting them.
+	///
+	/// The leaves under an included subtree are counted at once by
+	/// the range of leaves, without visiting them one by one.
+	pub fn count(&self, q: &impl AABBQuery<B>) -> usize {
+		let nodes = self.nodes();
+		let mut count = 0;
+		for step in nodes.traverse(q, Vec::with_capacity(nodes.depth)) {
+			match step {
+				Step::Include(id) => {
+					count += nodes.rightmost(id) - nodes.leftmost(id) + 1
+				},
+				Step::Leaf(_, _) => count += 1,
+				_ => {},
+			}
+		}
+		count
+	}
+
+	/// find_first returns the first item hit by the AABB query, which
+	/// is the first one enumerated by query, or None if nothing is hit.
+	///
+	/// The traversal stops right at the first hit leaf, or the first
+	/// included subtree with its leftmost leaf taken.
+	pub fn find_first<'b>(
+		&'b self, q: &impl AABBQuery<B>,
+	) -> Option<&'b V> {
+		let nodes = self.nodes();
+		for step in nodes.traverse(q, Vec::with_capacity(nodes.depth)) {
+			match step {
+				Step::Include(id) => {
+					return Some(&self.leaves[nodes.leftmost(id)].value)
+				},
+				Step::Leaf(id, _) => return Some(&self.leaves[id].value),
+				_ => {},
+			}
+		}
+		None
+	}
+
+	/// any checks whether anything is hit by the AABB query, with the
+	/// traversal stopped at the first hit, see also find_first.
+	pub fn any(&self, q: &impl AABBQuery<B>) -> bool {
+		self.find_first(q).is_some()
+	}
+
+	/// query_trace records the actions taken while traversing the BVH
+	/// with the query body, in the traversal order.
+	///
+	/// This is mainly for locking down the behavior of traversal, so
+	/// that accidental changes can be caught by golden tests.
+	pub fn query_trace<Q: AABBQuery<B>>(&self, q: &Q) -> Vec<TraceEvent> {
+		self.nodes()
+			.traverse(q, Vec::with_capacity(self.depth))
+			.map(|step| match step {
+				Step::Prune(node) => match decompose(node) {
+					(id, true) => TraceEvent::PruneBranch(id),
+					(id, false) => TraceEvent::PruneLeaf(id),
+				},
+				Step::Enter(id) => TraceEvent::EnterBranch(id),
+				Step::Include(id) => TraceEvent::IncludeSubtree(id),
+				Step::Leaf(id, _) => TraceEvent::VisitLeaf(id),
+			})
+			.collect()
+	}
+}
+
+// PAR_LEVELS is the number of levels from the root in which the
+// subtrees are traversed in parallel, and the deeper subtrees are
+// traversed sequentially, since splitting them is not worthy.
+#[cfg(feature = "rayon")]
+const PAR_LEVELS: usize = 8;
+
+#[cfg(feature = "rayon")]
+impl<'n, B: Sync, V: Sync> BVHNodes<'n, B, V> {
+	// par_visit collects the items hit by the query in the subtree of
+	// the node with the encoded id, with both subtrees of a branch
+	// traversed in parallel until the level reaches PAR_LEVELS.
+	fn par_visit<Q: AABBQuery<B> + Sync + ?Sized>(
+		self, q: &Q, node: usize, level: usize,
+	) -> Vec<&'n V> {
+		let (id, is_branch) = decompose(node);
+		if !is_branch || level >= PAR_LEVELS {
+			let mut result = Vec::new();
+			let nodes = BVHNodes { root: node, ..self };
+			nodes.visit(q, Vec::with_capacity(self.depth), |x| {
+				result.push(x)
+			});
+			return result;
+		}
+		let branch = &self.branches[id];
+		match q.check(&branch.bound) {
+			AABBRelation::Interleave => Vec::new(),
+			AABBRelation::Include => {
+				self.leaves_of(id).iter().map(|x| &x.value).collect()
+			},
+			AABBRelation::Intersect => {
+				let (mut left, right) = rayon::join(
+					|| self.par_visit(q, branch.left, level + 1),
+					|| self.par_visit(q, branch.right, level + 1),
+				);
+				left.extend(right);
+				left
+			},
+		}
+	}
+}
+
+#[cfg(feature = "rayon")]
+impl<B: Sync, V: Sync> BVH<B, V> {
+	/// par_query collects the items hit by the AABB query, with the
+	/// traversal split across the rayon thread pool.
+	///
+	/// The subtrees of the branches near the root are traversed in
+	/// parallel, and their results are concatenated in order, so the
+	/// items are in the same order of query.
+	pub fn par_query(&self, q: &(impl AABBQuery<B> + Sync)) -> Vec<&V> {
+		let nodes = self.nodes();
+		nodes.par_visit(q, nodes.root, 0)
+	}
+}
+
+impl<B, V> BVH<B, V> {
+	/// nearest returns the item whose bound is the closest to the
+	/// point, or None if the BVH is empty.
+	///
+	/// The nodes are visited best-first, ordered by the distance from
+	/// the point to their bounds. Since the distance to a branch never
+	/// exceeds those to the leaves under it, the first leaf reached is
+	/// the nearest one, and the subtrees farther than it are pruned
+	/// without being visited. Ties are resolved by the order in the
+	/// leaves' list, so the result is deterministic.
+	pub fn nearest<P>(&self, point: P) -> Option<&V>
+	where
+		B: PointDistance<P>,
+	{
+		let nodes = self.nodes();
+		if nodes.leaves.is_empty() {
+			return None;
+		}
+		// XXX: branches are ordered before leaves at the same distance,
+		// so that all leaves at the distance are in the heap when the
+		// first of them is popped, and the foremost one wins.
+		let entry = |node: usize| {
+			let (_, is_branch) = decompose(node);
+			Reverse((
+				nodes.bound(node).distance2(&point),
+				!is_branch,
+				node,
+			))
+		};
+		let mut heap = BinaryHeap::with_capacity(nodes.depth * 2);
+		heap.push(entry(nodes.root));
+		while let Some(Reverse((_, _, node))) = heap.pop() {
+			let (id, is_branch) = decompose(node);
+			if !is_branch {
+				return Some(&nodes.leaves[id].value);
+			}
+			let branch = &nodes.branches[id];
+			heap.push(entry(branch.left));
+			heap.push(entry(branch.right));
+		}
+		None
+	}
+
+	/// k_nearest returns at most k items whose bounds are the closest
+	/// to the point, sorted nearest-first.
+	///
+	/// The k closest leaves found so far are kept in a max-heap, and
+	/// the branches farther than the worst of them are pruned once
+	/// there're k leaves found. The nearer child is always visited
+	/// first so that the worst distance shrinks quickly. Ties are
+	/// resolved by the order in the leaves' list, so the result is
+	/// deterministic.
+	pub fn k_nearest<P>(&self, point: P, k: usize) -> Vec<&V>
+	where
+		B: PointDistance<P>,
+	{
+		let nodes = self.nodes();
+		if k == 0 || nodes.leaves.is_empty() {
+			return Vec::new();
+		}
+		let mut heap: BinaryHeap<(B::Distance, usize)> =
+			BinaryHeap::with_capacity(k + 1);
+		let mut stack = Vec::with_capacity(nodes.depth + 1);
+		stack
+			.push((nodes.bound(nodes.root).distance2(&point), nodes.root));
+		while let Some((dist, node)) = stack.pop() {
+			// XXX: a branch at the same distance with the worst one
+			// might still hold a leaf preceding it, so it is pruned
+			// only when it is strictly farther.
+			if heap.len() == k && dist > heap.peek().unwrap().0 {
+				continue;
+			}
+			let (id, is_branch) = decompose(node);
+			if !is_branch {
+				heap.push((dist, id));
+				if heap.len() > k {
+					heap.pop();
+				}
+				continue;
+			}
+			let branch = &nodes.branches[id];
+			let left = nodes.bound(branch.left).distance2(&point);
+			let right = nodes.bound(branch.right).distance2(&point);
+			if left <= right {
+				stack.push((right, branch.right));
+				stack.push((left, branch.left));
+			} else {
+				stack.push((left, branch.left));
+				stack.push((right, branch.right));
+			}
+		}
+		heap.into_sorted_vec()
+			.into_iter()
+			.map(|(_, id)| &nodes.leaves[id].value)
+			.collect()
+	}
+}
+
+/// QueryRunner replays queries over a BVH with a callback, and the
+/// traversal stack is kept in the runner and reused by every query,
+/// so that the allocation is amortized.
+pub struct QueryRunner<'a, B, V> {
+	bvh: &'a BVH<B, V>,
+	stack: RefCell<Vec<usize>>,
+}
+
+impl<'a, B, V> QueryRunner<'a, B, V> {
+	pub fn new(bvh: &'a BVH<B, V>) -> Self {
+		Self {
+			bvh: bvh,
+			stack: RefCell::new(Vec::with_capacity(bvh.depth)),
+		}
+	}
+
+	/// run the query and invoke the callback with each hit item, in
+	/// the same order of BVH::query.
+	pub fn run(&self, q: &impl AABBQuery<B>, f: impl FnMut(&'a V)) {
+		let stack = self.bvh.nodes().visit(q, self.stack.take(), f);
+		self.stack.replace(stack);
+	}
+}
+
+/// QueryCursor issues queries over a BVH in bulk, with both the
+/// traversal stack and the list of hit items kept in the cursor and
+/// reused by every query, so that the allocation is amortized.
+pub struct QueryCursor<'a, B, V> {
+	bvh: &'a BVH<B, V>,
+	stack: Vec<usize>,
+	result: Vec<&'a V>,
+}
+
+impl<B, V> BVH<B, V> {
+	/// query_cursor creates a cursor for issuing many queries, see
+	/// also QueryCursor.
+	pub fn query_cursor(&self) -> QueryCursor<'_, B, V> {
+		QueryCursor {
+			bvh: self,
+			stack: Vec::with_capacity(self.depth),
+			result: Vec::new(),
+		}
+	}
+}
+
+impl<'a, B, V> QueryCursor<'a, B, V> {
+	/// query invokes the callback with each item hit by the query,
+	/// in the same order of BVH::query.
+	pub fn query(&mut self, q: &impl AABBQuery<B>, f: impl FnMut(&'a V)) {
+		let stack = std::mem::take(&mut self.stack);
+		self.stack = self.bvh.nodes().visit(q, stack, f);
+	}
+
+	/// collect the items hit by the query, in the same order of
+	/// BVH::query. The items are valid until the next query.
+	pub fn collect(&mut self, q: &impl AABBQuery<B>) -> &[&'a V] {
+		let mut result = std::mem::take(&mut self.result);
+		result.clear();
+		self.query(q, |x| result.push(x));
+		self.result = result;
+		&self.result
+	}
+}
+
+/// BVHForest queries several BVHs together as if they were a single
+/// one, e.g. the scene split across several baked assets, without
+/// merging them into one BVH.
+pub struct BVHForest<'a, B, V> {
+	trees: &'a [&'a BVH<B, V>],
+}
+
+impl<'a, B, V> BVHForest<'a, B, V> {
+	pub fn new(trees: &'a [&'a BVH<B, V>]) -> Self {
+		Self { trees: trees }
+	}
+
+	/// query for all items hit by the AABB query in every BVH, which
+	/// are the items of BVH::query of each BVH chained in the order
+	/// of the BVHs, and each BVH is traversed lazily.
+	pub fn query<'q>(
+		&self, q: &'q impl AABBQuery<B>,
+	) -> impl 'q + Iterator<Item = &'a V>
+	where
+		'a: 'q,
+	{
+		self.trees.iter().flat_map(move |bvh| bvh.query(q))
+	}
+}
+
+// WideBranch is the branch of WideBVH, whose children are the range
+// of the children list, and whose leaves are the range of the leaves
+// in its subtree.
+struct WideBranch<B> {
+	bound: B,
+	children: (usize, usize),
+	leaves: (usize, usize),
+}
+
+/// WideBVH is the BVH whose branches hold up to a fanout number of
+/// children, which is shallower than the binary BVH with the same
+/// leaves, for reducing the pointer chasing of wide static scenes.
+///
+/// It is built by collapsing the branches of the binary BVH, so the
+/// leaves are in the same order, and the queries enumerate the items
+/// in the same order of BVH::query.
+pub struct WideBVH<B, V> {
+	root: usize,
+	depth: usize,
+	fanout: usize,
+	branches: Vec<WideBranch<B>>,
+	children: Vec<usize>,
+	leaves: Vec<BVHLeaf<B, V>>,
+}
+
+impl<B: Bounded + Clone, V> BVH<B, V> {
+	/// build_with_fanout constructs the WideBVH from the specified
+	/// leaves, whose branches hold up to fanout children, which must
+	/// be at least 2. The binary BVH built by build is collapsed, by
+	/// repeatedly replacing the child with the most leaves by its own
+	/// children, until there're fanout children or only leaves.
+	///
+	/// Unlike build, the result is a WideBVH rather than a BVH, which
+	/// is static and provides only len, is_empty and query.
+	pub fn build_with_fanout(
+		items: Vec<(B, V)>, fanout: usize,
+	) -> WideBVH<B, V> {
+		assert!(fanout >= 2, "fanout {} is less than 2", fanout);
+		let bvh = Self::build(items);
+		let mut branches = Vec::new();
+		let mut children = Vec::new();
+		let (root, depth) = match bvh.leaves.len() {
+			0 => (0, 0),
+			_ => widen(
+				bvh.nodes(),
+				bvh.root,
+				fanout,
+				&mut branches,
+				&mut children,
+			),
+		};
+		WideBVH {
+			root: root,
+			depth: depth,
+			fanout: fanout,
+			branches: branches,
+			children: children,
+			leaves: bvh.leaves,
+		}
+	}
+}
+
+// collapse the subtree of the node with the encoded id in
+// post-order, where split returns the nodes replacing the node as
+// its children in the collapsed hierarchy, or None for turning it
+// into a leaf, and join creates the collapsed node from the node
+// and its collapsed children, which are empty for a leaf.
+fn collapse<N>(
+	node: usize, split: &impl Fn(usize) -> Option<Vec<usize>>,
+	join: &mut impl FnMut(usize, Vec<N>) -> N,
+) -> N {
+	let children = match split(node) {
+		Some(frontier) => frontier
+			.into_iter()
+			.map(|child| collapse(child, split, join))
+			.collect(),
+		None => Vec::new(),
+	};
+	join(node, children)
+}
+
+// widen collapses the subtree of the node with the encoded id into
+// the branches of WideBVH, with its children placed before it, and
+// returns the encoded id of the collapsed node and its depth.
+fn widen<B: Clone, V>(
+	nodes: BVHNodes<'_, B, V>, node: usize, fanout: usize,
+	branches: &mut Vec<WideBranch<B>>, children: &mut Vec<usize>,
+) -> (usize, usize) {
+	let size = |node: usize| {
+		let (lo, hi) = nodes.span(node);
+		hi - lo
+	};
+	let split = |node: usize| {
+		let (id, is_branch) = decompose(node);
+		if !is_branch {
+			return None;
+		}
+		let branch = &nodes.branches[id];
+		let mut frontier = vec![branch.left, branch.right];
+		while frontier.len() < fanout {
+			let widest = (0..frontier.len())
+				.filter(|&i| decompose(frontier[i]).1)
+				.max_by_key(|&i| (size(frontier[i]), Reverse(i)));
+			let i = match widest {
+				Some(i) => i,
+				None => break,
+			};
+			let (id, _) = decompose(frontier[i]);
+			let split = &nodes.branches[id];
+			frontier.splice(i..=i, [split.left, split.right]);
+		}
+		Some(frontier)
+	};
+	collapse(node, &split, &mut |node, collapsed: Vec<_>| {
+		if collapsed.is_empty() {
+			return (node, 1);
+		}
+		let start = children.len();
+		children.extend(collapsed.iter().map(|x| x.0));
+		branches.push(WideBranch {
+			bound: nodes.bound(node).clone(),
+			children: (start, children.len()),
+			leaves: nodes.span(node),
+		});
+		let depth = collapsed.iter().map(|x| x.1).max().unwrap();
+		(((branches.len() - 1) << 1) | 1, depth + 1)
+	})
+}
+
+impl<B, V> WideBVH<B, V> {
+	/// len returns the number of leaves in the WideBVH.
+	pub fn len(&self) -> usize {
+		self.leaves.len()
+	}
+
+	/// is_empty checks whether there's no leaf in the WideBVH.
+	pub fn is_empty(&self) -> bool {
+		self.leaves.is_empty()
+	}
+
+	/// query for all items hit by the AABB query, with the same
+	/// semantic and order of BVH::query.
+	pub fn query<'a, 'b: 'a>(
+		&'b self, q: &'a impl AABBQuery<B>,
+	) -> impl 'a + Iterator<Item = &'b V> {
+		let mut stack = Vec::with_capacity(self.depth * self.fanout);
+		if !self.is_empty() {
+			stack.push(self.root);
+		}
+		let mut pending: std::slice::Iter<'b, BVHLeaf<B, V>> = [].iter();
+		std::iter::from_fn(move || loop {
+			if let Some(leaf) = pending.next() {
+				return Some(&leaf.value);
+			}
+			let (id, is_branch) = decompose(stack.pop()?);
+			if !is_branch {
+				let leaf = &self.leaves[id];
+				if q.check(&leaf.bound) != AABBRelation::Interleave {
+					return Some(&leaf.value);
+				}
+				continue;
+			}
+			let branch = &self.branches[id];
+			match q.check(&branch.bound) {
+				AABBRelation::Interleave => {},
+				AABBRelation::Include => {
+					let (lo, hi) = branch.leaves;
+					pending = self.leaves[lo..hi].iter();
+				},
+				AABBRelation::Intersect => {
+					let (lo, hi) = branch.children;
+					stack.extend(self.children[lo..hi].iter().rev());
+				},
+			}
+		})
+	}
+}
+
+/// BatchedBVH is the BVH whose leaves hold up to a maximum number of
+/// items, which is shallower than the BVH with one item per leaf,
+/// trading the depth of the hierarchy for the linear scans in the
+/// fat leaves.
+///
+/// The leaves of BVH are the ranges of the items, which are placed
+/// in the same order of the leaves built by BVH::build, so the
+/// queries enumerate the items in the same order of BVH::query.
+pub struct BatchedBVH<B, V> {
+	bvh: BVH<B, (usize, usize)>,
+	items: Vec<BVHLeaf<B, V>>,
+}
+
+impl<B: Bounded + Clone, V> BVH<B, V> {
+	/// build_batched constructs the BatchedBVH from the specified
+	/// leaves, whose leaves hold up to max_per_leaf items, which must
+	/// be at least 1. The subtrees of the binary BVH built by build
+	/// are collapsed into single leaves while they hold no more than
+	/// max_per_leaf items, so max_per_leaf of 1 keeps the hierarchy.
+	pub fn build_batched(
+		items: Vec<(B, V)>, max_per_leaf: usize,
+	) -> BatchedBVH<B, V> {
+		assert!(
+			max_per_leaf >= 1,
+			"max_per_leaf {} is less than 1",
+			max_per_leaf,
+		);
+		let bvh = Self::build(items);
+		let mut branches = Vec::new();
+		let mut leaves = Vec::new();
+		let root = match bvh.leaves.len() {
+			0 => 0,
+			_ => batch(
+				bvh.nodes(),
+				bvh.root,
+				max_per_leaf,
+				&mut branches,
+				&mut leaves,
+			),
+		};
+		BatchedBVH {
+			bvh: BVH::from_nodes(root, branches, leaves).unwrap(),
+			items: bvh.leaves,
+		}
+	}
+}
+
+// batch collapses the subtree of the node with the encoded id into
+// the nodes of BatchedBVH, with the children placed before it, and
+// returns the encoded id of the collapsed node.
+fn batch<B: Clone, V>(
+	nodes: BVHNodes<'_, B, V>, node: usize, max_per_leaf: usize,
+	branches: &mut Vec<BVHBranch<B>>,
+	leaves: &mut Vec<BVHLeaf<B, (usize, usize)>>,
+) -> usize {
+	let split = |node: usize| {
+		let (lo, hi) = nodes.span(node);
+		if hi - lo <= max_per_leaf {
+			return None;
+		}
+		let branch = &nodes.branches[decompose(node).0];
+		Some(vec![branch.left, branch.right])
+	};
+	collapse(node, &split, &mut |node, collapsed: Vec<_>| {
+		let bound = nodes.bound(node).clone();
+		if collapsed.is_empty() {
+			leaves.push(BVHLeaf {
+				bound: bound,
+				value: nodes.span(node),
+			});
+			return (leaves.len() - 1) << 1;
+		}
+		branches.push(BVHBranch {
+			bound: bound,
+			left: collapsed[0],
+			right: collapsed[1],
+		});
+		((branches.len() - 1) << 1) | 1
+	})
+}
+
+impl<B, V> BatchedBVH<B, V> {
+	/// len returns the number of items in the BatchedBVH.
+	pub fn len(&self) -> usize {
+		self.items.len()
+	}
+
+	/// is_empty checks whether there's no item in the BatchedBVH.
+	pub fn is_empty(&self) -> bool {
+		self.items.is_empty()
+	}
+
+	/// query for all items hit by the AABB query, with the same
+	/// semantic and order of BVH::query.
+	///
+	/// The items in the leaves intersecting with the query body are
+	/// checked one by one, while all items in the included leaves
+	/// and subtrees are taken without checking them.
+	pub fn query<'a, 'b: 'a>(
+		&'b self, q: &'a impl AABBQuery<B>,
+	) -> impl 'a + Iterator<Item = &'b V> {
+		let nodes = self.bvh.nodes();
+		let mut steps = nodes.traverse(q, Vec::with_capacity(nodes.depth));
+		let mut pending = 0..0;
+		let mut check = false;
+		std::iter::from_fn(move || loop {
+			for i in &mut pending {
+				let item = &self.items[i];
+				if !check
+					|| q.check(&item.bound) != AABBRelation::Interleave
+				{
+					return Some(&item.value);
+				}
+			}
+			match steps.next()? {
+				Step::Include(id) => {
+					let leaves = nodes.leaves_of(id);
+					let (lo, _) = leaves[0].value;
+					let (_, hi) = leaves[leaves.len() - 1].value;
+					pending = lo..hi;
+					check = false;
+				},
+				Step::Leaf(id, relation) => {
+					let (lo, hi) = nodes.leaves[id].value;
+					pending = lo..hi;
+					check = relation != AABBRelation::Include;
+				},
+				_ => {},
+			}
+		})
+	}
+}
+
+/// BVHArena is the shared storage of the nodes of many BVHs.
+///
+/// Scenes full of small objects might hold a BVH for each of them,
+/// and allocating lists for every of them adds up. Building them
+/// into the arena places their nodes contiguously in the lists of
+/// the arena instead, and each of them is referred by an ArenaBVH
+/// holding the ranges of its nodes.
+pub struct BVHArena<B, V> {
+	branches: Vec<BVHBranch<B>>,
+	leaves: Vec<BVHLeaf<B, V>>,
+}
+
+/// ArenaBVH is the handle of a BVH built into the BVHArena.
+///
+/// The handle is only meaningful to the arena building it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ArenaBVH {
+	root: usize,
+	depth: usize,
+	branches: (usize, usize),
+	leaves: (usize, usize),
+}
+
+impl<B, V> BVHArena<B, V> {
+	/// new creates the empty arena, which is also the default one.
+	pub fn new() -> Self {
+		Self {
+			branches: Vec::new(),
+			leaves: Vec::new(),
+		}
+	}
+
+	fn nodes(&self, bvh: &ArenaBVH) -> BVHNodes<'_, B, V> {
+		BVHNodes {
+			root: bvh.root,
+			depth: bvh.depth,
+			branches: &self.branches[bvh.branches.0..bvh.branches.1],
+			leaves: &self.leaves[bvh.leaves.0..bvh.leaves.1],
+		}
+	}
+
+	/// query the BVH built into this arena, with the same semantic
+	/// of BVH::query.
+	pub fn query<'a, 'b: 'a>(
+		&'b self, bvh: &ArenaBVH, q: &'a impl AABBQuery<B>,
+	) -> impl 'a + Iterator<Item = &'b V> {
+		self.nodes(bvh).query(q)
+	}
+}
+
+impl<B, V> Default for BVHArena<B, V> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<B: Bounded + Clone, V> BVHArena<B, V> {
+	/// build the BVH from the specified leaves into the arena, with
+	/// the same strategy of BVH::build.
+	pub fn build(&mut self, items: Vec<(B, V)>) -> ArenaBVH {
+		let branches = self.branches.len();
+		let leaves = self.leaves.len();
+		let (root, depth) = bake(
+			items,
+			&mut self.branches,
+			&mut self.leaves,
+			split_median,
+			|_, _, _| {},
+		);
+		ArenaBVH {
+			root: root,
+			depth: depth,
+			branches: (branches, self.branches.len()),
+			leaves: (leaves, self.leaves.len()),
+		}
+	}
+}
+
+/// BVHEditor collects the leaves of a BVH for applying a batch of
+/// insertions and removals, and bakes them into a new BVH.
+///
+/// The whole hierarchy is rebuilt from the surviving leaves on
+/// finish, which costs the same as building from them, so it pays
+/// off only when the edits are applied in batches.
+pub struct BVHEditor<B, V> {
+	items: Vec<(B, V)>,
+}
+
+impl<B, V> BVH<B, V> {
+	/// edit consumes the BVH and turns it into an editor.
+	pub fn edit(self) -> BVHEditor<B, V> {
+		BVHEditor {
+			items: self
+				.leaves
+				.into_iter()
+				.map(|leaf| (leaf.bound, leaf.value))
+				.collect(),
+		}
+	}
+}
+
+impl<B, V> BVHEditor<B, V> {
+	/// insert a new leaf into the editor.
+	pub fn insert(&mut self, bound: B, value: V) {
+		self.items.push((bound, value));
+	}
+
+	/// remove_where removes all leaves satisfying the predicate, and
+	/// returns the number of removed leaves.
+	pub fn remove_where(
+		&mut self, mut f: impl FnMut(&B, &V) -> bool,
+	) -> usize {
+		let len = self.items.len();
+		self.items.retain(|(bound, value)| !f(bound, value));
+		len - self.items.len()
+	}
+}
+
+impl<B: Bounded + Clone, V> BVHEditor<B, V> {
+	/// finish rebuilds the BVH from the surviving leaves.
+	pub fn finish(self) -> BVH<B, V> {
+		BVH::build(self.items)
+	}
+}
+
+cfg_test! {
+	pub(crate) fn testdata_bvh_i64(
+		size: usize,
+	) -> Vec<(AABB3<i64>, usize)> {
+		gen_bvh_i64(&mut prng_seeded().0, size)
+	}
+
+	// gen_bvh_i64 generates the data of testdata_bvh_i64 from the
+	// specified generator, which is also used by the benchmarks.
+	pub(crate) fn gen_bvh_i64(
+		rng: &mut impl Rng, size: usize,
+	) -> Vec<(AABB3<i64>, usize)> {
+		let mut result = Vec::new();
+		for i in 0..size {
+			let v1 = gen_vec3_i64(rng);
+			let v2 = gen_vec3_i64(rng);
+			result.push((AABB3::new(v1, v2), i));
+		}
+		result
+	}
+
+	pub(crate) fn testdata_bvh_plane3_i64(
+		size: usize,
+	) -> Vec<Plane3<i64, i64>> {
+		gen_bvh_plane3_i64(&mut prng_seeded().0, size)
+	}
+
+	// gen_bvh_plane3_i64 generates the data of testdata_bvh_plane3_i64
+	// from the specified generator, which is also used by the benchmarks.
+	pub(crate) fn gen_bvh_plane3_i64(
+		rng: &mut impl Rng, size: usize,
+	) -> Vec<Plane3<i64, i64>> {
+		let mut result = Vec::new();
+		for _ in 0..size {
+			let point = gen_vec3_i64(rng);
+			let mut normal = gen_vec3_i64(rng);
+			while (normal ^ normal) == 0 {
+				normal = gen_vec3_i64(rng);
+			}
+			result.push(Plane3::new(point, normal));
+		}
+		result
+	}
+
+	// assert_bvh_query checks the query result of the BVH against
+	// the result of checking each item naively.
+	pub(crate) fn assert_bvh_query<Q: AABBQuery<AABB3<i64>>>(
+		bvh: &BVH<AABB3<i64>, usize>, items: &[(AABB3<i64>, usize)],
+		q: &Q,
+	) {
+		let mut actual: Vec<usize> = bvh.query(q).copied().collect();
+		actual.sort();
+		let mut expected: Vec<usize> = items
+			.iter()
+			.filter(|x| q.check(&x.0) != AABBRelation::Interleave)
+			.map(|x| x.1)
+			.collect();
+		expected.sort();
+		assert_eq!(actual, expected);
+	}
+
+	// assert_bvh_structure checks the structural invariants of BVH,
+	// that there're n-1 branches for n leaves, each node is referred
+	// exactly once, the leaves under each branch are contiguous, and
+	// the depth is the number of nodes on the longest path.
+	pub(crate) fn assert_bvh_structure<B, V>(bvh: &BVH<B, V>) {
+		if bvh.leaves.is_empty() {
+			assert_eq!(bvh.root, 0);
+			assert_eq!(bvh.depth, 0);
+			assert_eq!(bvh.branches.len(), 0);
+			return;
+		}
+		assert_eq!(bvh.branches.len() + 1, bvh.leaves.len());
+		let mut referred = vec![0; bvh.branches.len() * 2 + 2];
+		referred[bvh.root] += 1;
+		for branch in &bvh.branches {
+			referred[branch.left] += 1;
+			referred[branch.right] += 1;
+		}
+		assert!(referred[..referred.len() - 1].iter().all(|x| *x == 1));
+
+		// counts the leaves under the node and check they are
+		// exactly the leaves between leftmost and rightmost.
+		fn count<B, V>(nodes: BVHNodes<'_, B, V>, node: usize) -> usize {
+			let (id, is_branch) = decompose(node);
+			if !is_branch {
+				return 1;
+			}
+			let branch = &nodes.branches[id];
+			let n = count(nodes, branch.left) + count(nodes, branch.right);
+			assert_eq!(nodes.rightmost(id) - nodes.leftmost(id) + 1, n);
+			n
+		}
+		assert_eq!(count(bvh.nodes(), bvh.root), bvh.leaves.len());
+
+		fn depth<B, V>(nodes: BVHNodes<'_, B, V>, node: usize) -> usize {
+			let (id, is_branch) = decompose(node);
+			if !is_branch {
+				return 1;
+			}
+			let branch = &nodes.branches[id];
+			1 + max(depth(nodes, branch.left), depth(nodes, branch.right))
+		}
+		assert_eq!(depth(bvh.nodes(), bvh.root), bvh.depth);
+	}
+
+	// Everything intersects with every bound, so that the traversal
+	// with it will visit all nodes of the BVH.
+	struct Everything;
+
+	impl<B> AABBQuery<B> for Everything {
+		fn check(&self, _: &B) -> AABBRelation {
+			AABBRelation::Intersect
+		}
+	}
+
+	#[test] fn test_bvh_i64_query_stack() {
+		for size in [1, 2, 3, 1000, 4097] {
+			let bvh = BVH::build(testdata_bvh_i64(size));
+			assert_bvh_structure(&bvh);
+			let nodes = bvh.nodes();
+			let stack = Vec::with_capacity(nodes.depth);
+			let capacity = stack.capacity();
+			let mut traversal = nodes.traverse(&Everything, stack);
+			let mut visited = 0;
+			while let Some(_) = traversal.next() {
+				assert!(traversal.stack.len() <= nodes.depth);
+				assert_eq!(traversal.stack.capacity(), capacity);
+				visited += 1;
+			}
+			assert_eq!(visited, bvh.branches.len() + bvh.leaves.len());
+			assert_eq!(bvh.query(&Everything).count(), size);
+		}
+	}
+
+	#[test] fn test_bvh_i64_build() {
+		let mut items = Vec::new();
+		for i in 0..3 {
+			for j in 0..3 {
+				for k in 0..3 {
+					let p = Vec3::new(2 * i, 2 * j, 2 * k);
+					let aabb = AABB3::new(p, p + Vec3::new(1, 1, 1));
+					items.push((aabb, (i, j, k)));
+				}
+			}
+		}
+		let bvh = BVH::build(items.clone());
+		assert_bvh_structure(&bvh);
+
+		let query = |p: (i64, i64, i64), n: (i64, i64, i64)| {
+			let plane = Plane3::new(p.into(), n.into());
+			let mut result: Vec<_> =
+				bvh.query(&plane).copied().collect();
+			result.sort();
+			result
+		};
+		let expected = |f: &dyn Fn(i64, i64, i64) -> bool| {
+			let mut result: Vec<_> = items
+				.iter()
+				.map(|x| x.1)
+				.filter(|(i, j, k)| f(*i, *j, *k))
+				.collect();
+			result.sort();
+			result
+		};
+		assert_eq!(
+			query((3, 0, 0), (1, 0, 0)),
+			expected(&|i, _, _| i <= 1),
+		);
+		assert_eq!(
+			query((0, 2, 0), (0, -1, 0)),
+			expected(&|_, j, _| j >= 1),
+		);
+		assert_eq!(
+			query((3, 3, 3), (1, 1, 1)),
+			expected(&|i, j, k| i + j + k <= 4),
+		);
+		assert_eq!(query((0, 0, -1), (0, 0, 1)), vec![]);
+		assert_eq!(query((0, 0, 9), (0, 0, 1)).len(), items.len());
+	}
+
+	// testdata_bvh_clustered_i64 generates the small boxes gathered in
+	// the clusters, which is the case that the median split performs
+	// poorly, since the split planes often cross the clusters.
+	pub(crate) fn testdata_bvh_clustered_i64(
+		size: usize,
+	) -> Vec<(AABB3<i64>, usize)> {
+		gen_bvh_clustered_i64(&mut prng_seeded().0, size)
+	}
+
+	// gen_bvh_clustered_i64 generates the data of
+	// testdata_bvh_clustered_i64 from the specified generator, which is
+	// also used by the benchmarks.
+	pub(crate) fn gen_bvh_clustered_i64(
+		rng: &mut impl Rng, size: usize,
+	) -> Vec<(AABB3<i64>, usize)> {
+		let mut gen = |shift: usize| Vec3::new(
+			(rng.gen::<i32>() >> shift) as i64,
+			(rng.gen::<i32>() >> shift) as i64,
+			(rng.gen::<i32>() >> shift) as i64,
+		);
+		let centers: Vec<_> = (0..16).map(|_| gen(12)).collect();
+		let mut result = Vec::new();
+		for i in 0..size {
+			let p = centers[i % centers.len()] + gen(20);
+			result.push((AABB3::new(p, p + gen(24)), i));
+		}
+		result
+	}
+
+	#[test] fn test_bvh_i64_build_sah() {
+		let items = testdata_bvh_clustered_i64(1000);
+		let bvh = BVH::build_sah(items.clone());
+		assert_bvh_structure(&bvh);
+		for plane in testdata_bvh_plane3_i64(100) {
+			assert_bvh_query(&bvh, &items, &plane);
+		}
+		for (aabb, _) in &items[..100] {
+			assert_bvh_query(&bvh, &items, aabb);
+		}
+
+		// All centroids are the same, falling back to median split.
+		let p = Vec3::<i64>::new(1, 2, 3);
+		let items: Vec<(AABB3<i64>, usize)> = (0..100)
+			.map(|i| {
+				let d = Vec3::new(i, i, i);
+				(AABB3::new(p - d, p + d), i as usize)
+			})
+			.collect();
+		let bvh = BVH::build_sah(items.clone());
+		assert_bvh_structure(&bvh);
+		assert_eq!(bvh.depth, BVH::build(items).depth);
+
+		let bvh = BVH::build_sah(testdata_bvh_i64(1));
+		assert_bvh_structure(&bvh);
+		let bvh = BVH::<AABB3<i64>, usize>::build_sah(Vec::new());
+		assert_bvh_structure(&bvh);
+	}
+
+	fn fixture_bench_bvh_i64_clustered(
+		b: &mut Bencher,
+		build: impl Fn(Vec<(AABB3<i64>, usize)>) -> BVH<AABB3<i64>, usize>,
+	) {
+		const POW2: usize = 1 << 10;
+		let items = gen_bvh_clustered_i64(&mut prng(), 1 << 14);
+		let bvh = build(items.clone());
+		let mut i = 0;
+		b.iter(|| {
+			let j = i;
+			i = (i + 1) & (POW2 - 1);
+			bvh.count(&items[j].0)
+		});
+	}
+
+	#[bench] fn bench_bvh_i64_clustered_query(b: &mut Bencher) {
+		fixture_bench_bvh_i64_clustered(b, BVH::build);
+	}
+
+	#[bench] fn bench_bvh_i64_clustered_sah_query(b: &mut Bencher) {
+		fixture_bench_bvh_i64_clustered(b, BVH::build_sah);
+	}
+
+	#[test] fn test_bvh_i64_edit() {
+		let mut items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		let mut editor = bvh.edit();
+		assert_eq!(editor.remove_where(|_, v| v % 3 == 0), 334);
+		items.retain(|x| x.1 % 3 != 0);
+		for (bound, value) in testdata_bvh_i64(1500).drain(1000..) {
+			editor.insert(bound, value);
+			items.push((bound, value));
+		}
+		let bvh = editor.finish();
+		assert_bvh_structure(&bvh);
+		assert_eq!(bvh.leaves.len(), items.len());
+		for plane in testdata_bvh_plane3_i64(100) {
+			assert_bvh_query(&bvh, &items, &plane);
+		}
+	}
+
+	#[test] fn test_bvh_i64_query_aabb3() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		let rng = &mut prng_seeded().0;
+		for _ in 0..100 {
+			let aabb =
+				AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng));
+			assert_bvh_query(&bvh, &items, &aabb);
+		}
+		for (aabb, _) in &items[..100] {
+			assert_bvh_query(&bvh, &items, aabb);
+		}
+	}
+
+	#[test] fn test_bvh_i64_nearest() {
+		// The coordinates are small enough that the squared distances
+		// will never overflow i64.
+		let rng = &mut prng_seeded().0;
+		let mut gen = || Vec3::new(
+			(rng.gen::<i32>() >> 4) as i64,
+			(rng.gen::<i32>() >> 4) as i64,
+			(rng.gen::<i32>() >> 4) as i64,
+		);
+		let empty: BVH<AABB3<i64>, usize> = BVH::build(Vec::new());
+		assert!(empty.nearest(gen()).is_none());
+
+		let mut items = Vec::new();
+		for i in 0..1000 {
+			let p = gen();
+			items.push((AABB3::new(p, p), i));
+		}
+		let bvh = BVH::build(items.clone());
+		for _ in 0..1000 {
+			let point = gen();
+			let expected = items
+				.iter()
+				.map(|x| x.0.distance2(&point))
+				.min()
+				.unwrap();
+			let actual = bvh.nearest(point).unwrap();
+			assert_eq!(items[*actual].0.distance2(&point), expected);
+		}
+		for (bound, value) in &items {
+			assert_eq!(bvh.nearest(bound.min()), Some(value));
+		}
+	}
+
+	#[test] fn test_bvh_i64_k_nearest() {
+		let rng = &mut prng_seeded().0;
+		for shift in [4, 28] {
+			// The coordinates are small enough that the squared
+			// distances will never overflow i64, and there will be
+			// many ties with the smaller coordinates.
+			let mut gen = || Vec3::new(
+				(rng.gen::<i32>() >> shift) as i64,
+				(rng.gen::<i32>() >> shift) as i64,
+				(rng.gen::<i32>() >> shift) as i64,
+			);
+			let mut items = Vec::new();
+			for i in 0..500 {
+				let (p0, p1) = (gen(), gen());
+				let p1 = if i % 2 == 0 { p0 } else { p1 };
+				items.push((AABB3::new(p0, p1), i));
+			}
+			let bvh = BVH::build(items);
+			for i in 0..100 {
+				let k = [0, 1, 2, 10, 499, 500, 501, 1000][i % 8];
+				let point = gen();
+				let mut expected: Vec<_> = bvh
+					.leaves
+					.iter()
+					.enumerate()
+					.map(|(i, x)| (x.bound.distance2(&point), i, &x.value))
+					.collect();
+				expected.sort();
+				let expected: Vec<_> =
+					expected.into_iter().take(k).map(|x| x.2).collect();
+				assert_eq!(bvh.k_nearest(point, k), expected);
+				if k == 1 {
+					assert_eq!(bvh.nearest(point), Some(expected[0]));
+				}
+			}
+		}
+		let empty: BVH<AABB3<i64>, usize> = BVH::build(Vec::new());
+		assert!(empty.k_nearest(Vec3::new(0, 0, 0), 3).is_empty());
+	}
+
+	#[test] fn test_bvh_i64_query_order() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		fn assert_query_order<Q: AABBQuery<AABB3<i64>>>(
+			bvh: &BVH<AABB3<i64>, usize>, q: &Q,
+		) {
+			let actual: Vec<usize> = bvh.query(q).copied().collect();
+			let expected: Vec<usize> = bvh
+				.leaves
+				.iter()
+				.filter(|x| q.check(&x.bound) != AABBRelation::Interleave)
+				.map(|x| x.value)
+				.collect();
+			assert_eq!(actual, expected);
+		}
+		for plane in testdata_bvh_plane3_i64(100) {
+			assert_query_order(&bvh, &plane);
+		}
+		for (aabb, _) in &items[..100] {
+			assert_query_order(&bvh, aabb);
+		}
+		assert_query_order(&bvh, &Everything);
+	}
+
+	#[test] fn test_bvh_i64_for_each() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		fn assert_for_each<Q: AABBQuery<AABB3<i64>>>(
+			bvh: &BVH<AABB3<i64>, usize>, q: &Q,
+		) {
+			let mut actual = Vec::new();
+			bvh.for_each(q, |x| actual.push(*x));
+			let expected: Vec<usize> = bvh.query(q).copied().collect();
+			assert_eq!(actual, expected);
+		}
+		for plane in testdata_bvh_plane3_i64(100) {
+			assert_for_each(&bvh, &plane);
+		}
+		for (aabb, _) in &items[..100] {
+			assert_for_each(&bvh, aabb);
+		}
+		assert_for_each(&bvh, &Everything);
+	}
+
+	#[test] fn test_bvh_i64_count() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		for plane in testdata_bvh_plane3_i64(100) {
+			assert_eq!(bvh.count(&plane), bvh.query(&plane).count());
+		}
+		for (aabb, _) in &items[..100] {
+			assert_eq!(bvh.count(aabb), bvh.query(aabb).count());
+		}
+		assert_eq!(bvh.count(&Everything), items.len());
+	}
+
+	#[test] fn test_bvh_i64_query_bounds() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		let check = |q: &dyn AABBQuery<AABB3<i64>>| {
+			let hits: Vec<_> = bvh.query_bounds(&q).collect();
+			assert!(hits.iter().map(|x| x.1).eq(bvh.query(&q)));
+			for (bound, value) in hits {
+				assert_eq!(*bound, items[*value].0);
+			}
+		};
+		for plane in testdata_bvh_plane3_i64(100) {
+			check(&plane);
+		}
+		for (aabb, _) in &items[..100] {
+			check(aabb);
+		}
+		check(&Everything);
+	}
+
+	#[test] fn test_bvh_i64_query_filter() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		for plane in testdata_bvh_plane3_i64(100) {
+			let actual = bvh.query_filter(&plane, |x| x % 3 == 0);
+			let expected = bvh.query(&plane).filter(|x| *x % 3 == 0);
+			assert!(actual.eq(expected));
+		}
+		let mut all: Vec<usize> = bvh
+			.query_filter(&Everything, |x| x % 7 == 1)
+			.copied()
+			.collect();
+		all.sort();
+		assert!(all.into_iter().eq((1..items.len()).step_by(7)));
+		assert_eq!(bvh.query_filter(&Everything, |_| false).count(), 0);
+	}
+
+	#[test] fn test_bvh_i64_query_owned() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		fn hits(
+			bvh: &BVH<AABB3<i64>, usize>, p: Vec3<i64>,
+		) -> impl '_ + Iterator<Item = &'_ usize> {
+			bvh.query_owned(Plane3::new(p, Vec3::new(1, 1, 1)))
+		}
+		for p in [Vec3::new(0, 0, 0), Vec3::new(1 << 28, -5, 7)] {
+			let plane = Plane3::new(p, Vec3::new(1, 1, 1));
+			assert!(hits(&bvh, p).eq(bvh.query(&plane)));
+		}
+		for (aabb, _) in &items[..100] {
+			assert!(bvh.query_owned(*aabb).eq(bvh.query(aabb)));
+		}
+		assert_eq!(bvh.query_owned(Everything).count(), items.len());
+	}
+
+	#[test] fn test_bvh_i64_find_first() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		for plane in testdata_bvh_plane3_i64(100) {
+			assert_eq!(bvh.find_first(&plane), bvh.query(&plane).next());
+			assert_eq!(bvh.any(&plane), bvh.count(&plane) > 0);
+		}
+		for (aabb, _) in &items[..100] {
+			assert_eq!(bvh.find_first(aabb), bvh.query(aabb).next());
+			assert!(bvh.any(aabb));
+		}
+		let far = AABB3::new(
+			Vec3::new(1 << 40, 1 << 40, 1 << 40),
+			Vec3::new(1 << 41, 1 << 41, 1 << 41),
+		);
+		assert_eq!(bvh.find_first(&far), None);
+		assert!(!bvh.any(&far));
+		assert_eq!(bvh.find_first(&Everything), bvh.values().next());
+
+		let empty = BVH::<AABB3<i64>, usize>::build(Vec::new());
+		assert!(!empty.any(&Everything));
+	}
+
+	fn fixture_bench_bvh_i64_count(
+		b: &mut Bencher,
+		f: impl Fn(&BVH<AABB3<i64>, usize>, &Plane3<i64, i64>) -> usize,
+	) {
+		const POW2: usize = 1 << 6;
+		let bvh = BVH::build(gen_bvh_i64(&mut prng(), 1 << 14));
+		let planes = gen_bvh_plane3_i64(&mut prng(), POW2);
+		let mut i = 0;
+		b.iter(|| {
+			let j = i;
+			i = (i + 1) & (POW2 - 1);
+			f(&bvh, &planes[j])
+		});
+	}
+
+	#[bench] fn bench_bvh_i64_count(b: &mut Bencher) {
+		fixture_bench_bvh_i64_count(b, |bvh, q| bvh.count(q));
+	}
+
+	#[bench] fn bench_bvh_i64_query_count(b: &mut Bencher) {
+		fixture_bench_bvh_i64_count(b, |bvh, q| bvh.query(q).count());
+	}
+
+	#[bench] fn bench_bvh_i64_any(b: &mut Bencher) {
+		fixture_bench_bvh_i64_count(b, |bvh, q| bvh.any(q) as usize);
+	}
+
+	#[bench] fn bench_bvh_i64_query_collect(b: &mut Bencher) {
+		fixture_bench_bvh_i64_count(b, |bvh, q| {
+			bvh.query(q).collect::<Vec<_>>().len()
+		});
+	}
+
+	#[bench] fn bench_bvh_i64_query_cursor_collect(b: &mut Bencher) {
+		const POW2: usize = 1 << 6;
+		let bvh = BVH::build(gen_bvh_i64(&mut prng(), 1 << 14));
+		let planes = gen_bvh_plane3_i64(&mut prng(), POW2);
+		let mut cursor = bvh.query_cursor();
+		let mut i = 0;
+		b.iter(|| {
+			let j = i;
+			i = (i + 1) & (POW2 - 1);
+			cursor.collect(&planes[j]).len()
+		});
+	}
+
+	#[cfg(feature = "rayon")]
+	#[test] fn test_bvh_i64_par_query() {
+		let items = testdata_bvh_i64(10000);
+		let bvh = BVH::build(items.clone());
+		for plane in testdata_bvh_plane3_i64(100) {
+			let expected: Vec<_> = bvh.query(&plane).collect();
+			assert_eq!(bvh.par_query(&plane), expected);
+		}
+		for (aabb, _) in &items[..100] {
+			let expected: Vec<_> = bvh.query(aabb).collect();
+			assert_eq!(bvh.par_query(aabb), expected);
+		}
+		assert_eq!(bvh.par_query(&Everything).len(), items.len());
+
+		let empty = BVH::<AABB3<i64>, usize>::build(Vec::new());
+		assert!(empty.par_query(&Everything).is_empty());
+	}
+
+	#[cfg(feature = "serde")]
+	#[test] fn test_bvh_i64_serde() {
+		let mut items = Vec::new();
+		for i in 0..5 {
+			let p = Vec3::new(i, 2 * i, -i);
+			let aabb = AABB3::new(p, p + Vec3::new(1, 1, 1));
+			items.push((aabb, i as usize));
+		}
+		let bvh = BVH::build(items.clone());
+		let json = serde_json::to_string(&bvh).unwrap();
+		let restored: BVH<AABB3<i64>, usize> =
+			serde_json::from_str(&json).unwrap();
+		assert_bvh_structure(&restored);
+		assert_eq!(restored.root, bvh.root);
+		assert_eq!(restored.depth, bvh.depth);
+		assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+		for plane in testdata_bvh_plane3_i64(100) {
+			assert_bvh_query(&restored, &items, &plane);
+		}
+
+		// The intervals of AABB3 are reordered on deserializing.
+		let aabb: AABB3<i64> =
+			serde_json::from_str("[[1, 0], [2, 3], [5, 4]]").unwrap();
+		assert_eq!(
+			Vec3::<(i64, i64)>::from(aabb),
+			Vec3::new((0, 1), (2, 3), (4, 5)),
+		);
+	}
+
+	#[cfg(feature = "serde")]
+	#[test] fn test_bvh_i64_serde_malformed() {
+		let bvh = |root: usize, left: usize, right: usize| {
+			let bound = "[[0, 1], [0, 1], [0, 1]]";
+			let json = format!(
+				r#"{{
+					"root": {},
+					"branches": [{{"bound": {}, "left": {}, "right": {}}}],
+					"leaves": [
+						{{"bound": {}, "value": 0}},
+						{{"bound": {}, "value": 1}}
+					]
+				}}"#,
+				root, bound, left, right, bound, bound,
+			);
+			serde_json::from_str::<BVH<AABB3<i64>, usize>>(&json)
+		};
+		assert!(bvh(1, 0, 2).is_ok());
+		let err = bvh(1, 0, 9).err().unwrap().to_string();
+		assert!(err.contains("out of range"), "{}", err);
+		let err = bvh(1, 0, 0).err().unwrap().to_string();
+		assert!(err.contains("referred twice"), "{}", err);
+		let err = bvh(1, 2, 0).err().unwrap().to_string();
+		assert!(err.contains("out of order"), "{}", err);
+		assert!(bvh(0, 1, 2).is_err());
+	}
+
+	#[test] fn test_bvh_i64_query_partitioned() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		for plane in testdata_bvh_plane3_i64(100) {
+			let (included, intersecting) = bvh.query_partitioned(&plane);
+			let mut actual: Vec<usize> = included
+				.iter()
+				.chain(&intersecting)
+				.map(|x| **x)
+				.collect();
+			actual.sort();
+			let mut expected: Vec<usize> =
+				bvh.query(&plane).copied().collect();
+			expected.sort();
+			assert_eq!(actual, expected);
+
+			let mut actual: Vec<usize> =
+				included.iter().map(|x| **x).collect();
+			actual.sort();
+			let mut expected: Vec<usize> = items
+				.iter()
+				.filter(|x| plane.check(&x.0) == AABBRelation::Include)
+				.map(|x| x.1)
+				.collect();
+			expected.sort();
+			assert_eq!(actual, expected);
+		}
+	}
+
+	#[test] fn test_bvh_i64_query_contained() {
+		// The plane x = 5 cuts through the boxes [i, i + 2] straddling
+		// it, the boxes touching it from below are still included.
+		let items: Vec<_> = (0..10)
+			.map(|i| {
+				let p = Vec3::new(i, 0, 0);
+				(AABB3::new(p, p + Vec3::new(2, 1, 1)), i as usize)
+			})
+			.collect();
+		let bvh = BVH::build(items);
+		let plane = Plane3::new(Vec3::new(5, 0, 0), Vec3::new(1, 0, 0));
+		let contained: Vec<_> = bvh.query_contained(&plane).collect();
+		assert_eq!(contained, vec![&0, &1, &2]);
+		let hit: Vec<_> = bvh.query(&plane).collect();
+		assert_eq!(hit, vec![&0, &1, &2, &3, &4, &5]);
+
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		for plane in testdata_bvh_plane3_i64(100) {
+			let actual: Vec<_> = bvh.query_contained(&plane).collect();
+			let (included, _) = bvh.query_partitioned(&plane);
+			assert_eq!(actual, included);
+			let mut actual: Vec<usize> =
+				actual.iter().map(|x| **x).collect();
+			actual.sort();
+			let mut expected: Vec<usize> = items
+				.iter()
+				.filter(|x| plane.check(&x.0) == AABBRelation::Include)
+				.map(|x| x.1)
+				.collect();
+			expected.sort();
+			assert_eq!(actual, expected);
+		}
+		// Everything intersects with but includes nothing.
+		assert_eq!(bvh.query_contained(&Everything).count(), 0);
+	}
+
+	#[test] fn test_bvh_i64_query_ids() {
+		let items = testdata_bvh_i64(1000);
+		let mut bvh = BVH::build(items.clone());
+		let planes = testdata_bvh_plane3_i64(100);
+		for plane in &planes {
+			let (ids, values): (Vec<_>, Vec<_>) =
+				bvh.query_ids(plane).unzip();
+			assert_eq!(values, bvh.query(plane).collect::<Vec<_>>());
+			for (id, value) in ids.iter().zip(values) {
+				assert_eq!(bvh.get(*id), Some(value));
+				assert_eq!(bvh.iter().nth(id.index()).unwrap().1, value);
+			}
+		}
+		let all: Vec<_> = bvh.query_ids(&Everything).collect();
+		assert_eq!(all.len(), items.len());
+		let all: Vec<_> = all.into_iter().map(|x| x.0).collect();
+		assert_eq!(bvh.get(LeafId(items.len())), None);
+
+		// Mutate the values hit by the plane, and query again.
+		let ids: Vec<_> =
+			bvh.query_ids(&planes[0]).map(|x| x.0).collect();
+		assert!(!ids.is_empty());
+		for id in &ids {
+			*bvh.get_mut(*id).unwrap() += items.len();
+		}
+		assert!(bvh.get_mut(LeafId(items.len())).is_none());
+		for (id, value) in bvh.query_ids(&planes[0]) {
+			assert!(*value >= items.len());
+			assert!(ids.contains(&id));
+		}
+		let mutated = all
+			.iter()
+			.filter(|x| *bvh.get(**x).unwrap() >= items.len())
+			.count();
+		assert_eq!(mutated, ids.len());
+	}
+
+	#[test] fn test_bvh_i64_string_values() {
+		// String is neither Copy nor cheap to clone, and is yielded
+		// by references except query_cloned.
+		let items = testdata_bvh_i64(1000);
+		let strings: Vec<_> =
+			items.iter().map(|(b, i)| (*b, i.to_string())).collect();
+		let bvh = BVH::build(strings);
+		let numbers = BVH::build(items);
+		for plane in testdata_bvh_plane3_i64(10) {
+			let expected: Vec<String> =
+				numbers.query(&plane).map(|x| x.to_string()).collect();
+			let actual: Vec<&String> = bvh.query(&plane).collect();
+			assert_eq!(actual, expected.iter().collect::<Vec<_>>());
+			let cloned: Vec<String> = bvh.query_cloned(&plane).collect();
+			assert_eq!(cloned, expected);
+			assert_eq!(bvh.count(&plane), expected.len());
+			assert_eq!(bvh.any(&plane), !expected.is_empty());
+			let trees = [&bvh, &bvh];
+			let forest = BVHForest::new(&trees);
+			assert_eq!(forest.query(&plane).count(), expected.len() * 2);
+		}
+	}
+
+	#[test] fn test_bvh_i64_forest() {
+		let items = testdata_bvh_i64(1500);
+		let bvh1 = BVH::build(items[..1000].to_vec());
+		let bvh2 = BVH::build(items[1000..].to_vec());
+		let empty = BVH::empty();
+		let trees = [&bvh1, &empty, &bvh2];
+		let forest = BVHForest::new(&trees);
+		let whole = BVH::build(items);
+		for plane in testdata_bvh_plane3_i64(100) {
+			let actual: Vec<_> = forest.query(&plane).collect();
+			let mut expected: Vec<_> = bvh1.query(&plane).collect();
+			expected.extend(bvh2.query(&plane));
+			assert_eq!(actual, expected);
+
+			let mut actual: Vec<usize> =
+				actual.into_iter().copied().collect();
+			actual.sort();
+			let mut expected: Vec<usize> =
+				whole.query(&plane).copied().collect();
+			expected.sort();
+			assert_eq!(actual, expected);
+		}
+		assert_eq!(forest.query(&Everything).count(), 1500);
+		let none = BVHForest::<AABB3<i64>, usize>::new(&[]);
+		assert_eq!(none.query(&Everything).count(), 0);
+	}
+
+	// assert_bvh_query_matches asserts the query function yields the
+	// same items in the same order of querying the BVH, with the
+	// testing planes, the bounds of the items and everything.
+	fn assert_bvh_query_matches(
+		bvh: &BVH<AABB3<i64>, usize>, items: &[(AABB3<i64>, usize)],
+		query: impl Fn(&dyn AABBQuery<AABB3<i64>>) -> Vec<usize>,
+	) {
+		let check = |q: &dyn AABBQuery<AABB3<i64>>| {
+			let expected: Vec<usize> = bvh.query(&q).copied().collect();
+			assert_eq!(query(q), expected);
+		};
+		for plane in testdata_bvh_plane3_i64(100) {
+			check(&plane);
+		}
+		for (aabb, _) in &items[..100] {
+			check(aabb);
+		}
+		check(&Everything);
+		assert_eq!(query(&Everything).len(), bvh.len());
+	}
+
+	#[test] fn test_bvh_i64_build_with_fanout() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		for fanout in [2, 4, 8] {
+			let wide = BVH::build_with_fanout(items.clone(), fanout);
+			assert_eq!(wide.len(), 1000);
+			assert!(wide.depth <= bvh.depth);
+			for i in 0..wide.branches.len() {
+				let (lo, hi) = wide.branches[i].children;
+				assert!(2 <= hi - lo && hi - lo <= fanout);
+			}
+			assert_bvh_query_matches(&bvh, &items, |q| {
+				wide.query(&q).copied().collect()
+			});
+		}
+		let wide = BVH::build_with_fanout(items.clone(), 2);
+		assert_eq!(wide.depth, bvh.depth);
+		let wide = BVH::build_with_fanout(items.clone(), 8);
+		assert!(wide.depth < bvh.depth);
+
+		for size in [0, 1, 2, 3] {
+			let items = testdata_bvh_i64(size);
+			let wide = BVH::build_with_fanout(items, 4);
+			assert_eq!(wide.query(&Everything).count(), size);
+			assert_eq!(wide.is_empty(), size == 0);
+		}
+	}
+
+	#[test] fn test_bvh_i64_build_batched() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		for max_per_leaf in [1, 4] {
+			let batched = BVH::build_batched(items.clone(), max_per_leaf);
+			assert_eq!(batched.len(), 1000);
+			assert_bvh_structure(&batched.bvh);
+			for leaf in &batched.bvh.leaves {
+				let (lo, hi) = leaf.value;
+				assert!(1 <= hi - lo && hi - lo <= max_per_leaf);
+			}
+			assert_bvh_query_matches(&bvh, &items, |q| {
+				batched.query(&q).copied().collect()
+			});
+		}
+		let batched = BVH::build_batched(items.clone(), 1);
+		assert_eq!(batched.bvh.len(), bvh.len());
+		assert_eq!(batched.bvh.depth(), bvh.depth());
+		let batched = BVH::build_batched(items.clone(), 4);
+		assert!(batched.bvh.len() < bvh.len() / 2);
+		assert!(batched.bvh.depth() < bvh.depth());
+		let batched = BVH::build_batched(items.clone(), 1000);
+		assert_eq!(batched.bvh.len(), 1);
+
+		for size in [0, 1, 2, 3] {
+			let items = testdata_bvh_i64(size);
+			let batched = BVH::build_batched(items, 2);
+			assert_eq!(batched.query(&Everything).count(), size);
+			assert_eq!(batched.is_empty(), size == 0);
+		}
+	}
+
+	#[test]
+	#[should_panic(expected = "max_per_leaf 0 is less than 1")]
+	fn test_bvh_i64_build_batched_zero() {
+		BVH::build_batched(testdata_bvh_i64(10), 0);
+	}
+
+	#[test]
+	#[should_panic(expected = "fanout 1 is less than 2")]
+	fn test_bvh_i64_build_with_fanout_one() {
+		BVH::build_with_fanout(testdata_bvh_i64(10), 1);
+	}
+
+	#[test] fn test_bvh_i64_query_runner() {
+		let bvh = BVH::build(testdata_bvh_i64(1000));
+		let runner = QueryRunner::new(&bvh);
+		for plane in testdata_bvh_plane3_i64(100) {
+			let mut actual = Vec::new();
+			runner.run(&plane, |x| actual.push(*x));
+			let expected: Vec<usize> =
+				bvh.query(&plane).copied().collect();
+			assert_eq!(actual, expected);
+		}
+		assert!(runner.stack.borrow().capacity() > 0);
+	}
+
+	#[test] fn test_bvh_i64_query_cursor() {
+		let bvh = BVH::build(testdata_bvh_i64(1000));
+		let mut cursor = bvh.query_cursor();
+		for plane in testdata_bvh_plane3_i64(100) {
+			let expected: Vec<&usize> = bvh.query(&plane).collect();
+			assert_eq!(cursor.collect(&plane), &expected[..]);
+			let mut actual = Vec::new();
+			cursor.query(&plane, |x| actual.push(x));
+			assert_eq!(actual, expected);
+		}
+		assert!(cursor.stack.capacity() > 0);
+
+		let empty = BVH::<AABB3<i64>, usize>::empty();
+		assert!(empty.query_cursor().collect(&Everything).is_empty());
+	}
+
+	#[test] fn test_bvh_i64_build_with_progress() {
+		// Shrink the boxes so that the areas are not going to
+		// overflow while summing up.
+		let shrink = |v: Vec3<i64>| {
+			Vec3::new(v.0 >> 12, v.1 >> 12, v.2 >> 12)
+		};
+		let items: Vec<_> = testdata_bvh_i64(1000)
+			.into_iter()
+			.map(|(aabb, i)| {
+				let (v0, v1) = Vec3::<(i64, i64)>::from(aabb).unzip();
+				(AABB3::new(shrink(v0), shrink(v1)), i)
+			})
+			.collect();
+		let mut reports = Vec::new();
+		let bvh =
+			BVH::build_with_progress(items.clone(), |p| reports.push(p));
+		assert_eq!(reports.len(), 2 * items.len() - 1);
+		for i in 1..reports.len() {
+			assert!(reports[i - 1].leaves <= reports[i].leaves);
+			assert!(reports[i - 1].cost <= reports[i].cost);
+		}
+		let last = reports[reports.len() - 1];
+		assert_eq!(last.leaves, items.len());
+		assert_eq!(last.depth, 0);
+		let cost: i64 =
+			bvh.branches.iter().map(|b| b.bound.surface_area()).sum();
+		assert_eq!(last.cost, cost);
+		for plane in testdata_bvh_plane3_i64(100) {
+			assert_bvh_query(&bvh, &items, &plane);
+		}
+	}
+
+	struct Marker {
+		aabb: AABB3<i64>,
+		name: String,
+	}
+
+	impl Leaf for Marker {
+		type Bound = AABB3<i64>;
+		type Value = String;
+
+		fn bound(&self) -> AABB3<i64> {
+			self.aabb
+		}
+
+		fn value(&self) -> &String {
+			&self.name
+		}
+	}
+
+	#[test] fn test_bvh_i64_from_leaf_objects() {
+		let items = testdata_bvh_i64(1000);
+		let markers = items
+			.iter()
+			.map(|x| Marker {
+				aabb: x.0,
+				name: format!("marker-{}", x.1),
+			})
+			.collect();
+		let bvh = BVH::from_leaf_objects(markers);
+		for plane in testdata_bvh_plane3_i64(100) {
+			let mut actual: Vec<String> =
+				bvh.query(&plane).map(|x| x.value().clone()).collect();
+			actual.sort();
+			let mut expected: Vec<String> = items
+				.iter()
+				.filter(|x| plane.check(&x.0) != AABBRelation::Interleave)
+				.map(|x| format!("marker-{}", x.1))
+				.collect();
+			expected.sort();
+			assert_eq!(actual, expected);
+		}
+	}
+
+	#[test] fn test_bvh_i64_query_trace() {
+		let items: Vec<_> = [20, 0, 30, 10]
+			.into_iter()
+			.map(|x| {
+				let aabb = AABB3::new(
+					Vec3::new(x, 0, 0),
+					Vec3::new(x + 1, 1, 1),
+				);
+				(aabb, x)
+			})
+			.collect();
+		let bvh = BVH::build(items);
+		let values: Vec<i64> =
+			bvh.leaves.iter().map(|x| x.value).collect();
+		assert_eq!(values, vec![0, 10, 20, 30]);
+
+		let trace = |x| {
+			let normal = Vec3::new(1, 0, 0);
+			bvh.query_trace(&Plane3::new(Vec3::new(x, 0, 0), normal))
+		};
+		use TraceEvent::*;
+		assert_eq!(trace(11), vec![
+			EnterBranch(2), EnterBranch(0), VisitLeaf(0), VisitLeaf(1),
+			PruneBranch(1),
+		]);
+		assert_eq!(trace(15), vec![
+			EnterBranch(2), IncludeSubtree(0), PruneBranch(1),
+		]);
+		assert_eq!(trace(25), vec![
+			EnterBranch(2), IncludeSubtree(0), EnterBranch(1),
+			VisitLeaf(2), PruneLeaf(3),
+		]);
+		assert_eq!(trace(-1), vec![PruneBranch(2)]);
+		assert_eq!(trace(32), vec![IncludeSubtree(2)]);
+	}
+
+	#[test] fn test_bvh_arena_i64_query() {
+		let items = testdata_bvh_i64(1000);
+		let mut arena = BVHArena::default();
+		let mut bvhs = Vec::new();
+		for size in [0, 1, 2, 3, 10, 100, 884] {
+			let part: Vec<_> = items[..size].to_vec();
+			bvhs.push((arena.build(part.clone()), part));
+		}
+		for plane in testdata_bvh_plane3_i64(100) {
+			for (bvh, part) in &bvhs {
+				let mut actual: Vec<usize> =
+					arena.query(bvh, &plane).copied().collect();
+				actual.sort();
+				let mut expected: Vec<usize> = part
+					.iter()
+					.filter(|x| {
+						plane.check(&x.0) != AABBRelation::Interleave
+					})
+					.map(|x| x.1)
+					.collect();
+				expected.sort();
+				assert_eq!(actual, expected);
+			}
+		}
+	}
+}
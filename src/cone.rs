@@ -0,0 +1,215 @@
+use std::cmp::Ordering;
+
+use crate::{
+	cfg_test, AABBQuery, AABBRelation, Coordinate, Frac, PointDistance,
+	Vec3, AABB3,
+};
+
+cfg_test! {
+	use rand::Rng;
+	use crate::prng_seeded;
+}
+
+/// Cone3 is a three dimensional infinite cone denoted by its apex,
+/// the direction of its axis, and the squared cosine of its half
+/// angle, e.g. the lit region of a spotlight.
+///
+/// A point p is inside the cone if and only if (p - apex) ^ axis is
+/// non-negative, and ((p - apex) ^ axis)^2 >= cos2 * |p - apex|^2 *
+/// |axis|^2, so the axis needs not to be normalized, and no square
+/// root or trigonometric function is involved. The half angle is
+/// always within 90 degrees, so the cone is convex.
+///
+/// The comparison takes the fourth power of the coordinates times
+/// the denominator of cos2, U must be wide enough to hold them.
+#[derive(Copy, Clone, Debug)]
+pub struct Cone3<T, U> {
+	apex: Vec3<T>,
+	axis: Vec3<T>,
+	dir: Vec3<Ordering>,
+	cos2: Frac<U>,
+	scaled: U,
+}
+
+impl<T, U> Cone3<T, U>
+where
+	T: Coordinate<Product = U>,
+	U: Coordinate<Product = U>,
+{
+	/// new creates the cone with the squared cosine of the half angle
+	/// cos2.num / cos2.den, which must be within [0, 1] with a
+	/// positive denominator.
+	#[inline(always)]
+	pub fn new(apex: Vec3<T>, axis: Vec3<T>, cos2: Frac<U>) -> Self {
+		Self {
+			apex: apex,
+			axis: axis,
+			dir: axis.to_ordering(),
+			cos2: cos2,
+			scaled: cos2.num * (axis ^ axis),
+		}
+	}
+}
+
+impl<T, U> Cone3<T, U>
+where
+	T: Coordinate<Product = U>,
+	U: Coordinate<Product = U>,
+{
+	/// apex of the cone.
+	#[inline(always)]
+	pub fn apex(&self) -> Vec3<T> {
+		self.apex
+	}
+
+	/// axis of the cone.
+	#[inline(always)]
+	pub fn axis(&self) -> Vec3<T> {
+		self.axis
+	}
+
+	/// contains_point checks whether the point is inside the cone,
+	/// including its surface.
+	#[inline(always)]
+	pub fn contains_point(&self, p: Vec3<T>) -> bool {
+		let w = p - self.apex;
+		let d = w ^ self.axis;
+		d >= U::default() && self.cos2.den * d * d >= self.scaled * (w ^ w)
+	}
+}
+
+/// The AABB is Interleave if its points provably miss the cone, by
+/// bounding the projection of its points on the axis from above
+/// with its farthest corner along the axis, and the distance of its
+/// points to the apex from below with its closest point to the apex.
+/// Since the cone is convex, the AABB is Include if all of its eight
+/// corners are inside the cone.
+///
+/// This is conservative that AABB outside the cone might still be
+/// reported as Intersect, e.g. those standing beside the surface
+/// of the cone but far from the apex.
+impl<T, U> AABBQuery<AABB3<T>> for Cone3<T, U>
+where
+	T: Coordinate<Product = U>,
+	U: Coordinate<Product = U>,
+{
+	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
+		let (_, vp) = bound.support_points(self.dir);
+		let d = (vp - self.apex) ^ self.axis;
+		if d < U::default() {
+			return AABBRelation::Interleave;
+		}
+		let r2 = bound.distance2(&self.apex);
+		if self.cos2.den * d * d < self.scaled * r2 {
+			return AABBRelation::Interleave;
+		}
+		match bound.corners().into_iter().all(|p| self.contains_point(p)) {
+			true => AABBRelation::Include,
+			false => AABBRelation::Intersect,
+		}
+	}
+}
+
+cfg_test! {
+	fn gen_vec3_i64_tiny(rng: &mut impl Rng) -> Vec3<i64> {
+		Vec3::new(
+			rng.gen_range(-64, 64),
+			rng.gen_range(-64, 64),
+			rng.gen_range(-64, 64),
+		)
+	}
+
+	// naive_cone3_i64 samples the points on a 5x5x5 grid over the
+	// AABB including its corners, and returns whether all and any
+	// of them are inside the cone.
+	fn naive_cone3_i64(
+		cone: &Cone3<i64, i64>, aabb: &AABB3<i64>,
+	) -> (bool, bool) {
+		let (lo, hi) = (aabb.min(), aabb.max());
+		let (mut all, mut any) = (true, false);
+		for i in 0..5 {
+			for j in 0..5 {
+				for k in 0..5 {
+					let p = lo + Vec3::new(
+						(hi.0 - lo.0) * i / 4,
+						(hi.1 - lo.1) * j / 4,
+						(hi.2 - lo.2) * k / 4,
+					);
+					let inside = cone.contains_point(p);
+					all &= inside;
+					any |= inside;
+				}
+			}
+		}
+		(all, any)
+	}
+
+	#[test] fn test_cone3_i64_query() {
+		// The cone of 45 degrees around +x from the origin.
+		let cone = Cone3::new(
+			Vec3::new(0, 0, 0),
+			Vec3::new(2, 0, 0),
+			Frac { num: 1, den: 2 },
+		);
+		let check = |p0: (i64, i64, i64), p1: (i64, i64, i64)| {
+			cone.check(&AABB3::new(p0.into(), p1.into()))
+		};
+		assert!(cone.contains_point(Vec3::new(10, 10, 0)));
+		assert!(!cone.contains_point(Vec3::new(10, 10, 1)));
+		assert!(!cone.contains_point(Vec3::new(-10, 0, 0)));
+		assert_eq!(check((5, -1, -1), (6, 1, 1)), AABBRelation::Include);
+		assert_eq!(check((1, -1, -1), (6, 1, 1)), AABBRelation::Intersect);
+		assert_eq!(
+			check((-1, -1, -1), (1, 1, 1)),
+			AABBRelation::Intersect,
+		);
+		assert_eq!(
+			check((-9, -1, -1), (-1, 1, 1)),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(check((1, 5, -1), (2, 9, 1)), AABBRelation::Interleave);
+		assert_eq!(check((0, 3, 3), (1, 9, 9)), AABBRelation::Interleave);
+	}
+
+	#[test] fn test_cone3_i64_random_query() {
+		let rng = &mut prng_seeded().0;
+		let mut include = 0usize;
+		let mut intersect = 0usize;
+		let mut interleave = 0usize;
+		for _ in 0..100000 {
+			let apex = gen_vec3_i64_tiny(rng);
+			let mut axis = gen_vec3_i64_tiny(rng);
+			while (axis ^ axis) == 0 {
+				axis = gen_vec3_i64_tiny(rng);
+			}
+			let den = rng.gen_range(1, 16);
+			let cos2 = Frac { num: rng.gen_range(0, den + 1), den: den };
+			let cone = Cone3::new(apex, axis, cos2);
+			let aabb = AABB3::new(
+				gen_vec3_i64_tiny(rng),
+				gen_vec3_i64_tiny(rng),
+			);
+			let (all, any) = naive_cone3_i64(&cone, &aabb);
+			let actual = cone.check(&aabb);
+			let message = format!("cone = {:?}, aabb = {:?}", cone, aabb);
+			match actual {
+				AABBRelation::Include => {
+					assert!(all, "{}", message);
+					include += 1;
+				},
+				AABBRelation::Intersect => {
+					assert!(!all, "{}", message);
+					intersect += 1;
+				},
+				AABBRelation::Interleave => {
+					assert!(!any, "{}", message);
+					interleave += 1;
+				},
+			}
+		}
+		println!(
+			"include = {}, intersect = {}, interleave = {}",
+			include, intersect, interleave,
+		);
+	}
+}
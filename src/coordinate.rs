@@ -0,0 +1,165 @@
+use std::ops::{Add, Mul, Sub};
+
+use crate::cfg_test;
+
+cfg_test! {
+	use crate::{AABBQuery, AABBRelation, Frustum3, Plane3, Vec3};
+	use crate::{AABB3, BVH};
+}
+
+/// Coordinate is the totally ordered scalar type closed under the
+/// addition and subtraction, whose products are in its Product type,
+/// which is the coordinate type expected by most of the exact queries.
+///
+/// The products are in the coordinate type itself for all primitive
+/// integer types, while the newtypes might take the wider products,
+/// e.g. Plane3<T, U> is created for T whose Product is U. The bounds
+/// requiring the products in a specific type are therefore written
+/// as Coordinate<Product = U>.
+///
+/// The query bodies and the exact distances are bounded on it, while
+/// the BVH bounds, the transforms of AABB3 and the checks of Plane3
+/// keep their fewer bounds, so that they are still available for the
+/// types not satisfying all of them, e.g. the planes of f64 created
+/// by Plane3::new_partial.
+pub trait Coordinate:
+	Ord
+	+ Copy
+	+ Default
+	+ Add<Output = Self>
+	+ Sub<Output = Self>
+	+ Mul<Output = <Self as Coordinate>::Product>
+{
+	/// Product is the type of the products of the coordinates.
+	type Product;
+}
+
+macro_rules! impl_coordinate {
+	($($t:ty),*) => {
+		$(
+			impl Coordinate for $t {
+				type Product = $t;
+			}
+		)*
+	};
+}
+
+impl_coordinate!(i8, i16, i32, i64, i128, isize);
+impl_coordinate!(u8, u16, u32, u64, u128, usize);
+
+cfg_test! {
+	// Meter is the newtype of the coordinate, which implements the
+	// Coordinate for indexing and querying with it.
+	#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+	struct Meter(i64);
+
+	impl Add for Meter {
+		type Output = Meter;
+		fn add(self, a: Meter) -> Meter {
+			Meter(self.0 + a.0)
+		}
+	}
+
+	impl Sub for Meter {
+		type Output = Meter;
+		fn sub(self, a: Meter) -> Meter {
+			Meter(self.0 - a.0)
+		}
+	}
+
+	impl Mul for Meter {
+		type Output = Meter;
+		fn mul(self, a: Meter) -> Meter {
+			Meter(self.0 * a.0)
+		}
+	}
+
+	impl Coordinate for Meter {
+		type Product = Meter;
+	}
+
+	#[test] fn test_coordinate_newtype() {
+		let m = |x: i64, y: i64, z: i64| {
+			Vec3::new(Meter(x), Meter(y), Meter(z))
+		};
+		let items: Vec<_> = (0..10)
+			.map(|i| (AABB3::new(m(i, 0, 0), m(i + 1, 1, 1)), i))
+			.collect();
+		let bvh = BVH::build(items);
+
+		// The plane x = 3 with the normal facing +x.
+		let (a, b, c) = (m(3, 0, 0), m(3, 1, 0), m(3, 0, 1));
+		let plane = Plane3::from_points(a, b, c).unwrap();
+		assert_eq!(
+			plane.check(&AABB3::new(m(1, 0, 0), m(2, 1, 1))),
+			AABBRelation::Include,
+		);
+		assert_eq!(
+			plane.check(&AABB3::new(m(4, 0, 0), m(5, 1, 1))),
+			AABBRelation::Interleave,
+		);
+		let mut hit: Vec<_> = bvh.query(&plane).copied().collect();
+		hit.sort();
+		assert_eq!(hit, vec![0, 1, 2, 3]);
+
+		let (zero, one) = (Meter(0), Meter(1));
+		let identity = [
+			[one, zero, zero],
+			[zero, one, zero],
+			[zero, zero, one],
+		];
+		let a = AABB3::new(m(0, 0, 0), m(2, 2, 2));
+		let b = a.transform(identity, m(1, 1, 1));
+		assert_eq!(b, AABB3::new(m(1, 1, 1), m(3, 3, 3)));
+	}
+
+	// Milli is the newtype of the coordinate whose products are in
+	// the wider i64, which implements the Coordinate for querying
+	// with the planes whose distances are in i64.
+	#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+	struct Milli(i32);
+
+	impl Add for Milli {
+		type Output = Milli;
+		fn add(self, a: Milli) -> Milli {
+			Milli(self.0 + a.0)
+		}
+	}
+
+	impl Sub for Milli {
+		type Output = Milli;
+		fn sub(self, a: Milli) -> Milli {
+			Milli(self.0 - a.0)
+		}
+	}
+
+	impl Mul for Milli {
+		type Output = i64;
+		fn mul(self, a: Milli) -> i64 {
+			self.0 as i64 * a.0 as i64
+		}
+	}
+
+	impl Coordinate for Milli {
+		type Product = i64;
+	}
+
+	#[test] fn test_coordinate_newtype_wide() {
+		let m = |x: i32, y: i32, z: i32| {
+			Vec3::new(Milli(x), Milli(y), Milli(z))
+		};
+		// The distances of the planes would overflow i32.
+		const L: i32 = 1 << 30;
+		let frustum = Frustum3::new(vec![
+			Plane3::new(m(-L, 0, 0), m(-L, 0, 0)),
+			Plane3::new(m(L, 0, 0), m(L, 0, 0)),
+		]);
+		let check = |x0: i32, x1: i32| {
+			frustum.check(&AABB3::new(m(x0, 0, 0), m(x1, 1, 1)))
+		};
+		assert_eq!(check(-1, 1), AABBRelation::Include);
+		assert_eq!(check(0, L), AABBRelation::Intersect);
+		assert_eq!(check(-L - 2, -L), AABBRelation::Intersect);
+		assert_eq!(check(-L - 2, -L - 1), AABBRelation::Interleave);
+	}
+}
@@ -0,0 +1,335 @@
+use std::cmp::max;
+
+use crate::bvh::{BVHBranch, BVHLeaf};
+use crate::{cfg_test, Bounded, SurfaceArea, BVH};
+
+cfg_test! {
+	use crate::{
+		testdata_bvh_clustered_i64, testdata_bvh_plane3_i64,
+		assert_bvh_query, assert_bvh_structure, AABB3,
+	};
+}
+
+enum DynamicKind<V> {
+	Leaf(V),
+	Branch(usize, usize),
+}
+
+struct DynamicNode<B, V> {
+	bound: B,
+	parent: Option<usize>,
+	kind: DynamicKind<V>,
+}
+
+/// DynamicBVH is a mutable BVH which is built incrementally, by
+/// inserting and removing leaves one at a time.
+///
+/// The nodes are linked by their indices in a list, and the slots
+/// of removed nodes are reused by later insertions. Each inserted
+/// leaf is identified by the id returned by insert, which remains
+/// valid until the leaf is removed.
+///
+/// The shape of the hierarchy depends on the order of insertions,
+/// and is usually worse than the one baked by BVH::build. Once the
+/// leaves settle down, it might be distilled into the compact BVH.
+pub struct DynamicBVH<B, V> {
+	root: Option<usize>,
+	nodes: Vec<Option<DynamicNode<B, V>>>,
+	free: Vec<usize>,
+}
+
+impl<B, V> DynamicBVH<B, V> {
+	/// new creates the DynamicBVH without any leaf, which is also the
+	/// default one.
+	pub fn new() -> Self {
+		Self {
+			root: None,
+			nodes: Vec::new(),
+			free: Vec::new(),
+		}
+	}
+
+	fn node(&self, id: usize) -> &DynamicNode<B, V> {
+		self.nodes[id].as_ref().unwrap()
+	}
+
+	fn node_mut(&mut self, id: usize) -> &mut DynamicNode<B, V> {
+		self.nodes[id].as_mut().unwrap()
+	}
+
+	fn alloc(&mut self, node: DynamicNode<B, V>) -> usize {
+		match self.free.pop() {
+			Some(id) => {
+				self.nodes[id] = Some(node);
+				id
+			},
+			None => {
+				self.nodes.push(Some(node));
+				self.nodes.len() - 1
+			},
+		}
+	}
+
+	fn release(&mut self, id: usize) -> DynamicNode<B, V> {
+		self.free.push(id);
+		self.nodes[id].take().unwrap()
+	}
+
+	// replace_child replaces the child of the parent, or the root if
+	// there's no parent, from old to new.
+	fn replace_child(
+		&mut self, parent: Option<usize>, old: usize, new: usize,
+	) {
+		let p = match parent {
+			Some(p) => p,
+			None => {
+				self.root = Some(new);
+				return;
+			},
+		};
+		if let DynamicKind::Branch(left, right) =
+			&mut self.node_mut(p).kind
+		{
+			if *left == old {
+				*left = new;
+			} else {
+				*right = new;
+			}
+		}
+	}
+
+	/// distill flattens the hierarchy into the compact BVH, with
+	/// the nodes laid out in the N-B-N ordering.
+	///
+	/// The shape of the hierarchy is kept as is, so the ids of the
+	/// leaves are discarded and the leaves are placed in the order
+	/// of the mid-order traversal instead.
+	pub fn distill(self) -> BVH<B, V> {
+		// Distill is the pending work of the walk, either visiting
+		// a node at the level, or joining the last two visited nodes
+		// into a branch with the bound.
+		enum Distill<B> {
+			Visit(usize, usize),
+			Join(B),
+		}
+
+		let mut nodes = self.nodes;
+		let mut branches = Vec::new();
+		let mut leaves = Vec::new();
+		let mut depth = 0;
+		let mut visited = Vec::new();
+		let mut stack = Vec::new();
+		if let Some(root) = self.root {
+			stack.push(Distill::Visit(root, 1));
+		}
+		while let Some(work) = stack.pop() {
+			match work {
+				Distill::Visit(id, level) => {
+					let node = nodes[id].take().unwrap();
+					match node.kind {
+						DynamicKind::Leaf(value) => {
+							visited.push(leaves.len() << 1);
+							leaves.push(BVHLeaf {
+								bound: node.bound,
+								value: value,
+							});
+							depth = max(depth, level);
+						},
+						DynamicKind::Branch(left, right) => {
+							stack.push(Distill::Join(node.bound));
+							stack.push(Distill::Visit(right, level + 1));
+							stack.push(Distill::Visit(left, level + 1));
+						},
+					}
+				},
+				Distill::Join(bound) => {
+					let right = visited.pop().unwrap();
+					let left = visited.pop().unwrap();
+					branches.push(BVHBranch {
+						bound: bound,
+						left: left,
+						right: right,
+					});
+					visited.push(((branches.len() - 1) << 1) | 1);
+				},
+			}
+		}
+		BVH {
+			root: visited.pop().unwrap_or(0),
+			depth: depth,
+			branches: branches,
+			leaves: leaves,
+		}
+	}
+}
+
+impl<B, V> Default for DynamicBVH<B, V> {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<B: Bounded + Clone, V> DynamicBVH<B, V> {
+	// refit recomputes the bounds of the branch and its ancestors.
+	fn refit(&mut self, mut id: Option<usize>) {
+		while let Some(i) = id {
+			let node = self.node(i);
+			if let DynamicKind::Branch(left, right) = node.kind {
+				let bound =
+					self.node(left).bound.union(&self.node(right).bound);
+				self.node_mut(i).bound = bound;
+			}
+			id = self.node(i).parent;
+		}
+	}
+
+	/// remove the leaf with the id returned by insert, and returns
+	/// its bound and value, or None if there's no such leaf.
+	pub fn remove(&mut self, id: usize) -> Option<(B, V)> {
+		match self.nodes.get(id) {
+			Some(Some(DynamicNode {
+				kind: DynamicKind::Leaf(_),
+				..
+			})) => {},
+			_ => return None,
+		}
+		let leaf = self.release(id);
+		let value = match leaf.kind {
+			DynamicKind::Leaf(value) => value,
+			DynamicKind::Branch(_, _) => unreachable!(),
+		};
+		let parent = match leaf.parent {
+			Some(parent) => parent,
+			None => {
+				self.root = None;
+				return Some((leaf.bound, value));
+			},
+		};
+
+		// Replace the parent with the sibling of the leaf.
+		let branch = self.release(parent);
+		let sibling = match branch.kind {
+			DynamicKind::Branch(left, right) if left == id => right,
+			DynamicKind::Branch(left, _) => left,
+			DynamicKind::Leaf(_) => unreachable!(),
+		};
+		self.node_mut(sibling).parent = branch.parent;
+		self.replace_child(branch.parent, parent, sibling);
+		self.refit(branch.parent);
+		Some((leaf.bound, value))
+	}
+}
+
+impl<B: Bounded + SurfaceArea + Clone, V> DynamicBVH<B, V> {
+	/// insert the leaf into the hierarchy and returns its id.
+	///
+	/// Starting from the root, the new leaf is paired with the node
+	/// as its sibling, unless the child whose union with the leaf
+	/// has the smaller surface area is still smaller than the node,
+	/// then the search descends into the child. Finally the bounds
+	/// of the ancestors are refitted.
+	pub fn insert(&mut self, bound: B, value: V) -> usize {
+		let leaf = self.alloc(DynamicNode {
+			bound: bound,
+			parent: None,
+			kind: DynamicKind::Leaf(value),
+		});
+		let mut sibling = match self.root {
+			Some(root) => root,
+			None => {
+				self.root = Some(leaf);
+				return leaf;
+			},
+		};
+		let bound = &self.node(leaf).bound;
+		while let DynamicKind::Branch(left, right) =
+			self.node(sibling).kind
+		{
+			let lcost = self.node(left).bound.union(bound).surface_area();
+			let rcost = self.node(right).bound.union(bound).surface_area();
+			let (child, cost) = match lcost <= rcost {
+				true => (left, lcost),
+				false => (right, rcost),
+			};
+			if cost >= self.node(sibling).bound.surface_area() {
+				break;
+			}
+			sibling = child;
+		}
+
+		// Pair the leaf with the sibling under a new branch.
+		let node = self.node(sibling);
+		let parent = node.parent;
+		let union = node.bound.union(bound);
+		let branch = self.alloc(DynamicNode {
+			bound: union,
+			parent: parent,
+			kind: DynamicKind::Branch(sibling, leaf),
+		});
+		self.node_mut(sibling).parent = Some(branch);
+		self.node_mut(leaf).parent = Some(branch);
+		self.replace_child(parent, sibling, branch);
+		self.refit(parent);
+		leaf
+	}
+}
+
+cfg_test! {
+	#[test] fn test_dynamic_bvh_i64_distill() {
+		let items = testdata_bvh_clustered_i64(1000);
+		let mut dynamic = DynamicBVH::new();
+		let ids: Vec<usize> = items
+			.iter()
+			.map(|(aabb, i)| dynamic.insert(*aabb, *i))
+			.collect();
+		for (i, id) in ids.iter().enumerate().step_by(3) {
+			assert_eq!(dynamic.remove(*id).unwrap().1, i);
+			assert!(dynamic.remove(*id).is_none());
+		}
+		// The free slots are reused by later insertions.
+		let len = dynamic.nodes.len();
+		let items: Vec<_> = items
+			.into_iter()
+			.enumerate()
+			.filter(|(i, _)| i % 3 != 0)
+			.map(|(_, item)| item)
+			.chain(testdata_bvh_clustered_i64(1100).into_iter().skip(1000))
+			.collect();
+		for (aabb, i) in &items[items.len() - 100..] {
+			dynamic.insert(*aabb, *i);
+		}
+		assert_eq!(dynamic.nodes.len(), len);
+
+		let bvh = dynamic.distill();
+		assert_bvh_structure(&bvh);
+		assert_eq!(bvh.leaves.len(), items.len());
+		for plane in testdata_bvh_plane3_i64(100) {
+			assert_bvh_query(&bvh, &items, &plane);
+		}
+		for (aabb, _) in &items[..100] {
+			assert_bvh_query(&bvh, &items, aabb);
+		}
+	}
+
+	#[test] fn test_dynamic_bvh_i64_empty() {
+		let bvh = DynamicBVH::<AABB3<i64>, usize>::new().distill();
+		assert_bvh_structure(&bvh);
+		let bvh = DynamicBVH::<AABB3<i64>, usize>::default().distill();
+		assert_bvh_structure(&bvh);
+
+		let mut dynamic = DynamicBVH::new();
+		let items = testdata_bvh_clustered_i64(10);
+		let ids: Vec<usize> = items
+			.iter()
+			.map(|(aabb, i)| dynamic.insert(*aabb, *i))
+			.collect();
+		for id in ids.into_iter().rev() {
+			assert!(dynamic.remove(id).is_some());
+		}
+		assert!(dynamic.root.is_none());
+		let id = dynamic.insert(items[0].0, items[0].1);
+		assert_eq!(dynamic.remove(id).unwrap().1, items[0].1);
+		let bvh = dynamic.distill();
+		assert_bvh_structure(&bvh);
+	}
+}
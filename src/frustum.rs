@@ -0,0 +1,281 @@
+use std::ops::{Add, Mul, Neg};
+
+use crate::{
+	cfg_test, AABBQuery, AABBRelation, Coordinate, Plane3, AABB3,
+};
+
+cfg_test! {
+	use crate::{prng_seeded, gen_vec3_i64, Vec3};
+}
+
+/// Frustum3 is a convex body denoted by the intersection of the
+/// inner half spaces of several planes, e.g. the view frustum for
+/// culling with the near, far, left, right, top and bottom planes.
+///
+/// The frustum without any plane is the whole space.
+#[derive(Clone, Debug)]
+pub struct Frustum3<T, U> {
+	planes: Vec<Plane3<T, U>>,
+}
+
+impl<T, U> Frustum3<T, U> {
+	#[inline(always)]
+	pub fn new(planes: Vec<Plane3<T, U>>) -> Self {
+		Self { planes: planes }
+	}
+
+	/// planes bounding the frustum.
+	#[inline(always)]
+	pub fn planes(&self) -> &[Plane3<T, U>] {
+		&self.planes
+	}
+}
+
+// check_planes compares the AABB with each of the planes, it is
+// Interleave if the AABB is outside any plane, and Include if the
+// AABB is inside all planes.
+#[inline(always)]
+fn check_planes<T, U>(
+	planes: &[Plane3<T, U>], bound: &AABB3<T>,
+) -> AABBRelation
+where
+	T: PartialOrd + Copy + Mul<Output = U>,
+	U: PartialOrd + Copy + Add<Output = U>,
+{
+	let mut result = AABBRelation::Include;
+	for plane in planes {
+		match plane.check(bound) {
+			AABBRelation::Interleave => return AABBRelation::Interleave,
+			AABBRelation::Intersect => result = AABBRelation::Intersect,
+			AABBRelation::Include => {},
+		}
+	}
+	result
+}
+
+/// The frustum is compared with the AABB by each of its planes, it
+/// is Interleave if the AABB is outside any plane, and Include if
+/// the AABB is inside all planes.
+///
+/// This is conservative that AABB outside the frustum might still
+/// be reported as Intersect, e.g. those near the corner of frustum
+/// but straddling its planes. They are just false positives to be
+/// filtered out afterwards.
+impl<T, U> AABBQuery<AABB3<T>> for Frustum3<T, U>
+where
+	T: Coordinate<Product = U>,
+	U: Coordinate,
+{
+	#[inline(always)]
+	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
+		check_planes(&self.planes, bound)
+	}
+}
+
+/// ConvexPolytope3 is a reusable convex region denoted by the
+/// intersection of the inner half spaces of the planes, which is
+/// compared with the AABB in the same way as Frustum3.
+///
+/// The orientation of each normal, which picks the corners of the
+/// AABB to compare, is evaluated only once on creating the planes,
+/// so only the dot products are evaluated for each checked AABB.
+#[derive(Clone, Debug)]
+pub struct ConvexPolytope3<T, U> {
+	planes: Vec<Plane3<T, U>>,
+}
+
+impl<T, U> ConvexPolytope3<T, U> {
+	#[inline(always)]
+	pub fn new(planes: Vec<Plane3<T, U>>) -> Self {
+		Self { planes: planes }
+	}
+
+	/// planes bounding the polytope.
+	#[inline(always)]
+	pub fn planes(&self) -> &[Plane3<T, U>] {
+		&self.planes
+	}
+}
+
+impl<T, U> ConvexPolytope3<T, U>
+where
+	T: Coordinate<Product = U> + From<u8> + Neg<Output = T>,
+	U: Add<Output = U>,
+{
+	/// from_aabb creates the polytope of the AABB bounded by the six
+	/// planes of its faces, see also AABB3::surface_planes.
+	///
+	/// Please notice the planes are closed half spaces, so the boxes
+	/// merely touching a face, an edge or a corner of the AABB are
+	/// Intersect with the polytope, while they are Interleave with
+	/// the AABB itself as a query body. The polytope only agrees with
+	/// the AABB on the boxes not touching its surface from outside.
+	pub fn from_aabb(aabb: &AABB3<T>) -> Self {
+		Self::new(aabb.surface_planes().into())
+	}
+}
+
+impl<T, U> AABBQuery<AABB3<T>> for ConvexPolytope3<T, U>
+where
+	T: Coordinate<Product = U>,
+	U: Coordinate,
+{
+	#[inline(always)]
+	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
+		check_planes(&self.planes, bound)
+	}
+}
+
+cfg_test! {
+	fn testdata_frustum3_i64_box(
+		lo: (i64, i64, i64), hi: (i64, i64, i64),
+	) -> Frustum3<i64, i64> {
+		let (lo, hi) = (Vec3::from(lo), Vec3::from(hi));
+		Frustum3::new(vec![
+			Plane3::new(lo, Vec3::new(-1, 0, 0)),
+			Plane3::new(lo, Vec3::new(0, -1, 0)),
+			Plane3::new(lo, Vec3::new(0, 0, -1)),
+			Plane3::new(hi, Vec3::new(1, 0, 0)),
+			Plane3::new(hi, Vec3::new(0, 1, 0)),
+			Plane3::new(hi, Vec3::new(0, 0, 1)),
+		])
+	}
+
+	#[test] fn test_frustum3_i64_box_query() {
+		let frustum = testdata_frustum3_i64_box((0, 0, 0), (10, 10, 10));
+		let check = |p0: (i64, i64, i64), p1: (i64, i64, i64)| {
+			frustum.check(&AABB3::new(p0.into(), p1.into()))
+		};
+		assert_eq!(check((1, 1, 1), (9, 9, 9)), AABBRelation::Include);
+		assert_eq!(check((2, 3, 4), (5, 6, 7)), AABBRelation::Include);
+		assert_eq!(check((0, 1, 1), (9, 9, 9)), AABBRelation::Intersect);
+		assert_eq!(check((-5, 1, 1), (5, 9, 9)), AABBRelation::Intersect);
+		assert_eq!(
+			check((-5, -5, -5), (15, 15, 15)),
+			AABBRelation::Intersect,
+		);
+		assert_eq!(
+			check((9, 9, 9), (11, 11, 11)),
+			AABBRelation::Intersect,
+		);
+		assert_eq!(
+			check((11, 1, 1), (12, 9, 9)),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(
+			check((1, -3, 1), (9, -1, 9)),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(
+			check((1, 1, 11), (9, 9, 20)),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(
+			check((11, 11, 11), (20, 20, 20)),
+			AABBRelation::Interleave,
+		);
+
+		let whole = Frustum3::<i64, i64>::new(vec![]);
+		assert_eq!(
+			whole.check(&AABB3::new(
+				Vec3::new(-1, -1, -1),
+				Vec3::new(1, 1, 1),
+			)),
+			AABBRelation::Include,
+		);
+	}
+
+	#[test] fn test_frustum3_i64_corner_query() {
+		// The frustum is the octahedron |x| + |y| + |z| <= 6, and the
+		// AABB near its vertex straddles four planes but is outside.
+		let mut planes = Vec::new();
+		for x in [-1, 1] {
+			for y in [-1, 1] {
+				for z in [-1, 1] {
+					let n = Vec3::new(x, y, z);
+					planes.push(Plane3::new(Vec3::new(6 * x, 0, 0), n));
+				}
+			}
+		}
+		let frustum = Frustum3::new(planes);
+		let check = |p0: (i64, i64, i64), p1: (i64, i64, i64)| {
+			frustum.check(&AABB3::new(p0.into(), p1.into()))
+		};
+		assert_eq!(check((-1, -1, -1), (1, 1, 1)), AABBRelation::Include);
+		assert_eq!(check((1, 1, 1), (3, 3, 3)), AABBRelation::Intersect);
+		assert_eq!(check((3, 3, 3), (4, 4, 4)), AABBRelation::Interleave);
+		assert_eq!(
+			check((-4, 3, -3), (-3, 4, -2)),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(check((7, -1, -1), (8, 1, 1)), AABBRelation::Intersect);
+	}
+
+	#[test] fn test_convex_polytope3_i64_from_aabb() {
+		let rng = &mut prng_seeded().0;
+		for _ in 0..100000 {
+			let query = AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng));
+			let aabb = AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng));
+			let polytope = ConvexPolytope3::from_aabb(&query);
+			let actual = polytope.check(&aabb);
+			let expected = query.check(&aabb);
+			assert_eq!(
+				actual == AABBRelation::Interleave,
+				expected == AABBRelation::Interleave,
+				"query = {:?}, aabb = {:?}",
+				query, aabb,
+			);
+			if actual == AABBRelation::Include {
+				assert!(query.contains(&aabb));
+			}
+		}
+
+		let query = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(10, 10, 10));
+		let polytope = ConvexPolytope3::from_aabb(&query);
+		assert_eq!(polytope.planes().len(), 6);
+		let check = |p0: (i64, i64, i64), p1: (i64, i64, i64)| {
+			let aabb = AABB3::new(p0.into(), p1.into());
+			(polytope.check(&aabb), query.check(&aabb))
+		};
+		assert_eq!(
+			check((1, 1, 1), (9, 9, 9)),
+			(AABBRelation::Include, AABBRelation::Include),
+		);
+		assert_eq!(
+			check((5, 5, 5), (15, 15, 15)),
+			(AABBRelation::Intersect, AABBRelation::Intersect),
+		);
+		assert_eq!(
+			check((11, 1, 1), (12, 9, 9)),
+			(AABBRelation::Interleave, AABBRelation::Interleave),
+		);
+		assert_eq!(
+			check((-9, -9, -9), (-1, 20, 20)),
+			(AABBRelation::Interleave, AABBRelation::Interleave),
+		);
+
+		// The boxes touching the face, the edge or the corner from
+		// outside are only Intersect with the closed half spaces.
+		assert_eq!(
+			check((10, 0, 0), (11, 1, 1)),
+			(AABBRelation::Intersect, AABBRelation::Interleave),
+		);
+		assert_eq!(
+			check((10, 10, 0), (11, 11, 1)),
+			(AABBRelation::Intersect, AABBRelation::Interleave),
+		);
+		assert_eq!(
+			check((10, 10, 10), (11, 11, 11)),
+			(AABBRelation::Intersect, AABBRelation::Interleave),
+		);
+		assert_eq!(
+			check((-1, -1, -1), (0, 0, 0)),
+			(AABBRelation::Intersect, AABBRelation::Interleave),
+		);
+		// The boxes touching the surface from inside agree.
+		assert_eq!(
+			check((5, 5, 5), (10, 10, 10)),
+			(AABBRelation::Intersect, AABBRelation::Intersect),
+		);
+	}
+}
@@ -0,0 +1,258 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{cfg_test, AABBQuery, AABBRelation, Vec3, AABB3};
+
+cfg_test! {
+	extern crate test;
+
+	use rand::Rng;
+	use test::Bencher;
+	use crate::{prng, prng_seeded, Plane3};
+}
+
+/// SpatialGrid3 is the spatial hash grid indexing the values by
+/// their bounds, which is queried with the same AABBQuery of BVH.
+///
+/// The space is divided into the cubic cells of the fixed size, and
+/// each value is bucketed into all cells overlapped by its bound, so
+/// the grid is cheap to update and suits the values that are nearly
+/// uniformly distributed and not much larger than the cells. Each
+/// inserted value is identified by the id returned by insert, which
+/// remains valid until the value is removed.
+///
+/// The coordinates are fixed to i64, since the cells are indexed by
+/// flooring the coordinates, and the bounds must not overflow after
+/// flooring to the cell size.
+pub struct SpatialGrid3<V> {
+	cell: i64,
+	cells: HashMap<Vec3<i64>, Vec<usize>>,
+	entries: Vec<Option<(AABB3<i64>, V)>>,
+	free: Vec<usize>,
+}
+
+impl<V> SpatialGrid3<V> {
+	/// new creates the empty grid with the specified cell size.
+	pub fn new(cell: i64) -> Self {
+		assert!(cell > 0, "cell size {} is not positive", cell);
+		Self {
+			cell: cell,
+			cells: HashMap::new(),
+			entries: Vec::new(),
+			free: Vec::new(),
+		}
+	}
+
+	/// cell_size returns the size of the cells of the grid.
+	pub fn cell_size(&self) -> i64 {
+		self.cell
+	}
+
+	/// len returns the number of values in the grid.
+	pub fn len(&self) -> usize {
+		self.entries.len() - self.free.len()
+	}
+
+	/// is_empty checks whether there's no value in the grid.
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	// cell_range evaluates the range of cells overlapped by the
+	// bound, which is inclusive on both ends.
+	fn cell_range(&self, bound: &AABB3<i64>) -> (Vec3<i64>, Vec3<i64>) {
+		let floor = |x: i64| x.div_euclid(self.cell);
+		(bound.min().map(floor), bound.max().map(floor))
+	}
+
+	// for_cells calls the function with each cell overlapped by the
+	// bound.
+	fn for_cells(&self, bound: &AABB3<i64>, mut f: impl FnMut(Vec3<i64>)) {
+		let (lo, hi) = self.cell_range(bound);
+		for x in lo.0..=hi.0 {
+			for y in lo.1..=hi.1 {
+				for z in lo.2..=hi.2 {
+					f(Vec3::new(x, y, z));
+				}
+			}
+		}
+	}
+
+	/// insert the value with its bound and returns its id.
+	pub fn insert(&mut self, bound: AABB3<i64>, value: V) -> usize {
+		let id = match self.free.pop() {
+			Some(id) => id,
+			None => {
+				self.entries.push(None);
+				self.entries.len() - 1
+			},
+		};
+		self.entries[id] = Some((bound, value));
+		let mut cells = Vec::new();
+		self.for_cells(&bound, |c| cells.push(c));
+		for c in cells {
+			self.cells.entry(c).or_default().push(id);
+		}
+		id
+	}
+
+	/// remove the value with the id returned by insert, and returns
+	/// its bound and value, or None if there's no such value.
+	pub fn remove(&mut self, id: usize) -> Option<(AABB3<i64>, V)> {
+		let (bound, value) = self.entries.get_mut(id)?.take()?;
+		self.free.push(id);
+		let mut cells = Vec::new();
+		self.for_cells(&bound, |c| cells.push(c));
+		for c in cells {
+			let ids = self.cells.get_mut(&c).unwrap();
+			let index = ids.iter().position(|x| *x == id).unwrap();
+			ids.swap_remove(index);
+			if ids.is_empty() {
+				self.cells.remove(&c);
+			}
+		}
+		Some((bound, value))
+	}
+
+	/// query for all values whose bounds are hit by the AABB query.
+	///
+	/// The occupied cells are compared with the query body first,
+	/// and the values in the cells not outside the query body are
+	/// checked by their bounds then, so the query body must not be
+	/// outside a cell when it hits the part of a bound inside the
+	/// cell, which holds for the convex query bodies. Each value is
+	/// returned once even if it spans multiple cells, in no specified
+	/// order.
+	///
+	/// Please notice the query body tells nothing about its extent,
+	/// so all occupied cells are compared with it, and each query
+	/// costs time linear to the number of occupied cells, even if it
+	/// hits only a few values.
+	pub fn query<'a, 'b: 'a>(
+		&'b self, q: &'a impl AABBQuery<AABB3<i64>>,
+	) -> impl 'a + Iterator<Item = &'b V> {
+		let cell = self.cell;
+		let mut seen = HashSet::new();
+		self.cells
+			.iter()
+			.filter(move |(c, _)| {
+				// The cell is grown by one on each side, otherwise the
+				// query body touching the boundary of the cells would
+				// be outside all of them, while still hitting the
+				// values on the boundary.
+				let lo = c.map(|x| x * cell - 1);
+				let hi = lo.map(|x| x + cell + 2);
+				q.check(&AABB3::new(lo, hi)) != AABBRelation::Interleave
+			})
+			.flat_map(|(_, ids)| ids.iter())
+			.filter_map(move |id| {
+				if !seen.insert(*id) {
+					return None;
+				}
+				let (bound, value) = self.entries[*id].as_ref().unwrap();
+				(q.check(bound) != AABBRelation::Interleave)
+					.then_some(value)
+			})
+	}
+}
+
+cfg_test! {
+	fn gen_aabb3_i64_tiny(rng: &mut impl Rng) -> AABB3<i64> {
+		let mut gen = || {
+			Vec3::new(
+				rng.gen_range(-64, 64),
+				rng.gen_range(-64, 64),
+				rng.gen_range(-64, 64),
+			)
+		};
+		let p = gen();
+		AABB3::new(p, p + gen().map(|x| x / 4))
+	}
+
+	// naive_grid3_i64 filters the values hit by the query one by
+	// one, and returns them sorted.
+	fn naive_grid3_i64(
+		items: &[(AABB3<i64>, usize)], q: &impl AABBQuery<AABB3<i64>>,
+	) -> Vec<usize> {
+		let mut result: Vec<usize> = items
+			.iter()
+			.filter(|(b, _)| q.check(b) != AABBRelation::Interleave)
+			.map(|x| x.1)
+			.collect();
+		result.sort();
+		result
+	}
+
+	#[test] fn test_spatial_grid3_i64_query() {
+		let rng = &mut prng_seeded().0;
+		let mut grid = SpatialGrid3::new(16);
+		let mut items: Vec<_> = (0..1000)
+			.map(|i| (gen_aabb3_i64_tiny(rng), i))
+			.collect();
+		let ids: Vec<usize> = items
+			.iter()
+			.map(|(b, i)| grid.insert(*b, *i))
+			.collect();
+		for id in ids.iter().step_by(3) {
+			assert!(grid.remove(*id).is_some());
+			assert!(grid.remove(*id).is_none());
+		}
+		items.retain(|x| x.1 % 3 != 0);
+		assert_eq!(grid.len(), items.len());
+		// The free slots are reused by later insertions.
+		for i in 1000..1100 {
+			let bound = gen_aabb3_i64_tiny(rng);
+			assert!(grid.insert(bound, i) < 1000);
+			items.push((bound, i));
+		}
+		assert!(items.iter().any(|(b, _)| {
+			let (lo, hi) = grid.cell_range(b);
+			lo != hi
+		}));
+
+		for _ in 0..100 {
+			let aabb = gen_aabb3_i64_tiny(rng);
+			let mut actual: Vec<usize> =
+				grid.query(&aabb).copied().collect();
+			actual.sort();
+			assert_eq!(actual, naive_grid3_i64(&items, &aabb));
+
+			let mut normal = gen_aabb3_i64_tiny(rng).min();
+			while (normal ^ normal) == 0 {
+				normal = gen_aabb3_i64_tiny(rng).min();
+			}
+			let point = gen_aabb3_i64_tiny(rng).min();
+			let plane = Plane3::new(point, normal);
+			let mut actual: Vec<usize> =
+				grid.query(&plane).copied().collect();
+			actual.sort();
+			assert_eq!(actual, naive_grid3_i64(&items, &plane));
+		}
+
+		let empty = SpatialGrid3::<usize>::new(1);
+		assert!(empty.is_empty());
+		assert_eq!(empty.query(&items[0].0).count(), 0);
+	}
+
+	#[test]
+	#[should_panic(expected = "cell size 0 is not positive")]
+	fn test_spatial_grid3_i64_zero_cell() {
+		SpatialGrid3::<usize>::new(0);
+	}
+
+	#[bench] fn bench_spatial_grid3_i64_query(b: &mut Bencher) {
+		const POW2: usize = 1 << 6;
+		let rng = &mut prng();
+		let mut grid = SpatialGrid3::new(16);
+		for i in 0..(1 << 14) {
+			grid.insert(gen_aabb3_i64_tiny(rng), i);
+		}
+		let queries: Vec<_> =
+			(0..POW2).map(|_| gen_aabb3_i64_tiny(rng)).collect();
+		let mut i = 0;
+		b.iter(|| {
+			let j = i;
+			i = (i + 1) & (POW2 - 1);
+			grid.query(&queries[j]).count()
+		});
+	}
+}
@@ -0,0 +1,254 @@
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+use crate::bvh::{BVHBranch, BVHLeaf};
+use crate::{cfg_test, Vec3, AABB3, BVH};
+
+cfg_test! {
+	use crate::{testdata_bvh_i64, assert_bvh_structure, Plane3};
+}
+
+/// Pod is the value encoded into a fixed number of bytes, so that
+/// the nodes of BVH can be laid out as arrays of fixed size records.
+pub trait Pod: Sized {
+	/// SIZE is the number of bytes of the encoded value.
+	const SIZE: usize;
+
+	/// encode the value into the buffer of SIZE bytes.
+	fn encode(&self, buf: &mut [u8]);
+
+	/// decode the value from the buffer of SIZE bytes.
+	fn decode(buf: &[u8]) -> Self;
+}
+
+macro_rules! impl_pod {
+	($($t:ty),*) => {
+		$(
+			impl Pod for $t {
+				const SIZE: usize = std::mem::size_of::<$t>();
+
+				fn encode(&self, buf: &mut [u8]) {
+					buf.copy_from_slice(&self.to_le_bytes());
+				}
+
+				fn decode(buf: &[u8]) -> Self {
+					Self::from_le_bytes(buf.try_into().unwrap())
+				}
+			}
+		)*
+	};
+}
+
+impl_pod!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128);
+
+/// usize is always encoded as u64, so that the format is portable
+/// across the platforms.
+///
+/// Please notice decoding panics if the value overflows usize, which
+/// is possible on the platforms whose usize is narrower than u64,
+/// see also BVH::read_from.
+impl Pod for usize {
+	const SIZE: usize = 8;
+
+	fn encode(&self, buf: &mut [u8]) {
+		(*self as u64).encode(buf)
+	}
+
+	fn decode(buf: &[u8]) -> Self {
+		let v = u64::decode(buf);
+		usize::try_from(v)
+			.unwrap_or_else(|_| panic!("u64 {} overflows usize", v))
+	}
+}
+
+// decode_index decodes the count or index of nodes encoded as u64,
+// which might overflow usize on the narrower platforms.
+fn decode_index(buf: &[u8]) -> std::result::Result<usize, String> {
+	let v = u64::decode(buf);
+	usize::try_from(v).map_err(|_| format!("index {} overflows usize", v))
+}
+
+impl<T: Pod> Pod for Vec3<T> {
+	const SIZE: usize = T::SIZE * 3;
+
+	fn encode(&self, buf: &mut [u8]) {
+		let (b0, b) = buf.split_at_mut(T::SIZE);
+		let (b1, b2) = b.split_at_mut(T::SIZE);
+		self.0.encode(b0);
+		self.1.encode(b1);
+		self.2.encode(b2);
+	}
+
+	fn decode(buf: &[u8]) -> Self {
+		let (b0, b) = buf.split_at(T::SIZE);
+		let (b1, b2) = b.split_at(T::SIZE);
+		Vec3::new(T::decode(b0), T::decode(b1), T::decode(b2))
+	}
+}
+
+/// AABB3 is encoded as its lowest corner followed by the highest,
+/// and the intervals are reordered on decoding.
+impl<T: Pod + PartialOrd + Copy> Pod for AABB3<T> {
+	const SIZE: usize = Vec3::<T>::SIZE * 2;
+
+	fn encode(&self, buf: &mut [u8]) {
+		let (lo, hi) = buf.split_at_mut(Vec3::<T>::SIZE);
+		self.min().encode(lo);
+		self.max().encode(hi);
+	}
+
+	fn decode(buf: &[u8]) -> Self {
+		let (lo, hi) = buf.split_at(Vec3::<T>::SIZE);
+		AABB3::new(Vec3::decode(lo), Vec3::decode(hi))
+	}
+}
+
+// MAGIC is the leading bytes of the baked BVH.
+const MAGIC: [u8; 4] = *b"SBVH";
+
+// VERSION is the version of the format, which is bumped on every
+// incompatible change of the layout.
+const VERSION: u32 = 1;
+
+// HEADER_SIZE is the size of magic, version, branch count, leaf count
+// and the encoded root index.
+const HEADER_SIZE: usize = 4 + 4 + 8 + 8 + 8;
+
+impl<B: Pod, V: Pod> BVH<B, V> {
+	/// write_to bakes the BVH into the writer.
+	///
+	/// The header consists of the magic bytes, the version, the
+	/// number of branches and leaves and the root index, followed by
+	/// the array of branches and the array of leaves. Every integer
+	/// is little-endian and every record is of fixed size, so that
+	/// the nodes can be located without parsing.
+	pub fn write_to<W: Write>(&self, w: &mut W) -> Result<()> {
+		let mut header = [0u8; HEADER_SIZE];
+		header[0..4].copy_from_slice(&MAGIC);
+		VERSION.encode(&mut header[4..8]);
+		self.branches.len().encode(&mut header[8..16]);
+		self.leaves.len().encode(&mut header[16..24]);
+		self.root.encode(&mut header[24..32]);
+		w.write_all(&header)?;
+
+		let mut buf = vec![0u8; B::SIZE + 16];
+		for branch in &self.branches {
+			let (bound, b) = buf.split_at_mut(B::SIZE);
+			branch.bound.encode(bound);
+			branch.left.encode(&mut b[0..8]);
+			branch.right.encode(&mut b[8..16]);
+			w.write_all(&buf)?;
+		}
+		let mut buf = vec![0u8; B::SIZE + V::SIZE];
+		for leaf in &self.leaves {
+			let (bound, value) = buf.split_at_mut(B::SIZE);
+			leaf.bound.encode(bound);
+			leaf.value.encode(value);
+			w.write_all(&buf)?;
+		}
+		Ok(())
+	}
+
+	/// read_from loads the BVH baked by write_to from the reader.
+	///
+	/// Truncated input is reported as ErrorKind::UnexpectedEof, while
+	/// mismatched magic bytes or version, malformed structure of the
+	/// nodes, and the counts or indices overflowing usize are reported
+	/// as ErrorKind::InvalidData.
+	pub fn read_from<R: Read>(r: &mut R) -> Result<Self> {
+		let invalid =
+			|msg: String| Error::new(ErrorKind::InvalidData, msg);
+		let mut header = [0u8; HEADER_SIZE];
+		r.read_exact(&mut header)?;
+		if header[0..4] != MAGIC {
+			return Err(invalid("mismatched magic bytes".to_string()));
+		}
+		let version = u32::decode(&header[4..8]);
+		if version != VERSION {
+			return Err(invalid(format!(
+				"unsupported version {}",
+				version
+			)));
+		}
+		let num_branches =
+			decode_index(&header[8..16]).map_err(invalid)?;
+		let num_leaves = decode_index(&header[16..24]).map_err(invalid)?;
+		let root = decode_index(&header[24..32]).map_err(invalid)?;
+
+		// XXX: the counts are not trusted for preallocating, since
+		// they might be too large for malformed input, which should
+		// be reported as truncated instead of aborting.
+		let mut branches = Vec::new();
+		let mut buf = vec![0u8; B::SIZE + 16];
+		for _ in 0..num_branches {
+			r.read_exact(&mut buf)?;
+			let (bound, b) = buf.split_at(B::SIZE);
+			branches.push(BVHBranch {
+				bound: B::decode(bound),
+				left: decode_index(&b[0..8]).map_err(invalid)?,
+				right: decode_index(&b[8..16]).map_err(invalid)?,
+			});
+		}
+		let mut leaves = Vec::new();
+		let mut buf = vec![0u8; B::SIZE + V::SIZE];
+		for _ in 0..num_leaves {
+			r.read_exact(&mut buf)?;
+			let (bound, value) = buf.split_at(B::SIZE);
+			leaves.push(BVHLeaf {
+				bound: B::decode(bound),
+				value: V::decode(value),
+			});
+		}
+		BVH::from_nodes(root, branches, leaves).map_err(invalid)
+	}
+}
+
+cfg_test! {
+	#[test] fn test_bvh_i64_write_read() {
+		let items = testdata_bvh_i64(1000);
+		let bvh = BVH::build(items.clone());
+		let mut buf = Vec::new();
+		bvh.write_to(&mut buf).unwrap();
+		assert_eq!(buf.len(), 32 + 999 * (48 + 16) + 1000 * (48 + 8));
+
+		let restored: BVH<AABB3<i64>, usize> =
+			BVH::read_from(&mut buf.as_slice()).unwrap();
+		assert_bvh_structure(&restored);
+		let mut rebuf = Vec::new();
+		restored.write_to(&mut rebuf).unwrap();
+		assert_eq!(buf, rebuf);
+		let plane = Plane3::new(Vec3::new(0, 0, 0), Vec3::new(1, 1, 1));
+		assert!(bvh.query(&plane).eq(restored.query(&plane)));
+
+		let empty = BVH::<AABB3<i64>, usize>::build(Vec::new());
+		let mut buf = Vec::new();
+		empty.write_to(&mut buf).unwrap();
+		let restored: BVH<AABB3<i64>, usize> =
+			BVH::read_from(&mut buf.as_slice()).unwrap();
+		assert_eq!(restored.leaves.len(), 0);
+	}
+
+	#[test] fn test_bvh_i64_read_malformed() {
+		let bvh = BVH::build(testdata_bvh_i64(10));
+		let mut buf = Vec::new();
+		bvh.write_to(&mut buf).unwrap();
+		let read = |buf: &[u8]| {
+			let mut r = buf;
+			BVH::<AABB3<i64>, usize>::read_from(&mut r).err().unwrap()
+		};
+		for len in [0, 3, 31, 32, 100, buf.len() - 1] {
+			assert_eq!(read(&buf[..len]).kind(), ErrorKind::UnexpectedEof);
+		}
+
+		let mut version = buf.clone();
+		version[4] = 2;
+		assert_eq!(read(&version).kind(), ErrorKind::InvalidData);
+		let mut magic = buf.clone();
+		magic[0] = b'X';
+		assert_eq!(read(&magic).kind(), ErrorKind::InvalidData);
+
+		// Points the left child of the first branch out of range.
+		let mut node = buf.clone();
+		node[32 + 48..32 + 56].copy_from_slice(&999u64.to_le_bytes());
+		assert_eq!(read(&node).kind(), ErrorKind::InvalidData);
+	}
+}
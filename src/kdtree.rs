@@ -0,0 +1,198 @@
+use crate::{cfg_test, AABBQuery, AABBRelation, Vec3, AABB3};
+
+cfg_test! {
+	extern crate test;
+
+	use test::Bencher;
+	use crate::{prng, prng_seeded, gen_vec3_i64, Plane3};
+}
+
+/// KDTree3 is the K-D tree indexing three dimensional points, which
+/// is queried with the same AABBQuery of BVH.
+///
+/// The points are split at the median alternately along the x, y
+/// and z axes, and the tree is implicitly laid out in the list of
+/// points, that each range of points is a subtree whose root is the
+/// point at the middle. The bound of each subtree is kept for the
+/// root, so that the subtrees are pruned and included in the same
+/// way as the branches of BVH.
+pub struct KDTree3<T, V> {
+	points: Vec<(Vec3<T>, V)>,
+	bounds: Vec<AABB3<T>>,
+}
+
+// split_points reorders the points so that the point at the middle
+// is the median along the axis, and the same is recursively done to
+// the lower and the upper halves along the next axis.
+fn split_points<T: Ord + Copy, V>(
+	points: &mut [(Vec3<T>, V)], axis: usize,
+) {
+	if points.len() <= 1 {
+		return;
+	}
+	let mid = points.len() / 2;
+	points.select_nth_unstable_by_key(mid, |x| x.0[axis]);
+	let (lower, upper) = points.split_at_mut(mid);
+	split_points(lower, (axis + 1) % 3);
+	split_points(&mut upper[1..], (axis + 1) % 3);
+}
+
+// fill_bounds evaluates the bound of the subtree of the non-empty
+// range of points, and stores it at the middle of the range.
+fn fill_bounds<T: Ord + Copy, V>(
+	points: &[(Vec3<T>, V)], bounds: &mut [AABB3<T>],
+) -> AABB3<T> {
+	let mid = points.len() / 2;
+	let mut bound = bounds[mid];
+	if mid > 0 {
+		let lower = fill_bounds(&points[..mid], &mut bounds[..mid]);
+		bound = bound.extends(&lower);
+	}
+	if mid + 1 < points.len() {
+		let upper =
+			fill_bounds(&points[mid + 1..], &mut bounds[mid + 1..]);
+		bound = bound.extends(&upper);
+	}
+	bounds[mid] = bound;
+	bound
+}
+
+impl<T: Ord + Copy, V> KDTree3<T, V> {
+	/// build the K-D tree from the specified points.
+	pub fn build(mut points: Vec<(Vec3<T>, V)>) -> Self {
+		split_points(&mut points, 0);
+		let mut bounds: Vec<AABB3<T>> =
+			points.iter().map(|x| AABB3::new(x.0, x.0)).collect();
+		if !points.is_empty() {
+			fill_bounds(&points, &mut bounds);
+		}
+		Self {
+			points: points,
+			bounds: bounds,
+		}
+	}
+
+	/// len returns the number of points in the K-D tree.
+	pub fn len(&self) -> usize {
+		self.points.len()
+	}
+
+	/// is_empty checks whether there's no point in the K-D tree.
+	pub fn is_empty(&self) -> bool {
+		self.points.is_empty()
+	}
+
+	/// query for all items whose points are hit by the AABB query.
+	///
+	/// Each point is checked as a degenerate AABB, and those either
+	/// included in or intersecting with the query body are returned,
+	/// while all points in an included subtree are returned without
+	/// checking them. The items are in no specified order.
+	pub fn query<'a, 'b: 'a>(
+		&'b self, q: &'a impl AABBQuery<AABB3<T>>,
+	) -> impl 'a + Iterator<Item = &'b V> {
+		let mut stack = Vec::new();
+		if !self.points.is_empty() {
+			stack.push((0, self.points.len()));
+		}
+		let mut pending: std::slice::Iter<'b, (Vec3<T>, V)> = [].iter();
+		std::iter::from_fn(move || loop {
+			if let Some(x) = pending.next() {
+				return Some(&x.1);
+			}
+			let (lo, hi) = stack.pop()?;
+			let mid = lo + (hi - lo) / 2;
+			match q.check(&self.bounds[mid]) {
+				AABBRelation::Interleave => {},
+				AABBRelation::Include => {
+					pending = self.points[lo..hi].iter();
+				},
+				AABBRelation::Intersect => {
+					if mid + 1 < hi {
+						stack.push((mid + 1, hi));
+					}
+					if lo < mid {
+						stack.push((lo, mid));
+					}
+					let (p, value) = &self.points[mid];
+					let point = AABB3::new(*p, *p);
+					if q.check(&point) != AABBRelation::Interleave {
+						return Some(value);
+					}
+				},
+			}
+		})
+	}
+}
+
+cfg_test! {
+	fn testdata_kdtree3_i64(size: usize) -> Vec<(Vec3<i64>, usize)> {
+		let rng = &mut prng();
+		(0..size).map(|i| (gen_vec3_i64(rng), i)).collect()
+	}
+
+	// naive_kdtree3_i64 filters the points hit by the query one by
+	// one, and returns them sorted.
+	fn naive_kdtree3_i64(
+		points: &[(Vec3<i64>, usize)], q: &impl AABBQuery<AABB3<i64>>,
+	) -> Vec<usize> {
+		let mut result: Vec<usize> = points
+			.iter()
+			.filter(|(p, _)| {
+				q.check(&AABB3::new(*p, *p)) != AABBRelation::Interleave
+			})
+			.map(|x| x.1)
+			.collect();
+		result.sort();
+		result
+	}
+
+	#[test] fn test_kdtree3_i64_query() {
+		let rng = &mut prng_seeded().0;
+		for size in [0, 1, 2, 3, 1000] {
+			let mut points: Vec<_> = (0..size)
+				.map(|i| (gen_vec3_i64(rng), i))
+				.collect();
+			// Duplicated points are also indexed.
+			if size > 1 {
+				points[1].0 = points[0].0;
+			}
+			let tree = KDTree3::build(points.clone());
+			assert_eq!(tree.len(), size);
+			assert_eq!(tree.is_empty(), size == 0);
+			for _ in 0..100 {
+				let mut normal = gen_vec3_i64(rng);
+				while (normal ^ normal) == 0 {
+					normal = gen_vec3_i64(rng);
+				}
+				let plane = Plane3::new(gen_vec3_i64(rng), normal);
+				let mut actual: Vec<usize> =
+					tree.query(&plane).copied().collect();
+				actual.sort();
+				assert_eq!(actual, naive_kdtree3_i64(&points, &plane));
+
+				let aabb =
+					AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng));
+				let mut actual: Vec<usize> =
+					tree.query(&aabb).copied().collect();
+				actual.sort();
+				assert_eq!(actual, naive_kdtree3_i64(&points, &aabb));
+			}
+		}
+	}
+
+	#[bench] fn bench_kdtree3_i64_query(b: &mut Bencher) {
+		const POW2: usize = 1 << 6;
+		let tree = KDTree3::build(testdata_kdtree3_i64(1 << 14));
+		let rng = &mut prng();
+		let queries: Vec<_> = (0..POW2)
+			.map(|_| AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng)))
+			.collect();
+		let mut i = 0;
+		b.iter(|| {
+			let j = i;
+			i = (i + 1) & (POW2 - 1);
+			tree.query(&queries[j]).count()
+		});
+	}
+}
@@ -1,15 +1,45 @@
-#![feature(test, generators, generator_trait, unboxed_closures, fn_traits)]
+#![cfg_attr(test, feature(test))]
 mod vector;
 pub use vector::*;
+mod vector2;
+pub use vector2::*;
+mod vectorn;
+pub use vectorn::*;
+mod coordinate;
+pub use coordinate::*;
 mod aabb;
 pub use aabb::*;
+mod aabb2;
+pub use aabb2::*;
 mod bvh;
 pub use bvh::*;
-mod generator;
-use generator::*;
+mod dynamic;
+pub use dynamic::*;
+mod kdtree;
+pub use kdtree::*;
+mod grid;
+pub use grid::*;
 mod plane;
 pub use plane::*;
+mod line;
+pub use line::*;
+mod sphere;
+pub use sphere::*;
+mod ray;
+pub use ray::*;
+mod point;
+pub use point::*;
+mod frustum;
+pub use frustum::*;
+mod cone;
+pub use cone::*;
+mod voxel;
+pub use voxel::*;
+mod io;
+pub use io::*;
 mod cfg;
+#[cfg(any(test, feature = "testutil"))]
+pub mod naive;
 use cfg::*;
 
 cfg_test! {
@@ -0,0 +1,170 @@
+use std::cmp::Ordering;
+use std::ops::{Add, Mul};
+
+use crate::{cfg_test, AABBQuery, AABBRelation, Vec2, AABB2};
+
+cfg_test! {
+	use std::ops::Sub;
+	use crate::{prng_seeded, gen_vec2_i64, BVH};
+}
+
+/// Line2 is a two dimensional line denoted by a point on the line
+/// and the normal of the line, the counterpart of Plane3 on the
+/// plane.
+///
+/// The points below the line (with respect to the normal vector)
+/// are thought of as inside an infinite body whose edge is the
+/// line, see also Plane3.
+#[derive(Copy, Clone, Debug)]
+pub struct Line2<T, U> {
+	normal: Vec2<T>,
+	dir: Vec2<Ordering>,
+	distance: U,
+}
+
+impl<T, U> Line2<T, U>
+where
+	T: Ord + Copy + Mul<Output = U> + Default,
+	U: Add<Output = U>,
+{
+	#[inline(always)]
+	pub fn new(point: Vec2<T>, normal: Vec2<T>) -> Self {
+		Self {
+			normal: normal,
+			dir: normal.to_ordering(),
+			distance: point ^ normal,
+		}
+	}
+}
+
+impl<T, U> AABBQuery<AABB2<T>> for Line2<T, U>
+where
+	T: PartialOrd + Copy + Mul<Output = U>,
+	U: PartialOrd + Copy + Add<Output = U>,
+{
+	#[inline(always)]
+	fn check(&self, bound: &AABB2<T>) -> AABBRelation {
+		let (vn, vp) = bound.from_ordering(self.dir).unzip();
+		let dp = vp ^ self.normal;
+		let dn = vn ^ self.normal;
+		if dp > self.distance {
+			if dn >= self.distance {
+				return AABBRelation::Interleave;
+			}
+		} else {
+			if dn < self.distance {
+				return AABBRelation::Include;
+			}
+		}
+		AABBRelation::Intersect
+	}
+}
+
+cfg_test! {
+	fn testdata_aabb2_line2_i64(
+		size: usize,
+	) -> Vec<(Vec2<i64>, Vec2<i64>, AABB2<i64>)> {
+		let rng = &mut prng_seeded().0;
+		let mut result = Vec::new();
+		for _ in 0..size {
+			// Generate and regenerate normal vectors.
+			let point = gen_vec2_i64(rng);
+			let mut normal = gen_vec2_i64(rng);
+			while (normal ^ normal) == 0 {
+				normal = gen_vec2_i64(rng);
+			}
+			let v1 = gen_vec2_i64(rng);
+			let v2 = gen_vec2_i64(rng);
+			let aabb = AABB2::new(v1, v2);
+			result.push((point, normal, aabb));
+		}
+		result
+	}
+
+	// LineNaive2 performs the naive comparison with the query bodies
+	// by checking each corner of the AABB.
+	struct LineNaive2<T> {
+		point: Vec2<T>,
+		normal: Vec2<T>,
+	}
+
+	impl<T, U, V> AABBQuery<AABB2<T>> for LineNaive2<T>
+	where
+		T: Ord + Copy + Sub<Output = U> + Default,
+		U: Copy + Mul<T, Output = V>,
+		V: Copy + Add<Output = V> + Ord + Default,
+	{
+		fn check(&self, bound: &AABB2<T>) -> AABBRelation {
+			let zero = V::default();
+			let v = Vec2::<(T, T)>::from(*bound);
+			let vs : [Vec2<T>; 4] = [
+				Vec2::new(v.0 .0, v.1 .0),
+				Vec2::new(v.0 .0, v.1 .1),
+				Vec2::new(v.0 .1, v.1 .0),
+				Vec2::new(v.0 .1, v.1 .1),
+			];
+			let mut less = false;
+			let mut greater = false;
+			for v in vs {
+				let d = (v - self.point) ^ self.normal;
+				match d.cmp(&zero) {
+					Ordering::Less => less = true,
+					Ordering::Equal => {},
+					Ordering::Greater => greater = true,
+				}
+			}
+			match (less, greater) {
+				(true, false) => AABBRelation::Include,
+				(false, true) => AABBRelation::Interleave,
+				_ => AABBRelation::Intersect,
+			}
+		}
+	}
+
+	#[test] fn test_line2_i64_random_query() {
+		const NUM: usize = 1000000;
+		let data = testdata_aabb2_line2_i64(NUM);
+		let mut include = 0usize;
+		let mut intersect = 0usize;
+		let mut interleave = 0usize;
+		for (p, n, aabb) in data {
+			let actual = Line2::new(p, n).check(&aabb);
+			let expected = LineNaive2{ point: p, normal: n }.check(&aabb);
+			assert_eq!(
+				actual, expected,
+				"point = {:?}, normal = {:?}, aabb = {:?}",
+				p, n, aabb,
+			);
+			match actual {
+				AABBRelation::Include => include += 1,
+				AABBRelation::Intersect => intersect += 1,
+				AABBRelation::Interleave => interleave += 1,
+			}
+		}
+		println!(
+			"include = {}, intersect = {}, interleave = {}",
+			include, intersect, interleave,
+		);
+	}
+
+	#[test] fn test_bvh_aabb2_i64_line2_query() {
+		let items: Vec<_> = testdata_aabb2_line2_i64(1000)
+			.into_iter()
+			.enumerate()
+			.map(|(i, (_, _, aabb))| (aabb, i))
+			.collect();
+		let bvh = BVH::build(items.clone());
+		for (p, n, _) in testdata_aabb2_line2_i64(100) {
+			let line = Line2::new(p, n);
+			let mut actual: Vec<usize> =
+				bvh.query(&line).copied().collect();
+			actual.sort();
+			let expected: Vec<usize> = items
+				.iter()
+				.filter(|x| line.check(&x.0) != AABBRelation::Interleave)
+				.map(|x| x.1)
+				.collect();
+			assert_eq!(actual, expected);
+		}
+	}
+}
@@ -0,0 +1,188 @@
+//! naive provides the reference implementations of the query bodies
+//! in the crate, which compare the AABB by enumerating its corners,
+//! for validating the query bodies with random data.
+//!
+//! They are slow but simple enough to be trusted, and the custom
+//! query bodies can be cross-checked against them as well:
+//!
+//! ```
+//! use spatial::naive::PlaneNaive3;
+//! use spatial::{AABBQuery, AABBRelation, Vec3, AABB3};
+//!
+//! // Below is the half space z < 5, which is the custom query body.
+//! struct Below(i64);
+//!
+//! impl AABBQuery<AABB3<i64>> for Below {
+//!     fn check(&self, bound: &AABB3<i64>) -> AABBRelation {
+//!         if bound.max().2 < self.0 {
+//!             AABBRelation::Include
+//!         } else if bound.min().2 > self.0 {
+//!             AABBRelation::Interleave
+//!         } else {
+//!             AABBRelation::Intersect
+//!         }
+//!     }
+//! }
+//!
+//! let query = Below(5);
+//! let naive = PlaneNaive3::new(Vec3::new(0, 0, 5), Vec3::new(0, 0, 1));
+//! for z0 in [0, 3, 5, 7, 9] {
+//!     for z1 in [0, 3, 5, 7, 9] {
+//!         let (p0, p1) = (Vec3::new(0, 0, z0), Vec3::new(1, 1, z1));
+//!         let aabb = AABB3::new(p0, p1);
+//!         assert_eq!(query.check(&aabb), naive.check(&aabb));
+//!     }
+//! }
+//!
+//! // The boxes with a face on the plane intersect with it.
+//! let aabb = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(1, 1, 5));
+//! assert_eq!(naive.check(&aabb), AABBRelation::Intersect);
+//! let aabb = AABB3::new(Vec3::new(0, 0, 5), Vec3::new(1, 1, 9));
+//! assert_eq!(naive.check(&aabb), AABBRelation::Intersect);
+//! ```
+//!
+//! This module is only available with the testutil feature.
+
+use std::cmp::{max, min, Ordering};
+use std::ops::{Add, Mul, Sub};
+
+use crate::{AABBQuery, AABBRelation, Vec3, AABB3};
+
+/// PlaneNaive3 compares the AABB with the plane by the side of each
+/// of its corners, see also Plane3.
+///
+/// The plane is closed like Plane3, that the AABB with any corner
+/// on the plane is Intersect, even if it lies on one side.
+pub struct PlaneNaive3<T> {
+	point: Vec3<T>,
+	normal: Vec3<T>,
+}
+
+impl<T: Copy> PlaneNaive3<T> {
+	pub fn new(point: Vec3<T>, normal: Vec3<T>) -> Self {
+		Self {
+			point: point,
+			normal: normal,
+		}
+	}
+}
+
+impl<T, U, V> AABBQuery<AABB3<T>> for PlaneNaive3<T>
+where
+	T: Ord + Copy + Sub<Output = U> + Default,
+	U: Copy + Mul<T, Output = V>,
+	V: Copy + Add<Output = V> + Ord + Default,
+{
+	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
+		let zero = V::default();
+		let mut less = false;
+		let mut greater = false;
+		for v in bound.corners() {
+			let d = (v - self.point) ^ self.normal;
+			match &d.cmp(&zero) {
+				Ordering::Less => less = true,
+				Ordering::Equal => return AABBRelation::Intersect,
+				Ordering::Greater => greater = true,
+			}
+			if less && greater {
+				return AABBRelation::Intersect;
+			}
+		}
+		if greater {
+			AABBRelation::Interleave
+		} else {
+			AABBRelation::Include
+		}
+	}
+}
+
+/// SphereNaive3 compares the AABB with the sphere by evaluating the
+/// distances to all corners of the AABB, and the distance to the
+/// closest point by clamping the center into the AABB, see also
+/// Sphere3. The distances are evaluated in i128, which is exact.
+pub struct SphereNaive3 {
+	center: Vec3<i64>,
+	radius2: i128,
+}
+
+impl SphereNaive3 {
+	pub fn new(center: Vec3<i64>, radius2: i128) -> Self {
+		Self {
+			center: center,
+			radius2: radius2,
+		}
+	}
+}
+
+impl AABBQuery<AABB3<i64>> for SphereNaive3 {
+	fn check(&self, bound: &AABB3<i64>) -> AABBRelation {
+		let dist2 = |p: Vec3<i64>| -> i128 {
+			let d = p - self.center;
+			let (x, y, z) = (d.0 as i128, d.1 as i128, d.2 as i128);
+			x * x + y * y + z * z
+		};
+		let v = Vec3::<(i64, i64)>::from(*bound);
+		let c = self.center;
+		let closest = Vec3::new(
+			c.0.clamp(v.0 .0, v.0 .1),
+			c.1.clamp(v.1 .0, v.1 .1),
+			c.2.clamp(v.2 .0, v.2 .1),
+		);
+		if dist2(closest) > self.radius2 {
+			return AABBRelation::Interleave;
+		}
+		for v in bound.corners() {
+			if dist2(v) >= self.radius2 {
+				return AABBRelation::Intersect;
+			}
+		}
+		AABBRelation::Include
+	}
+}
+
+/// AABBNaive3 performs the box query by checking whether all corners
+/// of the AABB are strictly inside the query box, and whether the
+/// overlap length on each axis is positive, see also the AABBQuery
+/// of AABB3.
+pub struct AABBNaive3 {
+	lo: Vec3<i64>,
+	hi: Vec3<i64>,
+}
+
+impl AABBNaive3 {
+	pub fn new(query: &AABB3<i64>) -> Self {
+		Self {
+			lo: query.min(),
+			hi: query.max(),
+		}
+	}
+}
+
+impl AABBQuery<AABB3<i64>> for AABBNaive3 {
+	fn check(&self, bound: &AABB3<i64>) -> AABBRelation {
+		let inside = |p: Vec3<i64>| {
+			self.lo.0 < p.0
+				&& p.0 < self.hi.0
+				&& self.lo.1 < p.1
+				&& p.1 < self.hi.1
+				&& self.lo.2 < p.2
+				&& p.2 < self.hi.2
+		};
+		if bound.corners().into_iter().all(inside) {
+			return AABBRelation::Include;
+		}
+		let (lo, hi) = (bound.min(), bound.max());
+		let overlap = |a: i64, b: i64, c: i64, d: i64| {
+			min(b, d) - max(a, c) > 0
+				|| (a == b && c < a && a < d)
+				|| (c == d && a < c && c < b)
+		};
+		if overlap(lo.0, hi.0, self.lo.0, self.hi.0)
+			&& overlap(lo.1, hi.1, self.lo.1, self.hi.1)
+			&& overlap(lo.2, hi.2, self.lo.2, self.hi.2)
+		{
+			return AABBRelation::Intersect;
+		}
+		AABBRelation::Interleave
+	}
+}
@@ -1,15 +1,19 @@
 use std::cmp::Ordering;
-use std::ops::{Add, Mul};
+use std::ops::{Add, Mul, Neg, Sub};
 
-use crate::{cfg_test, AABBQuery, AABBRelation, Vec3, AABB3};
+use crate::{
+	cfg_test, AABBDetailedRelation, AABBQuery, AABBQueryDetailed,
+	AABBQueryExplain, AABBRelation, Coordinate, Sphere3, Vec3, AABB3,
+};
 
 cfg_test! {
 	extern crate test;
 
-	use std::ops::Sub;
-
+	use rand::{Rng, RngCore};
 	use test::Bencher;
-	use crate::{prng, gen_vec3_i64};
+	use crate::{prng, prng_seeded, gen_vec3_i64, testdata_bvh_i64};
+	use crate::{ConvexPolytope3, BVH};
+	use crate::naive::PlaneNaive3;
 }
 
 /// Plane3 is a three dimensional plane denoted by a point in the
@@ -32,7 +36,7 @@ pub struct Plane3<T, U> {
 
 impl<T, U> Plane3<T, U>
 where
-	T: Ord + Copy + Mul<Output = U> + Default,
+	T: Coordinate<Product = U>,
 	U: Add<Output = U>,
 {
 	#[inline(always)]
@@ -43,24 +47,387 @@ where
 			distance: point ^ normal,
 		}
 	}
+
+	/// with_point starts building a plane through the point, with
+	/// the normal defaulted to the +z axis.
+	#[inline(always)]
+	pub fn with_point(point: Vec3<T>) -> Plane3Builder<T>
+	where
+		T: From<u8>,
+	{
+		Plane3Builder::default().with_point(point)
+	}
+
+	/// with_normal starts building a plane with the normal, passing
+	/// through the origin by default.
+	#[inline(always)]
+	pub fn with_normal(normal: Vec3<T>) -> Plane3Builder<T>
+	where
+		T: From<u8>,
+	{
+		Plane3Builder::default().with_normal(normal)
+	}
+}
+
+impl<T, U> Plane3<T, U>
+where
+	T: PartialOrd + Copy + Mul<Output = U> + Default,
+	U: Add<Output = U>,
+{
+	/// new_partial creates a plane for partially ordered coordinates
+	/// like f32 and f64, and None will be returned if the normal is not
+	/// comparable, see also Vec3::to_ordering_partial.
+	#[inline(always)]
+	pub fn new_partial(point: Vec3<T>, normal: Vec3<T>) -> Option<Self> {
+		Some(Self {
+			normal: normal,
+			dir: normal.to_ordering_partial()?,
+			distance: point ^ normal,
+		})
+	}
+}
+
+impl<T: Coordinate<Product = T>> Plane3<T, T> {
+	/// from_points creates the plane passing through the three
+	/// points, or None if the points are collinear.
+	///
+	/// The normal is the cross product (b - a) * (c - a), so the
+	/// points are counterclockwise when viewed from above the plane.
+	/// Please notice the distance takes the cube of the coordinates,
+	/// and T must be wide enough to hold them. The normal is in the
+	/// products of T, so it is only created for T whose Product is T.
+	pub fn from_points(
+		a: Vec3<T>, b: Vec3<T>, c: Vec3<T>,
+	) -> Option<Self> {
+		let normal = (b - a) * (c - a);
+		if normal == Vec3::default() {
+			return None;
+		}
+		Some(Self::new(a, normal))
+	}
+}
+
+impl<T, U> Plane3<T, U>
+where
+	T: Copy + Mul<Output = U>,
+	U: Copy + Add<Output = U>,
+{
+	/// signed_distance_to evaluates (point ^ normal) - distance, which
+	/// is positive above the plane, zero on the plane and negative
+	/// below the plane.
+	///
+	/// The normal is not normalized, so the result is the actual
+	/// signed distance scaled by the length of the normal.
+	#[inline(always)]
+	pub fn signed_distance_to(&self, point: Vec3<T>) -> U
+	where
+		U: Sub<Output = U>,
+	{
+		(point ^ self.normal) - self.distance
+	}
+
+	/// classify tells whether the point is below (Less), on (Equal)
+	/// or above (Greater) the plane, with respect to the normal.
+	///
+	/// The sides are decided by comparing the projection of the
+	/// point with the distance directly, so no subtraction which
+	/// might overflow is involved.
+	#[inline(always)]
+	pub fn classify(&self, point: Vec3<T>) -> Ordering
+	where
+		U: Ord,
+	{
+		(point ^ self.normal).cmp(&self.distance)
+	}
+}
+
+impl<T, U> Plane3<T, U>
+where
+	T: Copy + Neg<Output = T>,
+	U: Copy,
+{
+	/// mirror reflects the plane across the plane through the origin
+	/// perpendicular to the axis, see also AABB3::mirror.
+	///
+	/// The component of the normal on the axis is negated, while the
+	/// distance is kept since the reflection preserves the dot product
+	/// between the mirrored point and normal. The axis must be 0, 1
+	/// or 2, otherwise it panics as indexing Vec3 does.
+	pub fn mirror(&self, axis: usize) -> Self {
+		let mut normal = self.normal;
+		normal[axis] = -normal[axis];
+		let mut dir = self.dir;
+		dir[axis] = dir[axis].reverse();
+		Self {
+			normal: normal,
+			dir: dir,
+			distance: self.distance,
+		}
+	}
+
+	/// flipped returns the plane with the normal negated, so that the
+	/// points above the plane become inside the body of the plane,
+	/// which swaps Include and Interleave on checking the AABB, while
+	/// those intersecting with the plane are still Intersect.
+	pub fn flipped(&self) -> Self
+	where
+		U: Neg<Output = U>,
+	{
+		Self {
+			normal: -self.normal,
+			dir: self.dir | Ordering::reverse,
+			distance: -self.distance,
+		}
+	}
+}
+
+/// The default plane is the z=0 plane with the +z axis as normal.
+impl<T, U> Default for Plane3<T, U>
+where
+	T: Coordinate<Product = U> + From<u8>,
+	U: Add<Output = U>,
+{
+	fn default() -> Self {
+		Plane3Builder::default().build()
+	}
+}
+
+/// Plane3Builder specifies the point and the normal of the plane
+/// fluently, e.g. `Plane3::with_point(p).with_normal(n).build()`.
+///
+/// It starts with the z=0 plane with the +z axis as normal.
+#[derive(Copy, Clone, Debug)]
+pub struct Plane3Builder<T> {
+	point: Vec3<T>,
+	normal: Vec3<T>,
+}
+
+impl<T: Copy + Default + From<u8>> Default for Plane3Builder<T> {
+	fn default() -> Self {
+		let zero = T::default();
+		Self {
+			point: Vec3::new(zero, zero, zero),
+			normal: Vec3::new(zero, zero, T::from(1)),
+		}
+	}
+}
+
+impl<T> Plane3Builder<T> {
+	/// with_point specifies a point in the plane.
+	#[inline(always)]
+	pub fn with_point(mut self, point: Vec3<T>) -> Self {
+		self.point = point;
+		self
+	}
+
+	/// with_normal specifies the normal of the plane.
+	#[inline(always)]
+	pub fn with_normal(mut self, normal: Vec3<T>) -> Self {
+		self.normal = normal;
+		self
+	}
+
+	/// build the plane from the specified point and normal.
+	#[inline(always)]
+	pub fn build<U>(self) -> Plane3<T, U>
+	where
+		T: Coordinate<Product = U>,
+		U: Add<Output = U>,
+	{
+		Plane3::new(self.point, self.normal)
+	}
+}
+
+impl<T> AABB3<T>
+where
+	T: Copy + From<u8> + Neg<Output = T>,
+{
+	/// surface_planes returns the planes of the six faces of the AABB,
+	/// in the order of the -x, -y, -z faces through the min corner and
+	/// the +x, +y, +z faces through the max corner.
+	///
+	/// The normals are facing outward, so that the points inside the
+	/// AABB are below or on all of the planes, and the planes can be
+	/// taken as the inner half spaces of a ConvexPolytope3.
+	///
+	/// The half spaces are closed, so the points on the surface are
+	/// inside, and the boxes merely touching the surface from outside
+	/// are Intersect with the planes, unlike the AABB as a query body
+	/// which takes them as Interleave.
+	///
+	/// The normals of the min faces are negative, so T must be signed.
+	pub fn surface_planes<U>(&self) -> [Plane3<T, U>; 6]
+	where
+		T: Coordinate<Product = U>,
+		U: Add<Output = U>,
+	{
+		let (zero, one) = (T::default(), T::from(1));
+		let neg = -one;
+		let (lo, hi) = (self.min(), self.max());
+		[
+			Plane3::new(lo, Vec3::new(neg, zero, zero)),
+			Plane3::new(lo, Vec3::new(zero, neg, zero)),
+			Plane3::new(lo, Vec3::new(zero, zero, neg)),
+			Plane3::new(hi, Vec3::new(one, zero, zero)),
+			Plane3::new(hi, Vec3::new(zero, one, zero)),
+			Plane3::new(hi, Vec3::new(zero, zero, one)),
+		]
+	}
+}
+
+/// WidePlane3 is the plane of Plane3 whose dot products are evaluated
+/// in the wider type W, into which the coordinates are converted
+/// before multiplying, e.g. querying AABB3<i32> with the distances
+/// in i64.
+///
+/// Please notice W must hold the sum of three products, so i64 is
+/// wide enough for i32 coordinates within [-2^30, 2^30].
+#[derive(Copy, Clone, Debug)]
+pub struct WidePlane3<T, W> {
+	normal: Vec3<T>,
+	dir: Vec3<Ordering>,
+	distance: W,
+}
+
+impl<T, W> WidePlane3<T, W>
+where
+	T: Coordinate,
+	W: Coordinate<Product = W> + From<T>,
+{
+	#[inline(always)]
+	pub fn new(point: Vec3<T>, normal: Vec3<T>) -> Self {
+		Self {
+			normal: normal,
+			dir: normal.to_ordering(),
+			distance: point.dot_wide(normal),
+		}
+	}
+
+	/// classify tells whether the point is below (Less), on (Equal)
+	/// or above (Greater) the plane, see also Plane3::classify.
+	#[inline(always)]
+	pub fn classify(&self, point: Vec3<T>) -> Ordering {
+		point.dot_wide::<W>(self.normal).cmp(&self.distance)
+	}
+}
+
+// relate compares the support points of the AABB projected onto
+// the normal with the distance of the plane, where dp is the lower
+// and dn is the upper one, which are shared by all plane queries.
+#[inline(always)]
+fn relate<U: PartialOrd>(
+	dp: &U, dn: &U, distance: &U,
+) -> AABBDetailedRelation {
+	if dp > distance {
+		AABBDetailedRelation::Interleave
+	} else if dn < distance {
+		AABBDetailedRelation::Include
+	} else if dp == distance || dn == distance {
+		AABBDetailedRelation::Touch
+	} else {
+		AABBDetailedRelation::Intersect
+	}
+}
+
+impl<T, W> AABBQuery<AABB3<T>> for WidePlane3<T, W>
+where
+	T: Coordinate,
+	W: Coordinate<Product = W> + From<T>,
+{
+	#[inline(always)]
+	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
+		let (vp, vn) = bound.support_points(self.dir);
+		let dp: W = vp.dot_wide(self.normal);
+		let dn: W = vn.dot_wide(self.normal);
+		relate(&dp, &dn, &self.distance).coarse()
+	}
 }
 
 impl<T, U> AABBQuery<AABB3<T>> for Plane3<T, U>
 where
-	T: Ord + Copy + Mul<Output = U>,
-	U: Ord + Copy + Add<Output = U>,
+	T: PartialOrd + Copy + Mul<Output = U>,
+	U: PartialOrd + Copy + Add<Output = U>,
 {
 	#[inline(always)]
 	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
-		let (vn, vp) = bound.from_ordering(self.dir).unzip();
+		let (vp, vn) = bound.support_points(self.dir);
+		let dp = vp ^ self.normal;
+		let dn = vn ^ self.normal;
+		relate(&dp, &dn, &self.distance).coarse()
+	}
+}
+
+/// The reason tells which side of the plane the AABB lies on, or
+/// whether it straddles or touches the plane, by the same corners
+/// compared in check.
+impl<T, U> AABBQueryExplain<AABB3<T>> for Plane3<T, U>
+where
+	T: PartialOrd + Copy + Mul<Output = U>,
+	U: PartialOrd + Copy + Add<Output = U>,
+{
+	#[inline(always)]
+	fn explain(&self, bound: &AABB3<T>) -> (AABBRelation, &'static str) {
+		let (vp, vn) = bound.support_points(self.dir);
 		let dp = vp ^ self.normal;
 		let dn = vn ^ self.normal;
-		if dp > self.distance {
-			if dn >= self.distance {
+		match relate(&dp, &dn, &self.distance) {
+			AABBDetailedRelation::Interleave => {
+				(AABBRelation::Interleave, "box entirely above plane")
+			},
+			AABBDetailedRelation::Include => {
+				(AABBRelation::Include, "box entirely below plane")
+			},
+			AABBDetailedRelation::Touch => {
+				(AABBRelation::Intersect, "box touches plane")
+			},
+			AABBDetailedRelation::Intersect => {
+				(AABBRelation::Intersect, "box straddles plane")
+			},
+		}
+	}
+}
+
+/// The AABB is Touch if it lies on one side of the plane with its
+/// surface on the plane, which is the "box touches plane" case of
+/// explain and reported as Intersect by check.
+impl<T, U> AABBQueryDetailed<AABB3<T>> for Plane3<T, U>
+where
+	T: PartialOrd + Copy + Mul<Output = U>,
+	U: PartialOrd + Copy + Add<Output = U>,
+{
+	#[inline(always)]
+	fn check_detailed(&self, bound: &AABB3<T>) -> AABBDetailedRelation {
+		let (vp, vn) = bound.support_points(self.dir);
+		let dp = vp ^ self.normal;
+		let dn = vn ^ self.normal;
+		relate(&dp, &dn, &self.distance)
+	}
+}
+
+/// The sphere is compared with the plane by the distance from its
+/// center to the plane. Since the normal is not normalized, both
+/// sides are scaled by the length of normal vector, and compared
+/// in squared form, that is, d^2 against radius^2 * |normal|^2.
+///
+/// Please notice the squared comparison takes the fourth power of
+/// the coordinates, U must be wide enough to hold them.
+impl<T, U> AABBQuery<Sphere3<T, U>> for Plane3<T, U>
+where
+	T: Coordinate<Product = U>,
+	U: Coordinate<Product = U>,
+{
+	#[inline(always)]
+	fn check(&self, bound: &Sphere3<T, U>) -> AABBRelation {
+		let dc = bound.center() ^ self.normal;
+		let rn = bound.radius2() * (self.normal ^ self.normal);
+		if dc > self.distance {
+			let d = dc - self.distance;
+			if d * d > rn {
 				return AABBRelation::Interleave;
 			}
-		} else {
-			if dn < self.distance {
+		} else if dc < self.distance {
+			let d = self.distance - dc;
+			if d * d > rn {
 				return AABBRelation::Include;
 			}
 		}
@@ -72,7 +439,15 @@ cfg_test! {
 	fn testdata_aabb3_plane3_i64(
 		size: usize,
 	) -> Vec<(Vec3<i64>, Vec3<i64>, AABB3<i64>)> {
-		let rng = &mut prng();
+		gen_aabb3_plane3_i64(&mut prng_seeded().0, size)
+	}
+
+	// gen_aabb3_plane3_i64 generates the data of
+	// testdata_aabb3_plane3_i64 from the specified generator, which is
+	// also used by the benchmarks.
+	fn gen_aabb3_plane3_i64(
+		rng: &mut impl Rng, size: usize,
+	) -> Vec<(Vec3<i64>, Vec3<i64>, AABB3<i64>)> {
 		let mut result = Vec::new();
 		for _ in 0..size {
 			// Generate and regenerate normal vectors.
@@ -89,75 +464,76 @@ cfg_test! {
 		result
 	}
 
-	// PlaneNaive3 performs the naive comparison with the
-	// query bodies. We will also generate random data for
-	// comparing the queries results.
-	struct PlaneNaive3<T> {
-		point: Vec3<T>,
-		normal: Vec3<T>,
+	#[test] fn test_plane3_i64_explain() {
+		let plane = Plane3::new(Vec3::new(0, 0, 5), Vec3::new(0, 0, 1));
+		let explain = |p0: (i64, i64, i64), p1: (i64, i64, i64)| {
+			let aabb = AABB3::new(p0.into(), p1.into());
+			let result = plane.explain(&aabb);
+			assert_eq!(result.0, plane.check(&aabb));
+			result
+		};
+		assert_eq!(
+			explain((0, 0, 6), (1, 1, 9)),
+			(AABBRelation::Interleave, "box entirely above plane"),
+		);
+		assert_eq!(
+			explain((0, 0, 0), (1, 1, 4)),
+			(AABBRelation::Include, "box entirely below plane"),
+		);
+		assert_eq!(
+			explain((0, 0, 0), (1, 1, 9)),
+			(AABBRelation::Intersect, "box straddles plane"),
+		);
+		assert_eq!(
+			explain((0, 0, 0), (1, 1, 5)),
+			(AABBRelation::Intersect, "box touches plane"),
+		);
+		assert_eq!(
+			explain((0, 0, 5), (1, 1, 5)),
+			(AABBRelation::Intersect, "box touches plane"),
+		);
 	}
 
-	impl<T: Copy> PlaneNaive3<T> {
-		fn new(point: Vec3<T>, normal: Vec3<T>) -> Self {
-			Self{
-				point: point,
-				normal: normal,
-			}
-		}
-	}
+	#[test] fn test_plane3_i64_check_detailed() {
+		let plane = Plane3::new(Vec3::new(0, 0, 5), Vec3::new(0, 0, 1));
+		let check = |p0: (i64, i64, i64), p1: (i64, i64, i64)| {
+			let aabb = AABB3::new(p0.into(), p1.into());
+			let detailed = plane.check_detailed(&aabb);
+			assert_eq!(detailed.coarse(), plane.check(&aabb));
+			detailed
+		};
+		assert_eq!(
+			check((0, 0, 6), (1, 1, 9)),
+			AABBDetailedRelation::Interleave,
+		);
+		assert_eq!(
+			check((0, 0, 0), (1, 1, 4)),
+			AABBDetailedRelation::Include,
+		);
+		assert_eq!(
+			check((0, 0, 0), (1, 1, 9)),
+			AABBDetailedRelation::Intersect,
+		);
+		assert_eq!(
+			check((0, 0, 0), (1, 1, 5)),
+			AABBDetailedRelation::Touch,
+		);
+		assert_eq!(
+			check((0, 0, 5), (1, 1, 9)),
+			AABBDetailedRelation::Touch,
+		);
+		assert_eq!(
+			check((0, 0, 5), (1, 1, 5)),
+			AABBDetailedRelation::Touch,
+		);
 
-	impl<T, U, V> AABBQuery<AABB3<T>> for PlaneNaive3<T>
-	where
-		T: Ord + Copy + Sub<Output = U> + Default,
-		U: Copy + Mul<T, Output = V>,
-		V: Copy + Add<Output = V> + Ord + Default,
-	{
-		#[inline(always)]
-		fn check(&self, bound: &AABB3<T>) -> AABBRelation {
-			let zero = V::default();
-			let v = Vec3::<(T, T)>::from(*bound);
-			let vs : [Vec3<T>; 8] = [
-				Vec3::new(v.0 .0, v.1 .0, v.2 .0),
-				Vec3::new(v.0 .0, v.1 .0, v.2 .1),
-				Vec3::new(v.0 .0, v.1 .1, v.2 .0),
-				Vec3::new(v.0 .0, v.1 .1, v.2 .1),
-				Vec3::new(v.0 .1, v.1 .0, v.2 .0),
-				Vec3::new(v.0 .1, v.1 .0, v.2 .1),
-				Vec3::new(v.0 .1, v.1 .1, v.2 .0),
-				Vec3::new(v.0 .1, v.1 .1, v.2 .1),
-			];
-
-			let mut less = false;
-			let mut equal = false;
-			let mut greater = false;
-			for v in vs {
-				let d = (v - self.point) ^ self.normal;
-				match &d.cmp(&zero) {
-					Ordering::Less => {
-						if greater {
-							return AABBRelation::Intersect;
-						}
-						less = true;
-					},
-					Ordering::Equal => equal = true,
-					Ordering::Greater => {
-						if less {
-							return AABBRelation::Intersect;
-						}
-						greater = true;
-					},
-				}
-			}
-			if greater {
-				AABBRelation::Interleave
-			} else if less {
-				AABBRelation::Include
-			} else {
-				if !equal {
-					unreachable!();
-				}
-				AABBRelation::Intersect
-			}
+		// Touching agrees with the explained reasons.
+		for (p, n, aabb) in testdata_aabb3_plane3_i64(1000) {
+			let plane = Plane3::new(p, n);
+			let touch = plane.explain(&aabb).1 == "box touches plane";
+			let detailed = plane.check_detailed(&aabb);
+			assert_eq!(detailed == AABBDetailedRelation::Touch, touch);
+			assert_eq!(detailed.coarse(), plane.check(&aabb));
 		}
 	}
 
@@ -187,6 +563,382 @@ cfg_test! {
 		);
 	}
 
+	#[test] fn test_plane3_i64_naive_boundary() {
+		let (p, n) = (Vec3::new(0, 0, 5), Vec3::new(0, 0, 1));
+		let (plane, naive) = (Plane3::new(p, n), PlaneNaive3::new(p, n));
+		for (z0, z1) in [(0, 5), (5, 9), (5, 5)] {
+			let (p0, p1) = (Vec3::new(0, 0, z0), Vec3::new(1, 1, z1));
+			let aabb = AABB3::new(p0, p1);
+			assert_eq!(plane.check(&aabb), AABBRelation::Intersect);
+			assert_eq!(naive.check(&aabb), AABBRelation::Intersect);
+		}
+
+		// The planes passing through a corner of the boxes.
+		let rng = &mut prng_seeded().0;
+		for _ in 0..10000 {
+			let v1 = gen_vec3_i64_small(rng);
+			let v2 = gen_vec3_i64_small(rng);
+			let aabb = AABB3::new(v1, v2);
+			let corners = aabb.corners();
+			let p = corners[rng.gen_range(0, corners.len())];
+			let mut n = gen_vec3_i64_small(rng);
+			while (n ^ n) == 0 {
+				n = gen_vec3_i64_small(rng);
+			}
+			assert_eq!(
+				Plane3::new(p, n).check(&aabb),
+				PlaneNaive3::new(p, n).check(&aabb),
+				"point = {:?}, normal = {:?}, aabb = {:?}",
+				p, n, aabb,
+			);
+		}
+	}
+
+	fn gen_vec3_i64_small(rng: &mut impl Rng) -> Vec3<i64> {
+		Vec3::new(
+			(rng.gen::<i16>() / 32) as i64,
+			(rng.gen::<i16>() / 32) as i64,
+			(rng.gen::<i16>() / 32) as i64,
+		)
+	}
+
+	// naive_sphere_plane3_i64 classifies the sphere by computing
+	// the distance from the point on plane to the sphere center in
+	// wider integers, which will never overflow.
+	fn naive_sphere_plane3_i64(
+		point: Vec3<i64>, normal: Vec3<i64>, sphere: &Sphere3<i64, i64>,
+	) -> AABBRelation {
+		let w = |v: Vec3<i64>| Vec3::new(
+			v.0 as i128, v.1 as i128, v.2 as i128,
+		);
+		let d = w(sphere.center() - point) ^ w(normal);
+		let rn = (sphere.radius2() as i128) * (w(normal) ^ w(normal));
+		if d * d <= rn {
+			AABBRelation::Intersect
+		} else if d > 0 {
+			AABBRelation::Interleave
+		} else {
+			AABBRelation::Include
+		}
+	}
+
+	#[test] fn test_plane3_sphere3_i64_random_query() {
+		const NUM: usize = 1000000;
+		let rng = &mut prng_seeded().0;
+		let mut include = 0usize;
+		let mut intersect = 0usize;
+		let mut interleave = 0usize;
+		for _ in 0..NUM {
+			let p = gen_vec3_i64_small(rng);
+			let mut n = gen_vec3_i64_small(rng);
+			while (n ^ n) == 0 {
+				n = gen_vec3_i64_small(rng);
+			}
+			let c = gen_vec3_i64_small(rng);
+			let r = (rng.gen::<u16>() >> 5) as i64;
+			let sphere = Sphere3::new(c, r * r);
+			let actual = Plane3::new(p, n).check(&sphere);
+			let expected = naive_sphere_plane3_i64(p, n, &sphere);
+			assert_eq!(
+				actual, expected,
+				"point = {:?}, normal = {:?}, sphere = {:?}",
+				p, n, sphere,
+			);
+			match actual {
+				AABBRelation::Include => include += 1,
+				AABBRelation::Intersect => intersect += 1,
+				AABBRelation::Interleave => interleave += 1,
+			}
+		}
+		println!(
+			"include = {}, intersect = {}, interleave = {}",
+			include, intersect, interleave,
+		);
+	}
+
+	#[test] fn test_plane3_i64_default() {
+		let plane = Plane3::<i64, i64>::default();
+		let origin = Vec3::new(0, 0, 0);
+		assert_eq!(
+			plane.check(&AABB3::new(origin, origin)),
+			AABBRelation::Intersect,
+		);
+		assert_eq!(
+			plane.check(&AABB3::new(
+				Vec3::new(-1, -1, 1),
+				Vec3::new(1, 1, 2),
+			)),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(
+			plane.check(&AABB3::new(
+				Vec3::new(-1, -1, -2),
+				Vec3::new(1, 1, -1),
+			)),
+			AABBRelation::Include,
+		);
+	}
+
+	#[test] fn test_plane3_i64_classify() {
+		let plane = Plane3::new(Vec3::new(1, 2, 3), Vec3::new(0, 0, 2));
+		let above = Vec3::new(0, 0, 5);
+		let on = Vec3::new(7, -3, 3);
+		let below = Vec3::new(1, 1, 1);
+		assert_eq!(plane.signed_distance_to(above), 4);
+		assert_eq!(plane.signed_distance_to(on), 0);
+		assert_eq!(plane.signed_distance_to(below), -4);
+		assert_eq!(plane.classify(above), Ordering::Greater);
+		assert_eq!(plane.classify(on), Ordering::Equal);
+		assert_eq!(plane.classify(below), Ordering::Less);
+
+		let plane = Plane3::new(Vec3::new(0, 0, 0), Vec3::new(1, -1, 1));
+		assert_eq!(plane.classify(Vec3::new(1, 1, 0)), Ordering::Equal);
+		assert_eq!(plane.classify(Vec3::new(0, -1, 0)), Ordering::Greater);
+		assert_eq!(plane.signed_distance_to(Vec3::new(-2, 1, 0)), -3);
+	}
+
+	#[test] fn test_plane3_i64_mirror() {
+		let plane = Plane3::new(Vec3::new(1, 2, 3), Vec3::new(1, -1, 2));
+		let mirrored = plane.mirror(0);
+		let expected =
+			Plane3::new(Vec3::new(-1, 2, 3), Vec3::new(-1, -1, 2));
+		assert_eq!(mirrored.normal, expected.normal);
+		assert_eq!(mirrored.dir, expected.dir);
+		assert_eq!(mirrored.distance, expected.distance);
+
+		let rng = &mut prng_seeded().0;
+		for _ in 0..10000 {
+			let mut normal = gen_vec3_i64(rng);
+			while (normal ^ normal) == 0 {
+				normal = gen_vec3_i64(rng);
+			}
+			let plane = Plane3::new(gen_vec3_i64(rng), normal);
+			let axis = rng.gen_range(0, 3);
+			let mirrored = plane.mirror(axis);
+			let p = gen_vec3_i64(rng);
+			let mut q = p;
+			q[axis] = -q[axis];
+			assert_eq!(mirrored.classify(q), plane.classify(p));
+			let aabb = AABB3::new(p, gen_vec3_i64(rng));
+			assert_eq!(
+				mirrored.check(&aabb.mirror(axis)),
+				plane.check(&aabb),
+			);
+			let mirrored = mirrored.mirror(axis);
+			assert_eq!(mirrored.normal, plane.normal);
+			assert_eq!(mirrored.dir, plane.dir);
+		}
+	}
+
+	#[test] fn test_plane3_i64_flipped() {
+		let plane = Plane3::new(Vec3::new(0, 0, 5), Vec3::new(0, 0, 1));
+		let flipped = plane.flipped();
+		let check = |z0: i64, z1: i64| {
+			let (p0, p1) = (Vec3::new(0, 0, z0), Vec3::new(1, 1, z1));
+			let aabb = AABB3::new(p0, p1);
+			(plane.check(&aabb), flipped.check(&aabb))
+		};
+		assert_eq!(
+			check(0, 3),
+			(AABBRelation::Include, AABBRelation::Interleave),
+		);
+		assert_eq!(
+			check(7, 9),
+			(AABBRelation::Interleave, AABBRelation::Include),
+		);
+		assert_eq!(
+			check(3, 7),
+			(AABBRelation::Intersect, AABBRelation::Intersect),
+		);
+		assert_eq!(
+			check(0, 5),
+			(AABBRelation::Intersect, AABBRelation::Intersect),
+		);
+		assert_eq!(flipped.classify(Vec3::new(0, 0, 9)), Ordering::Less);
+
+		let rng = &mut prng_seeded().0;
+		for _ in 0..10000 {
+			let mut normal = gen_vec3_i64(rng);
+			while (normal ^ normal) == 0 {
+				normal = gen_vec3_i64(rng);
+			}
+			let plane = Plane3::new(gen_vec3_i64(rng), normal);
+			let flipped = plane.flipped();
+			let aabb = AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng));
+			let expected = match plane.check(&aabb) {
+				AABBRelation::Include => AABBRelation::Interleave,
+				AABBRelation::Interleave => AABBRelation::Include,
+				AABBRelation::Intersect => AABBRelation::Intersect,
+			};
+			assert_eq!(flipped.check(&aabb), expected);
+			let p = gen_vec3_i64(rng);
+			assert_eq!(flipped.classify(p), plane.classify(p).reverse());
+			let flipped = flipped.flipped();
+			assert_eq!(flipped.normal, plane.normal);
+			assert_eq!(flipped.dir, plane.dir);
+			assert_eq!(flipped.distance, plane.distance);
+		}
+	}
+
+	#[test] fn test_plane3_i64_from_points() {
+		let plane = Plane3::from_points(
+			Vec3::new(1, 0, 0),
+			Vec3::new(0, 1, 0),
+			Vec3::new(0, 0, 1),
+		)
+		.unwrap();
+		assert_eq!(plane.classify(Vec3::new(1, 1, 1)), Ordering::Greater);
+		assert_eq!(plane.classify(Vec3::new(0, 0, 0)), Ordering::Less);
+		assert_eq!(plane.classify(Vec3::new(2, -2, 1)), Ordering::Equal);
+		assert_eq!(
+			plane.check(&AABB3::new(
+				Vec3::new(-1, -1, -1),
+				Vec3::new(0, 0, 0),
+			)),
+			AABBRelation::Include,
+		);
+
+		// Reversing the winding flips the sides.
+		let plane = Plane3::from_points(
+			Vec3::new(0, 0, 5),
+			Vec3::new(0, 3, 5),
+			Vec3::new(2, 0, 5),
+		)
+		.unwrap();
+		assert_eq!(plane.classify(Vec3::new(9, 9, 6)), Ordering::Less);
+		assert_eq!(plane.classify(Vec3::new(9, 9, 4)), Ordering::Greater);
+
+		let collinear = Plane3::from_points(
+			Vec3::new(0, 0, 0),
+			Vec3::new(1, 2, 3),
+			Vec3::new(-2, -4, -6),
+		);
+		assert!(collinear.is_none());
+		let p = Vec3::new(1, 1, 1);
+		assert!(Plane3::from_points(p, p, Vec3::new(0, 1, 2)).is_none());
+	}
+
+	#[test] fn test_plane3_i64_builder() {
+		let data = testdata_aabb3_plane3_i64(1000);
+		for (p, n, aabb) in data {
+			let expected = Plane3::new(p, n).check(&aabb);
+			let plane = Plane3::with_point(p).with_normal(n).build();
+			assert_eq!(plane.check(&aabb), expected);
+			let plane = Plane3::with_normal(n).with_point(p).build();
+			assert_eq!(plane.check(&aabb), expected);
+		}
+	}
+
+	#[test] fn test_plane3_f64_new_partial() {
+		let nan = Vec3::new(f64::NAN, 0.0, 1.0);
+		assert!(Plane3::new_partial(Vec3::default(), nan).is_none());
+
+		let plane = Plane3::new_partial(
+			Vec3::new(0.5, 0.5, 0.5),
+			Vec3::new(-0.0, 1.0, -1.0),
+		).unwrap();
+		let aabb = |p0: (f64, f64, f64), p1: (f64, f64, f64)| {
+			AABB3::new(p0.into(), p1.into())
+		};
+		assert_eq!(
+			plane.check(&aabb((0.0, 0.0, 1.0), (1.0, 0.25, 2.0))),
+			AABBRelation::Include,
+		);
+		assert_eq!(
+			plane.check(&aabb((0.0, 1.0, -1.0), (1.0, 2.0, 0.0))),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(
+			plane.check(&aabb((-9.0, 0.0, 0.0), (9.0, 1.0, 1.0))),
+			AABBRelation::Intersect,
+		);
+	}
+
+	#[test] fn test_aabb3_i64_surface_planes() {
+		let aabb = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(4, 6, 8));
+		let planes: [Plane3<i64, i64>; 6] = aabb.surface_planes();
+		let classify = |p: (i64, i64, i64)| {
+			planes.map(|plane| plane.classify(p.into()))
+		};
+		assert_eq!(classify((1, 2, 3)), [Ordering::Less; 6]);
+		let mut on_face = [Ordering::Less; 6];
+		on_face[3] = Ordering::Equal;
+		assert_eq!(classify((4, 2, 3)), on_face);
+		on_face[3] = Ordering::Greater;
+		assert_eq!(classify((5, 2, 3)), on_face);
+
+		// The box touching the +x face from outside is on the +x
+		// plane, which is Intersect while the AABB query is Interleave.
+		let polytope = ConvexPolytope3::new(planes.to_vec());
+		let touch = AABB3::new(Vec3::new(4, 0, 0), Vec3::new(5, 1, 1));
+		assert_eq!(planes[3].check(&touch), AABBRelation::Intersect);
+		assert_eq!(polytope.check(&touch), AABBRelation::Intersect);
+		assert_eq!(aabb.check(&touch), AABBRelation::Interleave);
+		let corner = AABB3::new(Vec3::new(-1, -1, -1), Vec3::new(0, 0, 0));
+		assert_eq!(polytope.check(&corner), AABBRelation::Intersect);
+		assert_eq!(aabb.check(&corner), AABBRelation::Interleave);
+		let inner = AABB3::new(Vec3::new(1, 1, 1), Vec3::new(4, 6, 8));
+		assert_eq!(polytope.check(&inner), AABBRelation::Intersect);
+		assert_eq!(aabb.check(&inner), AABBRelation::Intersect);
+
+		// Querying the BVH with the planes matches the box itself, as
+		// long as no leaf touches the surface of the box from outside,
+		// which is almost impossible for the random boxes.
+		let bvh = BVH::build(testdata_bvh_i64(1000));
+		let rng = &mut prng_seeded().0;
+		for _ in 0..100 {
+			let query = AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng));
+			let planes = query.surface_planes::<i64>();
+			let polytope = ConvexPolytope3::new(planes.to_vec());
+			let actual: Vec<_> = bvh.query(&polytope).collect();
+			let expected: Vec<_> = bvh.query(&query).collect();
+			assert_eq!(actual, expected, "query = {:?}", query);
+		}
+	}
+
+	#[test] fn test_wide_plane3_i32_query() {
+		// The i32 dot products overflow, e.g. 2 * i32::MAX.
+		let point = Vec3::new(2, 0, 0);
+		let normal = Vec3::new(i32::MAX, 1, 0);
+		let plane = WidePlane3::<i32, i64>::new(point, normal);
+		assert_eq!(plane.classify(point), Ordering::Equal);
+		assert_eq!(plane.classify(Vec3::new(2, 1, 0)), Ordering::Greater);
+		assert_eq!(plane.classify(Vec3::new(1, 9, 0)), Ordering::Less);
+		let aabb = |p0: (i32, i32, i32), p1: (i32, i32, i32)| {
+			AABB3::new(p0.into(), p1.into())
+		};
+		assert_eq!(
+			plane.check(&aabb((3, 0, 0), (4, 1, 1))),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(
+			plane.check(&aabb((-4, 0, 0), (1, 1, 1))),
+			AABBRelation::Include,
+		);
+		assert_eq!(
+			plane.check(&aabb((1, 0, 0), (3, 1, 1))),
+			AABBRelation::Intersect,
+		);
+
+		// The results agree with the planes of the cast coordinates.
+		let rng = &mut prng_seeded().0;
+		let gen = |rng: &mut dyn RngCore| -> Vec3<i32> {
+			Vec3::new(rng.gen(), rng.gen(), rng.gen()) | (|x: i32| x / 2)
+		};
+		for _ in 0..100000 {
+			let (p, n) = (gen(rng), gen(rng));
+			let aabb = AABB3::new(gen(rng), gen(rng));
+			let cast = AABB3::new(aabb.min().cast(), aabb.max().cast());
+			let expected = Plane3::<i64, i64>::new(p.cast(), n.cast());
+			assert_eq!(
+				WidePlane3::<i32, i64>::new(p, n).check(&aabb),
+				expected.check(&cast),
+				"point = {:?}, normal = {:?}, aabb = {:?}",
+				p, n, aabb,
+			);
+		}
+	}
+
 	fn fixture_bench_plane3_i64<Q, F>(
 		b: &mut Bencher, f: F,
 	)
@@ -195,7 +947,7 @@ cfg_test! {
 		F: Fn(Vec3<i64>, Vec3<i64>) -> Q,
 	{
 		const POW2: usize = 1 << 16;
-		let vs = testdata_aabb3_plane3_i64(POW2);
+		let vs = gen_aabb3_plane3_i64(&mut prng(), POW2);
 		let mut i = 0;
 		b.iter(|| {
 			let j = i;
@@ -221,7 +973,7 @@ cfg_test! {
 		F: Fn(Vec3<i64>, Vec3<i64>) -> Q,
 	{
 		const POW2: usize = 1 << 16;
-		let vs = testdata_aabb3_plane3_i64(POW2);
+		let vs = gen_aabb3_plane3_i64(&mut prng(), POW2);
 		let mut i = 0;
 		let query = f(vs[0].0, vs[0].1);
 		b.iter(|| {
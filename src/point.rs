@@ -0,0 +1,68 @@
+use crate::{cfg_test, AABBQuery, AABBRelation, Vec3, AABB3};
+
+cfg_test! {
+	use crate::BVH;
+}
+
+/// Point3 is a query body of a single point, for picking up the
+/// objects whose bounds contain the point, including those having
+/// the point on their surfaces.
+#[derive(Copy, Clone, Debug)]
+pub struct Point3<T>(pub Vec3<T>);
+
+/// The point is inside the AABB if it is inside the interval on
+/// every axis, and since a point could never contain a bound, any
+/// hit is reported as Intersect.
+impl<T: PartialOrd + Copy> AABBQuery<AABB3<T>> for Point3<T> {
+	#[inline(always)]
+	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
+		match bound.contains_point(self.0) {
+			true => AABBRelation::Intersect,
+			false => AABBRelation::Interleave,
+		}
+	}
+}
+
+cfg_test! {
+	#[test] fn test_point3_i64_query() {
+		let aabb = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(4, 4, 4));
+		let check = |p: (i64, i64, i64)| Point3(p.into()).check(&aabb);
+		assert_eq!(check((1, 2, 3)), AABBRelation::Intersect);
+		assert_eq!(check((0, 2, 3)), AABBRelation::Intersect);
+		assert_eq!(check((2, 4, 1)), AABBRelation::Intersect);
+		assert_eq!(check((0, 4, 2)), AABBRelation::Intersect);
+		assert_eq!(check((4, 0, 4)), AABBRelation::Intersect);
+		assert_eq!(check((0, 0, 0)), AABBRelation::Intersect);
+		assert_eq!(check((-1, 2, 2)), AABBRelation::Interleave);
+		assert_eq!(check((2, 5, 2)), AABBRelation::Interleave);
+		assert_eq!(check((2, 2, 5)), AABBRelation::Interleave);
+		assert_eq!(check((5, 5, 5)), AABBRelation::Interleave);
+		assert_eq!(check((4, 4, 5)), AABBRelation::Interleave);
+
+		let flat = AABB3::new(Vec3::new(1, 1, 1), Vec3::new(1, 3, 3));
+		assert_eq!(
+			Point3(Vec3::new(1, 2, 2)).check(&flat),
+			AABBRelation::Intersect,
+		);
+	}
+
+	#[test] fn test_bvh_i64_point3_query() {
+		let mut items = Vec::new();
+		for i in 0..4 {
+			let p = Vec3::new(i, i, i);
+			items.push((AABB3::new(p, p + Vec3::new(2, 2, 2)), i));
+		}
+		let bvh = BVH::build(items);
+		let query = |p: (i64, i64, i64)| {
+			let mut result: Vec<_> =
+				bvh.query(&Point3(p.into())).copied().collect();
+			result.sort();
+			result
+		};
+		assert_eq!(query((0, 0, 0)), vec![0]);
+		assert_eq!(query((2, 2, 2)), vec![0, 1, 2]);
+		assert_eq!(query((3, 3, 2)), vec![1, 2]);
+		assert_eq!(query((5, 5, 5)), vec![3]);
+		assert_eq!(query((6, 5, 5)), Vec::<i64>::new());
+	}
+}
@@ -0,0 +1,430 @@
+use std::ops::Sub;
+
+use crate::{
+	cfg_test, AABBQuery, AABBRelation, Coordinate, Vec3, AABB3, BVH,
+};
+
+cfg_test! {
+	use rand::Rng;
+	use crate::{prng_seeded, gen_vec3_i64};
+}
+
+/// Ray3 is a three dimensional ray denoted by its origin and its
+/// direction, which consists of the points origin + t * dir for
+/// every t >= 0.
+///
+/// The direction is not required to be normalized, and a zero
+/// direction degrades the ray into its origin point.
+#[derive(Copy, Clone, Debug)]
+pub struct Ray3<T> {
+	origin: Vec3<T>,
+	dir: Vec3<T>,
+}
+
+impl<T: Copy> Ray3<T> {
+	#[inline(always)]
+	pub fn new(origin: Vec3<T>, dir: Vec3<T>) -> Self {
+		Self {
+			origin: origin,
+			dir: dir,
+		}
+	}
+
+	/// origin of the ray.
+	#[inline(always)]
+	pub fn origin(&self) -> Vec3<T> {
+		self.origin
+	}
+
+	/// dir is the direction of the ray.
+	#[inline(always)]
+	pub fn dir(&self) -> Vec3<T> {
+		self.dir
+	}
+}
+
+// slab evaluates the parameters the ray enters and exits the slab
+// between lo and hi, as fractions (near / denom, far / denom) with
+// positive denom. None is returned if the ray is parallel to and
+// outside the slab, and Some(None) if parallel and inside it.
+fn slab<T>(
+	((lo, hi), (o, d)): ((T, T), (T, T)),
+) -> Option<Option<(T, T, T)>>
+where
+	T: Ord + Copy + Default + Sub<Output = T>,
+{
+	let zero = T::default();
+	if d > zero {
+		Some(Some((lo - o, hi - o, d)))
+	} else if d < zero {
+		Some(Some((o - hi, o - lo, zero - d)))
+	} else if lo <= o && o <= hi {
+		Some(None)
+	} else {
+		None
+	}
+}
+
+/// Frac is a fraction num / den, which keeps the parameters along
+/// the rays exact for integer coordinates. The denominator of the
+/// fractions evaluated here is always positive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Frac<T> {
+	pub num: T,
+	pub den: T,
+}
+
+// Clip is the pair of parameters the ray enters and exits the AABB,
+// each of which is the fraction (num, den) with positive den, and
+// the exit is None when the ray never exits.
+type Clip<T> = ((T, T), Option<(T, T)>);
+
+// clip evaluates the parameters the ray enters and exits the AABB by
+// the slab method, that the ray hits the AABB if and only if the
+// latest parameter entering each slab is no greater than the
+// earliest one exiting them. The parameters are fractions, and the
+// exit is None when the ray is parallel to all slabs.
+//
+// The parameters are compared by cross multiplication, so no
+// division is involved and it is exact for integer coordinates.
+fn clip<T, U>(ray: &Ray3<T>, bound: &AABB3<T>) -> Option<Clip<T>>
+where
+	T: Coordinate<Product = U> + From<u8>,
+	U: Ord,
+{
+	let slabs = bound.min() / bound.max() / (ray.origin / ray.dir);
+	let slabs = (slabs & slab)?;
+	let mut entry = (T::default(), T::from(1));
+	let mut exit: Option<(T, T)> = None;
+	for (near, far, q) in [slabs.0, slabs.1, slabs.2].into_iter().flatten()
+	{
+		if near * entry.1 > entry.0 * q {
+			entry = (near, q);
+		}
+		match exit {
+			Some((p, r)) if far * r >= p * q => {},
+			_ => exit = Some((far, q)),
+		}
+	}
+	match exit {
+		Some((p, r)) if entry.0 * r > p * entry.1 => None,
+		_ => Some((entry, exit)),
+	}
+}
+
+/// The ray is compared with the AABB by the slab method, see also
+/// AABB3::ray_intersection. U must be wide enough to hold the product
+/// of a coordinate difference and a direction component.
+///
+/// Since a ray could never be contained by a bounding box, any hit
+/// including touching the surface is reported as Intersect.
+impl<T, U> AABBQuery<AABB3<T>> for Ray3<T>
+where
+	T: Coordinate<Product = U> + From<u8>,
+	U: Ord,
+{
+	#[inline(always)]
+	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
+		match clip(self, bound) {
+			Some(_) => AABBRelation::Intersect,
+			None => AABBRelation::Interleave,
+		}
+	}
+}
+
+impl<T: Ord + Copy> AABB3<T> {
+	/// ray_intersection evaluates the parameters of the ray entering
+	/// and exiting the AABB, or None if the ray misses it.
+	///
+	/// The entry is zero when the origin is inside the AABB. For the
+	/// axes that the direction component is zero, the ray is always
+	/// inside the slab if the origin is within the interval, and
+	/// never otherwise. A ray with zero direction is its origin, so
+	/// both parameters are zero when it is inside.
+	pub fn ray_intersection<U>(
+		&self, ray: &Ray3<T>,
+	) -> Option<(Frac<T>, Frac<T>)>
+	where
+		T: Coordinate<Product = U> + From<u8>,
+		U: Ord,
+	{
+		let (entry, exit) = clip(ray, self)?;
+		let exit = exit.unwrap_or(entry);
+		Some((
+			Frac {
+				num: entry.0,
+				den: entry.1,
+			},
+			Frac {
+				num: exit.0,
+				den: exit.1,
+			},
+		))
+	}
+}
+
+impl<T: Ord + Copy, V> BVH<AABB3<T>, V> {
+	/// query_ray_sorted collects the items hit by the ray, sorted by
+	/// the parameters the ray enters their bounds, so the items are
+	/// front-to-back along the ray, and the first one is the nearest
+	/// hit, see also AABB3::ray_intersection.
+	///
+	/// The parameters are compared by cross multiplication, and the
+	/// items entered at the same parameter are in the order of query.
+	pub fn query_ray_sorted<U>(&self, ray: &Ray3<T>) -> Vec<&V>
+	where
+		T: Coordinate<Product = U> + From<u8>,
+		U: Ord,
+	{
+		let mut hits: Vec<((T, T), &V)> = self
+			.query_bounds(ray)
+			.filter_map(|(bound, value)| {
+				Some((clip(ray, bound)?.0, value))
+			})
+			.collect();
+		hits.sort_by(|((p, q), _), ((r, s), _)| (*p * *s).cmp(&(*r * *q)));
+		hits.into_iter().map(|x| x.1).collect()
+	}
+}
+
+/// Segment3 is a three dimensional line segment between the start
+/// and the end, which is the ray from the start towards the end
+/// clipped to the parameters in [0, 1].
+#[derive(Copy, Clone, Debug)]
+pub struct Segment3<T> {
+	ray: Ray3<T>,
+}
+
+impl<T: Copy + Sub<Output = T>> Segment3<T> {
+	#[inline(always)]
+	pub fn new(start: Vec3<T>, end: Vec3<T>) -> Self {
+		Self {
+			ray: Ray3::new(start, end - start),
+		}
+	}
+}
+
+/// The segment hits the AABB if the ray along it enters the AABB no
+/// later than the end of the segment, see also Ray3.
+impl<T, U> AABBQuery<AABB3<T>> for Segment3<T>
+where
+	T: Coordinate<Product = U> + From<u8>,
+	U: Ord,
+{
+	#[inline(always)]
+	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
+		match clip(&self.ray, bound) {
+			Some(((p, q), _)) if p <= q => AABBRelation::Intersect,
+			_ => AABBRelation::Interleave,
+		}
+	}
+}
+
+cfg_test! {
+	// naive_ray3_i64 samples the points on the ray at the origin and
+	// where the ray crosses the planes of the AABB, the ray hits the
+	// AABB if and only if one of them is inside, since the earliest
+	// hitting point must be one of them.
+	fn naive_ray3_i64(
+		ray: &Ray3<i64>, bound: &AABB3<i64>,
+	) -> AABBRelation {
+		let w = |v: Vec3<i64>| Vec3::new(
+			v.0 as i128, v.1 as i128, v.2 as i128,
+		);
+		let (o, d) = (w(ray.origin()), w(ray.dir()));
+		let (lo, hi) = (w(bound.min()), w(bound.max()));
+		let mut samples = vec![(0i128, 1i128)];
+		let axes = [(o.0, d.0, lo.0, hi.0), (o.1, d.1, lo.1, hi.1),
+			(o.2, d.2, lo.2, hi.2)];
+		for (o, d, lo, hi) in axes {
+			if d != 0 {
+				samples.push((lo - o, d));
+				samples.push((hi - o, d));
+			}
+		}
+		for (p, q) in samples {
+			let (p, q) = if q < 0 { (-p, -q) } else { (p, q) };
+			if p < 0 {
+				continue;
+			}
+			// The point is (o * q + p * d) / q on each axis.
+			let inside = axes.iter().all(|(o, d, lo, hi)| {
+				let x = o * q + p * d;
+				lo * q <= x && x <= hi * q
+			});
+			if inside {
+				return AABBRelation::Intersect;
+			}
+		}
+		AABBRelation::Interleave
+	}
+
+	#[test] fn test_ray3_i64_query() {
+		let aabb = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(4, 4, 4));
+		let check = |o: (i64, i64, i64), d: (i64, i64, i64)| {
+			Ray3::new(o.into(), d.into()).check(&aabb)
+		};
+		assert_eq!(check((-2, 1, 1), (1, 0, 0)), AABBRelation::Intersect);
+		assert_eq!(
+			check((-2, 1, 1), (-1, 0, 0)),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(check((2, 2, 2), (0, 0, 0)), AABBRelation::Intersect);
+		assert_eq!(check((5, 2, 2), (0, 0, 0)), AABBRelation::Interleave);
+		assert_eq!(check((-1, 9, 2), (1, -1, 0)), AABBRelation::Intersect);
+		assert_eq!(
+			check((-1, 10, 2), (1, -1, 0)),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(
+			check((-4, -4, -4), (1, 1, 1)),
+			AABBRelation::Intersect,
+		);
+		assert_eq!(check((8, 0, 0), (-3, 1, 0)), AABBRelation::Intersect);
+		assert_eq!(check((4, 5, 0), (0, -1, 0)), AABBRelation::Intersect);
+		assert_eq!(check((5, 5, 0), (0, -1, 0)), AABBRelation::Interleave);
+	}
+
+	#[test] fn test_ray3_i64_random_query() {
+		const NUM: usize = 1000000;
+		let rng = &mut prng_seeded().0;
+		let mut intersect = 0usize;
+		let mut interleave = 0usize;
+		for _ in 0..NUM {
+			let aabb = AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng));
+			let origin = gen_vec3_i64(rng);
+
+			// Aim at a point inside the AABB so that the rays will
+			// hit, or at a random one which usually misses.
+			let (lo, hi) = (aabb.min(), aabb.max());
+			let target = if rng.gen() {
+				Vec3::new(
+					lo.0 + rng.gen_range(0, hi.0 - lo.0 + 1),
+					lo.1 + rng.gen_range(0, hi.1 - lo.1 + 1),
+					lo.2 + rng.gen_range(0, hi.2 - lo.2 + 1),
+				)
+			} else {
+				gen_vec3_i64(rng)
+			};
+			let mut dir = target - origin;
+			if rng.gen_range(0, 4) == 0 {
+				dir.0 = 0;
+			}
+			if rng.gen_range(0, 4) == 0 {
+				dir.1 = 0;
+			}
+			let ray = Ray3::new(origin, dir);
+			let actual = ray.check(&aabb);
+			let expected = naive_ray3_i64(&ray, &aabb);
+			assert_eq!(
+				actual, expected,
+				"ray = {:?}, aabb = {:?}",
+				ray, aabb,
+			);
+			match actual {
+				AABBRelation::Intersect => intersect += 1,
+				AABBRelation::Interleave => interleave += 1,
+				AABBRelation::Include => unreachable!(),
+			}
+		}
+		println!("intersect = {}, interleave = {}", intersect, interleave);
+	}
+
+	#[test] fn test_bvh_i64_query_ray_sorted() {
+		// The boxes are placed along the ray out of order, and the box
+		// 4 is off the ray.
+		let cube = |x: i64, y: i64| {
+			let p = Vec3::new(x, y, 0);
+			AABB3::new(p, p + Vec3::new(2, 2, 2))
+		};
+		let items = vec![
+			(cube(9, 0), 0),
+			(cube(3, 0), 1),
+			(cube(-6, 0), 2),
+			(cube(6, 0), 3),
+			(cube(5, 8), 4),
+			(cube(0, 0), 5),
+		];
+		let bvh = BVH::build(items.clone());
+		let ray = Ray3::new(Vec3::new(-1, 1, 1), Vec3::new(3, 0, 0));
+		let hits = bvh.query_ray_sorted(&ray);
+		assert_eq!(hits, vec![&5, &1, &3, &0]);
+		let sorted = bvh.query_sorted_by(&ray, |b| b.min().0);
+		assert_eq!(sorted, hits);
+		let ray = Ray3::new(Vec3::new(20, 1, 1), Vec3::new(-1, 0, 0));
+		assert_eq!(bvh.query_ray_sorted(&ray), vec![&0, &3, &1, &5, &2]);
+
+		// The entries of the random rays are non-decreasing.
+		let rng = &mut prng_seeded().0;
+		for _ in 0..100 {
+			let origin = Vec3::new(
+				rng.gen_range(-10, 20),
+				rng.gen_range(-5, 10),
+				rng.gen_range(-5, 5),
+			);
+			let target = Vec3::new(
+				rng.gen_range(-10, 20),
+				rng.gen_range(-5, 10),
+				rng.gen_range(-5, 5),
+			);
+			let ray = Ray3::new(origin, target - origin);
+			let hits = bvh.query_ray_sorted(&ray);
+			assert_eq!(hits.len(), bvh.query(&ray).count());
+			let entries: Vec<_> = hits
+				.iter()
+				.map(|v| items[**v].0.ray_intersection(&ray).unwrap().0)
+				.collect();
+			for w in entries.windows(2) {
+				assert!(w[0].num * w[1].den <= w[1].num * w[0].den);
+			}
+		}
+	}
+
+	#[test] fn test_aabb3_i64_ray_intersection() {
+		let aabb = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(4, 4, 4));
+		let hit = |o: (i64, i64, i64), d: (i64, i64, i64)| {
+			let (near, far) =
+				aabb.ray_intersection(&Ray3::new(o.into(), d.into()))?;
+			Some(((near.num, near.den), (far.num, far.den)))
+		};
+
+		// Diagonal hits, from outside and from inside.
+		assert_eq!(hit((-1, -1, -1), (1, 1, 1)), Some(((1, 1), (5, 1))));
+		assert_eq!(hit((-2, 0, 1), (2, 1, 1)), Some(((2, 2), (6, 2))));
+		assert_eq!(hit((2, 2, 2), (1, -2, 1)), Some(((0, 1), (2, 2))));
+		assert_eq!(hit((-1, -1, -1), (-1, 1, 1)), None);
+
+		// Grazing the edge or the corner of the AABB.
+		assert_eq!(hit((-1, 3, 2), (1, 1, 0)), Some(((1, 1), (1, 1))));
+		assert_eq!(hit((-1, -1, -1), (1, 1, -1)), None);
+		assert_eq!(hit((-1, 9, 5), (1, -1, -1)), Some(((5, 1), (5, 1))));
+
+		// The axes of zero direction components.
+		assert_eq!(hit((2, -3, 4), (0, 1, 0)), Some(((3, 1), (7, 1))));
+		assert_eq!(hit((2, -3, 5), (0, 1, 0)), None);
+		assert_eq!(hit((2, 2, 2), (0, 0, 0)), Some(((0, 1), (0, 1))));
+		assert_eq!(hit((2, 2, 5), (0, 0, 0)), None);
+	}
+
+	#[test] fn test_segment3_i64_query() {
+		let aabb = AABB3::new(Vec3::new(0, 0, 0), Vec3::new(4, 4, 4));
+		let check = |a: (i64, i64, i64), b: (i64, i64, i64)| {
+			Segment3::new(a.into(), b.into()).check(&aabb)
+		};
+		assert_eq!(
+			check((-2, 1, 1), (-1, 1, 1)),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(check((-2, 1, 1), (0, 1, 1)), AABBRelation::Intersect);
+		assert_eq!(check((-2, 1, 1), (6, 1, 1)), AABBRelation::Intersect);
+		assert_eq!(check((1, 1, 1), (2, 2, 2)), AABBRelation::Intersect);
+		assert_eq!(check((-1, 3, 2), (0, 4, 2)), AABBRelation::Intersect);
+		assert_eq!(
+			check((-2, 3, 2), (-1, 4, 2)),
+			AABBRelation::Interleave,
+		);
+		assert_eq!(check((5, 1, 1), (9, 1, 1)), AABBRelation::Interleave);
+		assert_eq!(check((2, -1, 5), (2, 9, 5)), AABBRelation::Interleave);
+		assert_eq!(check((2, 2, 2), (2, 2, 2)), AABBRelation::Intersect);
+	}
+}
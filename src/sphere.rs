@@ -0,0 +1,178 @@
+use std::ops::Sub;
+
+use crate::{cfg_test, AABBQuery, AABBRelation, Coordinate, Vec3, AABB3};
+
+cfg_test! {
+	use rand::Rng;
+	use crate::{prng_seeded, gen_vec3_i64};
+	use crate::naive::SphereNaive3;
+}
+
+/// Sphere3 is a three dimensional sphere denoted by its center and
+/// the square of its radius.
+///
+/// Keeping the squared radius instead of the radius allows us to
+/// compare distances in squared form, so no square root is needed
+/// and the comparison stays exact for integer coordinates.
+///
+/// U is the type of squared distances, which are accumulated from
+/// the squares of three coordinate differences. So U must be capable
+/// to hold three times the square of the largest difference, e.g.
+/// the differences of i64 coordinates should be within the floor of
+/// sqrt(i64::MAX / 3), which is 1753413056, slightly above 2^30, for
+/// a U=i64, while U=i128 is always exact for i64 coordinates.
+#[derive(Copy, Clone, Debug)]
+pub struct Sphere3<T, U> {
+	center: Vec3<T>,
+	radius2: U,
+}
+
+impl<T: Copy, U: Copy> Sphere3<T, U> {
+	#[inline(always)]
+	pub fn new(center: Vec3<T>, radius2: U) -> Self {
+		Self {
+			center: center,
+			radius2: radius2,
+		}
+	}
+
+	/// center of the sphere.
+	#[inline(always)]
+	pub fn center(&self) -> Vec3<T> {
+		self.center
+	}
+
+	/// radius2 is the square of the sphere's radius.
+	#[inline(always)]
+	pub fn radius2(&self) -> U {
+		self.radius2
+	}
+}
+
+// farthest_of returns the endpoint of the interval farther from x.
+fn farthest_of<T: Ord + Copy + Sub<Output = T>>(x: T, lo: T, hi: T) -> T {
+	if x - lo >= hi - x {
+		lo
+	} else {
+		hi
+	}
+}
+
+/// The sphere is compared with the AABB by the squared distances
+/// from its center to the closest point and the farthest corner of
+/// the AABB, which are evaluated with Vec3::dot_wide in U.
+///
+/// Each component of the differences is evaluated in T, and then
+/// squared and summed in U, so U must be wide enough to hold the
+/// squared distance, e.g. U=i128 for T=i64 is always exact as long
+/// as the coordinates differences do not overflow i64.
+impl<T, U> AABBQuery<AABB3<T>> for Sphere3<T, U>
+where
+	T: Coordinate,
+	U: Coordinate<Product = U> + From<T>,
+{
+	#[inline(always)]
+	fn check(&self, bound: &AABB3<T>) -> AABBRelation {
+		let c = self.center;
+		let v = Vec3::<(T, T)>::from(*bound);
+		let near =
+			c / v | (|(x, (lo, hi)): (T, (T, T))| x - x.clamp(lo, hi));
+		if near.dot_wide::<U>(near) > self.radius2 {
+			return AABBRelation::Interleave;
+		}
+		let far = c / v
+			| (|(x, (lo, hi)): (T, (T, T))| x - farthest_of(x, lo, hi));
+		if far.dot_wide::<U>(far) < self.radius2 {
+			return AABBRelation::Include;
+		}
+		AABBRelation::Intersect
+	}
+}
+
+cfg_test! {
+	#[test] fn test_sphere3_i64_wide_query() {
+		const R: i64 = 1 << 41;
+		let c = Vec3::new(1 << 40, -(1 << 40), 1 << 40);
+		let r2 = (R as i128) * (R as i128);
+		let sphere = Sphere3::<i64, i128>::new(c, r2);
+		assert!(R.checked_mul(R).is_none());
+
+		let d = Vec3::new(1 << 39, 1 << 39, 1 << 39);
+		let inside = AABB3::new(c - d, c + d);
+		assert_eq!(sphere.check(&inside), AABBRelation::Include);
+
+		let d = Vec3::new(R, 0, 0);
+		let touch = AABB3::new(c + d, c + d + d);
+		assert_eq!(sphere.check(&touch), AABBRelation::Intersect);
+
+		let e = Vec3::new(1, 0, 0);
+		let outside = AABB3::new(c + d + e, c + d + d);
+		assert_eq!(sphere.check(&outside), AABBRelation::Interleave);
+
+		let d = Vec3::new(1 << 40, 1 << 40, 0);
+		let across = AABB3::new(c, c + d + d);
+		assert_eq!(sphere.check(&across), AABBRelation::Intersect);
+
+		let d = Vec3::new(R, R, R);
+		let enclosing = AABB3::new(c - d, c + d);
+		assert_eq!(sphere.check(&enclosing), AABBRelation::Intersect);
+
+		// The farthest corner is exactly on the sphere, which is
+		// not strictly inside the sphere.
+		let d = Vec3::new(R, 0, 0);
+		let surface = AABB3::new(c - d, c);
+		assert_eq!(sphere.check(&surface), AABBRelation::Intersect);
+	}
+
+	#[test] fn test_sphere3_i64_narrow_limit() {
+		// The largest differences whose squared distances fit i64.
+		const L: i64 = 1753413056;
+		let square3 = |x: i64| x.checked_mul(x)?.checked_mul(3);
+		assert!(square3(L).is_some());
+		assert!(square3(L + 1).is_none());
+		let r2 = 3 * L * L;
+		let c = Vec3::new(0, 0, 0);
+		let d = Vec3::new(L, L, L);
+		let corner = AABB3::new(d, d);
+		let bound = AABB3::new(c, d);
+
+		let sphere = Sphere3::<i64, i64>::new(c, r2);
+		assert_eq!(sphere.check(&corner), AABBRelation::Intersect);
+		assert_eq!(sphere.check(&bound), AABBRelation::Intersect);
+		let sphere = Sphere3::<i64, i64>::new(c, r2 - 1);
+		assert_eq!(sphere.check(&corner), AABBRelation::Interleave);
+		let sphere = Sphere3::<i64, i64>::new(c, i64::MAX);
+		assert_eq!(sphere.check(&bound), AABBRelation::Include);
+	}
+
+	#[test] fn test_sphere3_i64_random_query() {
+		const NUM: usize = 1000000;
+		let rng = &mut prng_seeded().0;
+		let mut include = 0usize;
+		let mut intersect = 0usize;
+		let mut interleave = 0usize;
+		for _ in 0..NUM {
+			let c = gen_vec3_i64(rng);
+			let r = (rng.gen::<u32>() >> 1) as i128;
+			let aabb = AABB3::new(gen_vec3_i64(rng), gen_vec3_i64(rng));
+			let sphere = Sphere3::<i64, i128>::new(c, r * r);
+			let naive = SphereNaive3::new(c, r * r);
+			let actual = sphere.check(&aabb);
+			let expected = naive.check(&aabb);
+			assert_eq!(
+				actual, expected,
+				"sphere = {:?}, aabb = {:?}",
+				sphere, aabb,
+			);
+			match actual {
+				AABBRelation::Include => include += 1,
+				AABBRelation::Intersect => intersect += 1,
+				AABBRelation::Interleave => interleave += 1,
+			}
+		}
+		println!(
+			"include = {}, intersect = {}, interleave = {}",
+			include, intersect, interleave,
+		);
+	}
+}
@@ -1,29 +1,106 @@
-use std::cmp::min;
+use std::cell::Cell;
+use std::sync::Once;
 
-use anyhow::Result;
 use rand::prng::chacha::{ChaChaCore, ChaChaRng};
 use rand::{Rng, SeedableRng};
 
+// env_seed retrieves the seed specified by SPATIAL_TEST_SEED, which
+// must be the base64 encoding of the 32-byte seed.
+fn env_seed() -> Option<[u8; 32]> {
+	let val = match std::env::var("SPATIAL_TEST_SEED") {
+		Ok(val) => val,
+		Err(std::env::VarError::NotPresent) => return None,
+		Err(err) => panic!("invalid SPATIAL_TEST_SEED: {}", err),
+	};
+	let bytes = match base64::decode_config(&val, base64::STANDARD) {
+		Ok(bytes) => bytes,
+		Err(err) => panic!("invalid SPATIAL_TEST_SEED {:?}: {}", val, err),
+	};
+	let mut seed = [0u8; 32];
+	if bytes.len() != seed.len() {
+		panic!(
+			"invalid SPATIAL_TEST_SEED {:?}: {} bytes, 32 expected",
+			val,
+			bytes.len(),
+		);
+	}
+	seed.copy_from_slice(&bytes);
+	Some(seed)
+}
+
+// make_prng creates the generator from the seed.
+fn make_prng(seed: [u8; 32]) -> ChaChaRng {
+	ChaChaRng::from(ChaChaCore::from_seed(seed))
+}
+
 /// prng creates a new instance of random number generator.
 ///
 /// The caller can utilize the environment SPATIAL_TEST_SEED for
-/// setting a seed for randomizing. When benchmarking, they will
-/// generate the same series of data so that different cases will
-/// operate on the same set of results.
+/// setting a seed for randomizing, which is the base64 encoding of
+/// the 32-byte seed, and the zero seed is used when it is unset.
+/// When benchmarking, they will generate the same series of data so
+/// that different cases will operate on the same set of results.
 pub fn prng() -> impl Rng {
-	let mut seed = [0 as u8; 32];
-	let _ = || -> Result<()> {
-		let val = std::env::var("SPATIAL_TEST_SEED")?;
-		if let Ok(_) =
-			base64::decode_config_slice(&val, base64::STANDARD, &mut seed)
-		{
-			return Ok(());
-		}
-		let bytes = val.as_bytes();
-		for i in 0..min(bytes.len(), 32) {
-			seed[i] = bytes[i]
-		}
-		Ok(())
-	}();
-	ChaChaRng::from(ChaChaCore::from_seed(seed))
+	make_prng(env_seed().unwrap_or([0u8; 32]))
+}
+
+/// prng_seeded creates a new instance of random number generator
+/// along with the seed it is created from.
+///
+/// The seed of the thread running the test is specified by
+/// SPATIAL_TEST_SEED as prng does, or a random one if it is unset,
+/// and the generators created in the same thread are seeded by it
+/// along with the order they are created. The seed of the thread is
+/// printed in the form of SPATIAL_TEST_SEED once the test panics,
+/// even if the generators have been dropped, so that the failed test
+/// can be reproduced with it.
+pub fn prng_seeded() -> (impl Rng, [u8; 32]) {
+	static HOOK: Once = Once::new();
+	HOOK.call_once(|| {
+		let next = std::panic::take_hook();
+		std::panic::set_hook(Box::new(move |info| {
+			next(info);
+			if let Some((seed, _)) = THREAD_SEED.with(Cell::get) {
+				eprintln!(
+					"SPATIAL_TEST_SEED={}",
+					base64::encode_config(seed, base64::STANDARD),
+				);
+			}
+		}));
+	});
+	let (mut seed, count) = THREAD_SEED.with(|cell| {
+		let (seed, count) = cell.get().unwrap_or_else(|| {
+			(env_seed().unwrap_or_else(rand::random), 0)
+		});
+		cell.set(Some((seed, count + 1)));
+		(seed, count)
+	});
+	for (b, c) in seed[24..].iter_mut().zip(count.to_le_bytes()) {
+		*b ^= c;
+	}
+	(make_prng(seed), seed)
+}
+
+thread_local! {
+	// THREAD_SEED is the seed of the current thread and the number of
+	// generators created from it by prng_seeded.
+	static THREAD_SEED: Cell<Option<([u8; 32], u64)>> =
+		const { Cell::new(None) };
+}
+
+#[test] fn test_prng_seeded() {
+	let (mut rng, seed) = prng_seeded();
+	let mut other = make_prng(seed);
+	for _ in 0..100 {
+		assert_eq!(rng.gen::<u64>(), other.gen::<u64>());
+	}
+
+	// The later generators of the thread are seeded differently.
+	let (mut rng, next) = prng_seeded();
+	assert_ne!(next, seed);
+	assert_eq!(next[..24], seed[..24]);
+	let mut other = make_prng(next);
+	for _ in 0..100 {
+		assert_eq!(rng.gen::<u64>(), other.gen::<u64>());
+	}
 }
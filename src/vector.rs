@@ -1,11 +1,15 @@
-use std::cmp::Ordering;
-use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Sub};
+use std::cmp::{max, min, Ordering};
+use std::hash::{Hash, Hasher};
+use std::ops::{
+	Add, BitAnd, BitOr, BitXor, Div, Index, IndexMut, Mul, Neg, Sub,
+};
 
 use crate::cfg_test;
 
 cfg_test! {
 	extern crate test;
 
+	use std::collections::HashSet;
 	use rand::Rng;
 	use crate::prng;
 	use test::Bencher;
@@ -14,6 +18,10 @@ cfg_test! {
 /// Vec3 represents a three-dimensional vector, offered with some
 /// convenient operations.
 #[derive(Copy, Clone, Debug)]
+#[cfg_attr(
+	feature = "serde",
+	derive(serde::Serialize, serde::Deserialize)
+)]
 pub struct Vec3<T>(pub T, pub T, pub T);
 
 impl<T> Vec3<T> {
@@ -21,6 +29,65 @@ impl<T> Vec3<T> {
 	pub fn new(v1: T, v2: T, v3: T) -> Self {
 		Self(v1, v2, v3)
 	}
+
+	/// map applies the function to each component in the order of
+	/// x, y and z, see also BitOr.
+	#[inline(always)]
+	pub fn map<U>(self, f: impl FnMut(T) -> U) -> Vec3<U> {
+		self | f
+	}
+
+	/// try_map applies the fallible function to each component in the
+	/// order of x, y and z, and returns the first error, e.g. the
+	/// failed conversion of components, see also BitAnd.
+	#[inline(always)]
+	pub fn try_map<U, E>(
+		self, mut f: impl FnMut(T) -> Result<U, E>,
+	) -> Result<Vec3<U>, E> {
+		Ok(Vec3::new(f(self.0)?, f(self.1)?, f(self.2)?))
+	}
+
+	/// cast converts each component into the type U losslessly, e.g.
+	/// from Vec3<i32> to Vec3<i64>.
+	#[inline(always)]
+	pub fn cast<U: From<T>>(self) -> Vec3<U> {
+		self.map(U::from)
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_i32_map_cast() {
+		let v = Vec3::<i32>::new(1, -2, i32::MAX);
+		let w: Vec3<i64> = v.cast();
+		assert_eq!(w, Vec3::new(1, -2, i32::MAX as i64));
+		assert_eq!(w.map(|x| x + 1), Vec3::new(2, -1, 1 << 31));
+		let f: Vec3<f64> = v.cast();
+		assert_eq!(f.map(|x| x as i32), v);
+
+		let mut order = Vec::new();
+		let s = v.map(|x| {
+			order.push(x);
+			x.to_string()
+		});
+		assert_eq!(order, vec![1, -2, i32::MAX]);
+		assert_eq!(s.0, "1");
+	}
+
+	#[test] fn test_vec3_i64_try_map() {
+		let v = Vec3::new(1i64, 2, 3);
+		let w: Result<Vec3<i8>, _> = v.try_map(i8::try_from);
+		assert_eq!(w, Ok(Vec3::new(1, 2, 3)));
+
+		// The mapping stops at the first error.
+		let v = Vec3::new(1i64, 300, -300);
+		let mut visited = Vec::new();
+		let w = v.try_map(|x| {
+			visited.push(x);
+			i8::try_from(x).map_err(|_| format!("{} out of range", x))
+		});
+		assert_eq!(w, Err("300 out of range".to_string()));
+		assert_eq!(visited, vec![1, 300]);
+	}
 }
 
 cfg_test! {
@@ -74,29 +141,139 @@ impl<T> From<(T, T, T)> for Vec3<T> {
 	}
 }
 
+impl<T> From<Vec3<T>> for [T; 3] {
+	#[inline(always)]
+	fn from(v: Vec3<T>) -> [T; 3] {
+		[v.0, v.1, v.2]
+	}
+}
+
+impl<T> From<[T; 3]> for Vec3<T> {
+	#[inline(always)]
+	fn from([x, y, z]: [T; 3]) -> Vec3<T> {
+		Self(x, y, z)
+	}
+}
+
+impl<T: Copy> Vec3<T> {
+	/// as_array copies the components into an array, in the order
+	/// of x, y and z.
+	#[inline(always)]
+	pub fn as_array(&self) -> [T; 3] {
+		[self.0, self.1, self.2]
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_i64_array() {
+		let v = Vec3::new(1, -2, 3);
+		let a: [i64; 3] = v.into();
+		assert_eq!(a, [v.0, v.1, v.2]);
+		assert_eq!(v.as_array(), a);
+		assert_eq!(Vec3::from(a), v);
+		assert_eq!(Vec3::from([4, 5, 6]), Vec3::new(4, 5, 6));
+	}
+}
+
+/// Index retrieves the component on the axis, which is either 0, 1
+/// or 2 for x, y and z, and panics otherwise.
+impl<T> Index<usize> for Vec3<T> {
+	type Output = T;
+	#[inline(always)]
+	fn index(&self, axis: usize) -> &T {
+		match axis {
+			0 => &self.0,
+			1 => &self.1,
+			2 => &self.2,
+			_ => panic!("axis {} out of range for Vec3", axis),
+		}
+	}
+}
+
+/// IndexMut retrieves the mutable component on the axis, see also
+/// the Index of Vec3.
+impl<T> IndexMut<usize> for Vec3<T> {
+	#[inline(always)]
+	fn index_mut(&mut self, axis: usize) -> &mut T {
+		match axis {
+			0 => &mut self.0,
+			1 => &mut self.1,
+			2 => &mut self.2,
+			_ => panic!("axis {} out of range for Vec3", axis),
+		}
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_i64_index() {
+		let mut v = Vec3::new(1, 2, 3);
+		assert_eq!((v[0], v[1], v[2]), (1, 2, 3));
+		v[0] = -1;
+		v[1] += 3;
+		v[2] *= 2;
+		assert_eq!(v, Vec3::new(-1, 5, 6));
+	}
+
+	#[test]
+	#[should_panic(expected = "axis 3 out of range for Vec3")]
+	fn test_vec3_i64_index_out_of_range() {
+		let v = Vec3::new(1, 2, 3);
+		let _ = v[3];
+	}
+
+	#[test]
+	#[should_panic(expected = "axis 3 out of range for Vec3")]
+	fn test_vec3_i64_index_mut_out_of_range() {
+		let mut v = Vec3::new(1, 2, 3);
+		v[3] = 4;
+	}
+}
+
 /// BitOr is bitwise mapping of each components.
-impl<T, U, F: FnMut<T, Output = U>> BitOr<F> for Vec3<T> {
+///
+/// The function takes each component as its single argument, so the
+/// components zipped by Div are taken as a single tuple, which must
+/// be destructured like |(x, y)| rather than |x, y|.
+impl<T, U, F: FnMut(T) -> U> BitOr<F> for Vec3<T> {
 	type Output = Vec3<U>;
 	#[inline(always)]
 	fn bitor(self, mut f: F) -> Self::Output {
-		Vec3::new(
-			f.call_mut(self.0),
-			f.call_mut(self.1),
-			f.call_mut(self.2),
-		)
+		Vec3::new(f(self.0), f(self.1), f(self.2))
 	}
 }
 
 /// BitAnd is failfast bitwise mapping of each components.
-impl<T, U: Sized, F: FnMut<T, Output = Option<U>>> BitAnd<F> for Vec3<T> {
+impl<T, U, F: FnMut(T) -> Option<U>> BitAnd<F> for Vec3<T> {
 	type Output = Option<Vec3<U>>;
 	#[inline(always)]
 	fn bitand(self, mut f: F) -> Self::Output {
-		Some(Vec3::new(
-			f.call_mut(self.0)?,
-			f.call_mut(self.1)?,
-			f.call_mut(self.2)?,
-		))
+		Some(Vec3::new(f(self.0)?, f(self.1)?, f(self.2)?))
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_i64_combinators() {
+		let v1 = Vec3::new(1, -2, 3);
+		let v2 = Vec3::new(4, 5, -6);
+		assert_eq!(v1 | (|x: i64| x * 2), Vec3::new(2, -4, 6));
+		assert_eq!(v1 / v2 | (|(x, y)| x + y), v1 + v2);
+		assert_eq!(v1 + v2, Vec3::new(5, 3, -3));
+		assert_eq!(v1 - v2, Vec3::new(-3, -7, 9));
+		assert_eq!(v1 ^ v2, 4 - 10 - 18);
+		assert_ne!(v1, v2);
+		assert_eq!(
+			v1 & (|x: i64| x.checked_add(1)),
+			Some(Vec3::new(2, -1, 4)),
+		);
+
+		// The mapping fails fast at the first failed component.
+		let mut visited = Vec::new();
+		let result = v1 & (|x: i64| {
+			visited.push(x);
+			(x > 0).then_some(x)
+		});
+		assert_eq!(result, None);
+		assert_eq!(visited, vec![1, -2]);
 	}
 }
 
@@ -126,7 +303,7 @@ impl<U, T: Add<S, Output = U>, S> Add<Vec3<S>> for Vec3<T> {
 	type Output = Vec3<U>;
 	#[inline(always)]
 	fn add(self, a: Vec3<S>) -> Self::Output {
-		self / a | (|x, y| x + y)
+		self / a | (|(x, y)| x + y)
 	}
 }
 
@@ -150,7 +327,27 @@ impl<U, T: Sub<S, Output = U>, S> Sub<Vec3<S>> for Vec3<T> {
 	type Output = Vec3<U>;
 	#[inline(always)]
 	fn sub(self, a: Vec3<S>) -> Self::Output {
-		self / a | (|x, y| x - y)
+		self / a | (|(x, y)| x - y)
+	}
+}
+
+/// Neg defines the vector negation.
+impl<U, T: Neg<Output = U>> Neg for Vec3<T> {
+	type Output = Vec3<U>;
+	#[inline(always)]
+	fn neg(self) -> Self::Output {
+		self | (|x: T| -x)
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_i64_neg() {
+		assert_eq!(-Vec3::new(1, -2, 3), Vec3::new(-1, 2, -3));
+		assert_eq!(-Vec3::<i8>::new(1, -2, 3), Vec3::new(-1, 2, -3));
+		assert_eq!(-Vec3::<i128>::new(0, 0, 7), Vec3::new(0, 0, -7));
+		for v in testdata_vec3_i64(100) {
+			assert_eq!(-v, Vec3::<i64>::default() - v);
+		}
 	}
 }
 
@@ -196,7 +393,7 @@ where
 	type Output = U;
 	#[inline(always)]
 	fn bitxor(self, a: Vec3<S>) -> Self::Output {
-		let v = self / a | (|x, y| x * y);
+		let v = self / a | (|(x, y)| x * y);
 		v.0 + v.1 + v.2
 	}
 }
@@ -213,6 +410,288 @@ cfg_test! {
 	}
 }
 
+// XXX: the scalar operations can't be implemented generically over
+// the scalar type S, since S might also be a Vec3 and overlap with
+// the cross product and zip operations. So they're implemented for
+// every primitive scalar type instead.
+macro_rules! impl_vec3_scalar {
+	($($t:ty),*) => {
+		$(
+			/// Mul with a scalar scales each components.
+			impl<U, T: Mul<$t, Output = U>> Mul<$t> for Vec3<T> {
+				type Output = Vec3<U>;
+				#[inline(always)]
+				fn mul(self, s: $t) -> Self::Output {
+					Vec3::new(self.0 * s, self.1 * s, self.2 * s)
+				}
+			}
+
+			/// Div with a scalar divides each components.
+			impl<U, T: Div<$t, Output = U>> Div<$t> for Vec3<T> {
+				type Output = Vec3<U>;
+				#[inline(always)]
+				fn div(self, s: $t) -> Self::Output {
+					Vec3::new(self.0 / s, self.1 / s, self.2 / s)
+				}
+			}
+		)*
+	};
+}
+
+impl_vec3_scalar!(i8, i16, i32, i64, i128, isize);
+impl_vec3_scalar!(u8, u16, u32, u64, u128, usize);
+impl_vec3_scalar!(f32, f64);
+
+cfg_test! {
+	#[test] fn test_vec3_i64_scalar() {
+		assert_eq!(Vec3::new(1, 2, 3) * 2, Vec3::new(2, 4, 6));
+		assert_eq!(Vec3::new(-4, 5, 6) * -3i64, Vec3::new(12, -15, -18));
+		assert_eq!(Vec3::new(2, 4, 6) / 2, Vec3::new(1, 2, 3));
+		assert_eq!(Vec3::new(-7, 7, 8) / 2i64, Vec3::new(-3, 3, 4));
+		let v: Vec3<f64> = Vec3::new(1.0, -2.0, 0.5) * 2.0;
+		assert!(v.approx_eq(Vec3::new(2.0, -4.0, 1.0), 0.0));
+	}
+
+	#[bench] fn bench_vec3_i64_scalar_mul(b: &mut Bencher) {
+		fixture_bench_vec3_i64(b, |x, y| x * y.0);
+	}
+}
+
+impl<T> Vec3<T> {
+	/// dot_wide evaluates the dot product in the wider type W, into
+	/// which the components are converted before multiplying.
+	///
+	/// This is useful for avoiding overflow of taking squares, e.g.
+	/// taking the dot product of i64 components in i128.
+	#[inline(always)]
+	pub fn dot_wide<W>(self, a: Vec3<T>) -> W
+	where
+		W: From<T> + Mul<Output = W> + Add<Output = W>,
+	{
+		let v = self / a | (|(x, y)| W::from(x) * W::from(y));
+		v.0 + v.1 + v.2
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_i64_dot_wide() {
+		let v1 = Vec3::new(1, 2, 3);
+		let v2 = Vec3::new(4, 5, 6);
+		assert_eq!(v1.dot_wide::<i128>(v2), 32);
+
+		let v = Vec3::new(i64::MAX, i64::MIN, 0);
+		let m = i64::MAX as i128;
+		assert_eq!(v.dot_wide::<i128>(v), m * m + (m + 1) * (m + 1));
+	}
+}
+
+// XXX: there's no trait for the checked arithmetics in std, so the
+// overflow-checked products are implemented for every primitive
+// integer type instead.
+macro_rules! impl_vec3_checked {
+	($($t:ty),*) => {
+		$(
+			impl Vec3<$t> {
+				/// checked_dot evaluates the dot product, returning
+				/// None if any of the operations overflows.
+				#[inline(always)]
+				pub fn checked_dot(self, a: Vec3<$t>) -> Option<$t> {
+					let v = self / a | (|(x, y): ($t, $t)| {
+						x.checked_mul(y)
+					});
+					v.0?.checked_add(v.1?)?.checked_add(v.2?)
+				}
+
+				/// checked_cross evaluates the cross product,
+				/// returning None if any of the operations overflows.
+				#[inline(always)]
+				pub fn checked_cross(
+					self, a: Vec3<$t>,
+				) -> Option<Vec3<$t>> {
+					let f = |x: $t, y: $t, z: $t, w: $t| {
+						x.checked_mul(y)?.checked_sub(z.checked_mul(w)?)
+					};
+					Some(Vec3::new(
+						f(self.1, a.2, self.2, a.1)?,
+						f(self.2, a.0, self.0, a.2)?,
+						f(self.0, a.1, self.1, a.0)?,
+					))
+				}
+			}
+		)*
+	};
+}
+
+impl_vec3_checked!(i8, i16, i32, i64, i128, isize);
+impl_vec3_checked!(u8, u16, u32, u64, u128, usize);
+
+cfg_test! {
+	#[test] fn test_vec3_i64_checked() {
+		let v1 = Vec3::new(1i64, 2, 3);
+		let v2 = Vec3::new(4i64, -5, 6);
+		assert_eq!(v1.checked_dot(v2), Some(v1 ^ v2));
+		assert_eq!(v1.checked_cross(v2), Some(v1 * v2));
+
+		let big = Vec3::new(i64::MAX, 1, 0);
+		assert_eq!(big.checked_dot(Vec3::new(2, 0, 0)), None);
+		assert_eq!(big.checked_dot(Vec3::new(1, 1, 0)), None);
+		let v = Vec3::new(1, -1, 0);
+		assert_eq!(big.checked_dot(v), Some(i64::MAX - 1));
+		assert_eq!(big.checked_cross(Vec3::new(0, 0, 2)), None);
+		assert_eq!(
+			big.checked_cross(Vec3::new(0, 0, 1)),
+			Some(Vec3::new(1, -i64::MAX, 0)),
+		);
+		let v = Vec3::new(i64::MIN, 0, 0);
+		assert_eq!(v.checked_cross(Vec3::new(0, 1, -1)), None);
+	}
+}
+
+impl<T: Copy> Vec3<T> {
+	/// length_squared evaluates the squared length of the vector,
+	/// which is the dot product with itself.
+	#[inline(always)]
+	pub fn length_squared<U>(self) -> U
+	where
+		T: Mul<Output = U>,
+		U: Add<Output = U>,
+	{
+		self ^ self
+	}
+
+	/// distance_squared evaluates the squared distance between the
+	/// points denoted by the vectors.
+	#[inline(always)]
+	pub fn distance_squared<U>(self, a: Vec3<T>) -> U
+	where
+		T: Sub<Output = T> + Mul<Output = U>,
+		U: Add<Output = U>,
+	{
+		(self - a).length_squared()
+	}
+
+	/// triple_product evaluates the scalar triple product a ^ (b * c),
+	/// which is the signed volume of the parallelepiped spanned by
+	/// the vectors. It is positive if the vectors form a right-handed
+	/// basis, negative if left-handed, and zero if coplanar.
+	///
+	/// Since only multiplications and subtractions are involved, it
+	/// is exact for the integer components, which is handy for the
+	/// orientation tests, e.g. which side of the plane through three
+	/// points is the fourth point on.
+	#[inline(always)]
+	pub fn triple_product<U, V, W>(a: Vec3<T>, b: Vec3<T>, c: Vec3<T>) -> W
+	where
+		T: Mul<Output = U> + Mul<V, Output = W>,
+		U: Sub<Output = V>,
+		V: Copy,
+		W: Add<Output = W>,
+	{
+		a ^ (b * c)
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_i64_length_squared() {
+		assert_eq!(Vec3::new(3, 4, 0).length_squared(), 25);
+		assert_eq!(Vec3::new(0, -5, 12).length_squared(), 169);
+		assert_eq!(Vec3::new(2, 3, 6).length_squared(), 49);
+		assert_eq!(Vec3::new(1, 4, 8).length_squared(), 81);
+		let p = Vec3::new(1, 2, 3);
+		assert_eq!(p.distance_squared(p + Vec3::new(-2, 3, 6)), 49);
+		assert_eq!((p + Vec3::new(4, -4, 7)).distance_squared(p), 81);
+		assert_eq!(p.distance_squared(p), 0);
+	}
+
+	#[test] fn test_vec3_i64_triple_product() {
+		let (x, y, z) = (
+			Vec3::new(1, 0, 0),
+			Vec3::new(0, 1, 0),
+			Vec3::new(0, 0, 1),
+		);
+		assert_eq!(Vec3::triple_product(x, y, z), 1);
+		assert_eq!(Vec3::triple_product(y, z, x), 1);
+		assert_eq!(Vec3::triple_product(y, x, z), -1);
+		assert_eq!(Vec3::triple_product(x, z, y), -1);
+
+		let (a, b) = (Vec3::new(1, 2, 3), Vec3::new(-4, 5, 6));
+		let c = Vec3::new(2, 0, -1);
+		assert_eq!(Vec3::triple_product(a, b, c), -19);
+		assert_eq!(Vec3::triple_product(b, a, c), 19);
+		assert_eq!(Vec3::triple_product(a * 2, b, c), -38);
+
+		// The coplanar vectors span no volume.
+		assert_eq!(Vec3::triple_product(a, b, a + b), 0);
+		assert_eq!(Vec3::triple_product(a, b, a * 3 - b * 2), 0);
+		assert_eq!(Vec3::triple_product(a, a, c), 0);
+
+		// The side of the point to the plane through three points.
+		let (p0, p1, p2) = (a, b, c);
+		let side = |p: Vec3<i64>| {
+			Vec3::triple_product(p1 - p0, p2 - p0, p - p0)
+		};
+		let normal = (p1 - p0) * (p2 - p0);
+		assert!(side(p0 + normal) > 0);
+		assert!(side(p0 - normal) < 0);
+		assert_eq!(side(p0 + (p1 - p0) * 5 + (p2 - p0) * -2), 0);
+	}
+
+	#[bench] fn bench_vec3_i64_length_squared(b: &mut Bencher) {
+		fixture_bench_vec3_i64(b, |x, _| x.length_squared::<i64>());
+	}
+}
+
+impl<T: Copy + Add<Output = T>> Vec3<T> {
+	/// midpoint evaluates the average of the vectors, which truncates
+	/// toward zero for integer components.
+	///
+	/// Please notice the components are summed before the division,
+	/// so T must be wide enough to hold the sum.
+	#[inline(always)]
+	pub fn midpoint(self, a: Vec3<T>) -> Vec3<T>
+	where
+		T: Div<Output = T> + From<u8>,
+	{
+		let two = T::from(2);
+		self / a | (|(x, y)| (x + y) / two)
+	}
+
+	/// lerp interpolates linearly from the current vector at t = 0 to
+	/// the other vector at t = 1, which is meant for the float
+	/// components with t in [0, 1].
+	#[inline(always)]
+	pub fn lerp(self, a: Vec3<T>, t: T) -> Vec3<T>
+	where
+		T: Sub<Output = T> + Mul<Output = T>,
+	{
+		self / a | (|(x, y)| x + (y - x) * t)
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_i64_midpoint() {
+		let v1 = Vec3::new(1, 2, -3);
+		let v2 = Vec3::new(5, 5, 0);
+		assert_eq!(v1.midpoint(v2), Vec3::new(3, 3, -1));
+		assert_eq!(v2.midpoint(v1), v1.midpoint(v2));
+		assert_eq!(v1.midpoint(v1), v1);
+		let v = Vec3::new(-1, 1, -2);
+		assert_eq!(v.midpoint(Vec3::default()), Vec3::new(0, 0, -1));
+	}
+
+	#[test] fn test_vec3_f64_lerp() {
+		let v1 = Vec3::new(1.0, -2.0, 4.0);
+		let v2 = Vec3::new(3.0, 2.0, -4.0);
+		assert!(v1.lerp(v2, 0.0).approx_eq(v1, 0.0));
+		assert!(v1.lerp(v2, 1.0).approx_eq(v2, 0.0));
+		let mid = Vec3::new(2.0, 0.0, 0.0);
+		assert!(v1.lerp(v2, 0.5).approx_eq(mid, 0.0));
+		assert!(v1.midpoint(v2).approx_eq(mid, 0.0));
+		let quarter = Vec3::new(1.5, -1.0, 2.0);
+		assert!(v1.lerp(v2, 0.25).approx_eq(quarter, 1e-12));
+	}
+}
+
 impl<T: Copy + Default> Default for Vec3<T> {
 	#[inline(always)]
 	fn default() -> Self {
@@ -232,19 +711,161 @@ impl<T: Copy + Ord + Default> Vec3<T> {
 	/// the orientations, so there's no need for normalizing.
 	#[inline(always)]
 	pub fn to_ordering(self) -> Vec3<Ordering> {
+		self.to_ordering_about(Vec3::default())
+	}
+}
+
+impl<T: Copy + PartialOrd + Default> Vec3<T> {
+	/// to_ordering_partial is the counterpart of to_ordering for the
+	/// partially ordered components like f32 and f64, and None will be
+	/// returned if any of the components is not comparable (NaN).
+	///
+	/// Please notice -0.0 compares equal to zero.
+	#[inline(always)]
+	pub fn to_ordering_partial(self) -> Option<Vec3<Ordering>> {
 		let zero = T::default();
-		Vec3::new(
-			(&self.0).cmp(&zero),
-			(&self.1).cmp(&zero),
-			(&self.2).cmp(&zero),
-		)
+		Some(Vec3::new(
+			self.0.partial_cmp(&zero)?,
+			self.1.partial_cmp(&zero)?,
+			self.2.partial_cmp(&zero)?,
+		))
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_f64_to_ordering_partial() {
+		let v = Vec3::new(1.5, -0.25, 0.0);
+		assert_eq!(
+			v.to_ordering_partial(),
+			Some(Vec3::new(
+				Ordering::Greater, Ordering::Less, Ordering::Equal,
+			)),
+		);
+		let v = Vec3::new(-0.0, 0.0, -1e-300);
+		assert_eq!(
+			v.to_ordering_partial(),
+			Some(Vec3::new(
+				Ordering::Equal, Ordering::Equal, Ordering::Less,
+			)),
+		);
+		let v = Vec3::new(1.0, f64::NAN, -1.0);
+		assert_eq!(v.to_ordering_partial(), None);
+	}
+}
+
+impl<T: Copy + Ord> Vec3<T> {
+	/// to_ordering_about evaluates the spatial orientation of the
+	/// vector relative to the pivot, by comparing each component
+	/// with the pivot's one.
+	///
+	/// This is the generalization of to_ordering, which is taken
+	/// about the origin.
+	#[inline(always)]
+	pub fn to_ordering_about(self, pivot: Vec3<T>) -> Vec3<Ordering> {
+		self / pivot | (|(x, y): (T, T)| x.cmp(&y))
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_i64_to_ordering_about() {
+		for v in testdata_vec3_i64(1000) {
+			let zero = Vec3::new(0, 0, 0);
+			assert_eq!(v.to_ordering_about(zero), v.to_ordering());
+		}
+
+		let v = Vec3::new(1, 2, 3);
+		assert_eq!(
+			v.to_ordering_about(Vec3::new(2, 2, 2)),
+			Vec3::new(Ordering::Less, Ordering::Equal, Ordering::Greater),
+		);
+	}
+}
+
+impl<T: Copy + Ord> Vec3<T> {
+	/// min takes the lesser one of each component.
+	#[inline(always)]
+	pub fn min(self, a: Vec3<T>) -> Vec3<T> {
+		self / a | (|(x, y)| min(x, y))
+	}
+
+	/// max takes the greater one of each component.
+	#[inline(always)]
+	pub fn max(self, a: Vec3<T>) -> Vec3<T> {
+		self / a | (|(x, y)| max(x, y))
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_i64_min_max() {
+		let v1 = Vec3::new(-1, 5, -3);
+		let v2 = Vec3::new(2, -6, -4);
+		assert_eq!(v1.min(v2), Vec3::new(-1, -6, -4));
+		assert_eq!(v1.max(v2), Vec3::new(2, 5, -3));
+		assert_eq!(v1.min(v1), v1);
+		assert_eq!(v2.max(v2), v2);
 	}
 }
 
-impl<T: Copy + Eq> PartialEq for Vec3<T> {
+/// PartialEq compares the components by reference, so that vectors
+/// of non-copyable components can also be compared.
+impl<T: PartialEq> PartialEq for Vec3<T> {
 	fn eq(&self, a: &Self) -> bool {
-		((*self) / (*a) & (|x, y| (x == y).then_some(()))).is_some()
+		self.0 == a.0 && self.1 == a.1 && self.2 == a.2
+	}
+}
+
+impl<T: Eq> Eq for Vec3<T> {}
+
+/// Hash feeds each component in order, so that equal vectors always
+/// hash equally, since equality is also compared componentwise.
+impl<T: Hash> Hash for Vec3<T> {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.0.hash(state);
+		self.1.hash(state);
+		self.2.hash(state);
 	}
 }
 
-impl<T: Copy + Eq> Eq for Vec3<T> {}
+cfg_test! {
+	#[test] fn test_vec3_i64_hash() {
+		let mut set = HashSet::new();
+		assert!(set.insert(Vec3::new(1, 2, 3)));
+		assert!(!set.insert(Vec3::new(1, 2, 3)));
+		assert!(set.insert(Vec3::new(3, 2, 1)));
+		assert!(set.insert(Vec3::new(1, 2, 4)));
+		assert_eq!(set.len(), 3);
+		assert!(set.contains(&Vec3::new(3, 2, 1)));
+		assert!(!set.contains(&Vec3::new(0, 0, 0)));
+	}
+
+	#[test] fn test_vec3_string_eq() {
+		let v = |x: &str, y: &str, z: &str| {
+			Vec3::new(x.to_string(), y.to_string(), z.to_string())
+		};
+		assert_eq!(v("a", "b", "c"), v("a", "b", "c"));
+		assert_ne!(v("a", "b", "c"), v("a", "b", "d"));
+		assert_ne!(v("a", "b", "c"), v("c", "b", "a"));
+	}
+}
+
+impl Vec3<f64> {
+	/// approx_eq checks whether each component of the vectors differs
+	/// from each other by no more than epsilon.
+	pub fn approx_eq(self, other: Vec3<f64>, epsilon: f64) -> bool {
+		let near =
+			|(x, y): (f64, f64)| ((x - y).abs() <= epsilon).then_some(());
+		(self / other & near).is_some()
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec3_f64_approx_eq() {
+		let v1 = Vec3::new(1.0, 2.0, 3.0);
+		let v2 = Vec3::new(1.0 + 1e-9, 2.0 - 1e-9, 3.0);
+		assert!(v1.approx_eq(v2, 1e-6));
+		assert!(!v1.approx_eq(v2, 1e-12));
+		let v3 = Vec3::new(1.0, 2.0, 3.1);
+		assert!(!v1.approx_eq(v3, 1e-6));
+		assert!(v1.approx_eq(v3, 0.2));
+	}
+}
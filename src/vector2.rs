@@ -0,0 +1,163 @@
+use std::cmp::Ordering;
+use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Sub};
+
+use crate::cfg_test;
+
+cfg_test! {
+	use rand::Rng;
+}
+
+/// Vec2 represents a two-dimensional vector, offered with the same
+/// operations as Vec3 except the cross product.
+#[derive(Copy, Clone, Debug)]
+pub struct Vec2<T>(pub T, pub T);
+
+impl<T> Vec2<T> {
+	#[inline(always)]
+	pub fn new(v1: T, v2: T) -> Self {
+		Self(v1, v2)
+	}
+}
+
+cfg_test! {
+	pub(crate) fn gen_vec2_i64(rng: &mut impl Rng) -> Vec2<i64> {
+		Vec2::new(
+			(rng.gen::<i32>() / 2) as i64,
+			(rng.gen::<i32>() / 2) as i64,
+		)
+	}
+}
+
+impl<T> From<Vec2<T>> for (T, T) {
+	#[inline(always)]
+	fn from(v: Vec2<T>) -> (T, T) {
+		(v.0, v.1)
+	}
+}
+
+impl<T> From<(T, T)> for Vec2<T> {
+	#[inline(always)]
+	fn from(v: (T, T)) -> Vec2<T> {
+		Self(v.0, v.1)
+	}
+}
+
+/// BitOr is bitwise mapping of each components.
+impl<T, U, F: FnMut(T) -> U> BitOr<F> for Vec2<T> {
+	type Output = Vec2<U>;
+	#[inline(always)]
+	fn bitor(self, mut f: F) -> Self::Output {
+		Vec2::new(f(self.0), f(self.1))
+	}
+}
+
+/// BitAnd is failfast bitwise mapping of each components.
+impl<T, U, F: FnMut(T) -> Option<U>> BitAnd<F> for Vec2<T> {
+	type Output = Option<Vec2<U>>;
+	#[inline(always)]
+	fn bitand(self, mut f: F) -> Self::Output {
+		Some(Vec2::new(f(self.0)?, f(self.1)?))
+	}
+}
+
+/// Div defines the zip operation for joining vector components of two
+/// vectors into tuples bitwisely.
+impl<T, U> Div<Vec2<U>> for Vec2<T> {
+	type Output = Vec2<(T, U)>;
+	#[inline(always)]
+	fn div(self, a: Vec2<U>) -> Self::Output {
+		Vec2::new((self.0, a.0), (self.1, a.1))
+	}
+}
+
+impl<T, U> Vec2<(T, U)> {
+	/// unzip separate the vector of two components back to two vectors.
+	#[inline(always)]
+	pub fn unzip(self) -> (Vec2<T>, Vec2<U>) {
+		(
+			Vec2::new(self.0 .0, self.1 .0),
+			Vec2::new(self.0 .1, self.1 .1),
+		)
+	}
+}
+
+/// Add defines the vector add for vectors.
+impl<U, T: Add<S, Output = U>, S> Add<Vec2<S>> for Vec2<T> {
+	type Output = Vec2<U>;
+	#[inline(always)]
+	fn add(self, a: Vec2<S>) -> Self::Output {
+		self / a | (|(x, y)| x + y)
+	}
+}
+
+/// Sub defines the vector sub for vectors.
+impl<U, T: Sub<S, Output = U>, S> Sub<Vec2<S>> for Vec2<T> {
+	type Output = Vec2<U>;
+	#[inline(always)]
+	fn sub(self, a: Vec2<S>) -> Self::Output {
+		self / a | (|(x, y)| x - y)
+	}
+}
+
+/// BitXor defines the vector dot product operation.
+impl<U, T, S> BitXor<Vec2<S>> for Vec2<T>
+where
+	U: Add<Output = U>,
+	T: Mul<S, Output = U>,
+{
+	type Output = U;
+	#[inline(always)]
+	fn bitxor(self, a: Vec2<S>) -> Self::Output {
+		let v = self / a | (|(x, y)| x * y);
+		v.0 + v.1
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec2_i64_arith() {
+		let v1 = Vec2::new(1, 2);
+		let v2 = Vec2::new(4, -5);
+		assert_eq!(v1 + v2, Vec2::new(5, -3));
+		assert_eq!(v1 - v2, Vec2::new(-3, 7));
+		assert_eq!(v1 ^ v2, -6);
+		assert_eq!((v1 / v2).unzip(), (v1, v2));
+	}
+}
+
+impl<T: Copy + Default> Default for Vec2<T> {
+	#[inline(always)]
+	fn default() -> Self {
+		let zero = T::default();
+		Vec2::new(zero, zero)
+	}
+}
+
+impl<T: Copy + Ord + Default> Vec2<T> {
+	/// to_ordering evaluates the spatial orientation of an vector,
+	/// see also Vec3::to_ordering.
+	#[inline(always)]
+	pub fn to_ordering(self) -> Vec2<Ordering> {
+		self / Vec2::default() | (|(x, y): (T, T)| x.cmp(&y))
+	}
+}
+
+cfg_test! {
+	#[test] fn test_vec2_i64_to_ordering() {
+		assert_eq!(
+			Vec2::new(-3, 0).to_ordering(),
+			Vec2::new(Ordering::Less, Ordering::Equal),
+		);
+		assert_eq!(
+			Vec2::new(5, -1).to_ordering(),
+			Vec2::new(Ordering::Greater, Ordering::Less),
+		);
+	}
+}
+
+impl<T: Copy + Eq> PartialEq for Vec2<T> {
+	fn eq(&self, a: &Self) -> bool {
+		((*self) / (*a) & (|(x, y)| (x == y).then_some(()))).is_some()
+	}
+}
+
+impl<T: Copy + Eq> Eq for Vec2<T> {}
@@ -0,0 +1,264 @@
+use std::array;
+use std::cmp::Ordering;
+use std::ops::{
+	Add, BitAnd, BitOr, BitXor, Div, Index, IndexMut, Mul, Neg, Sub,
+};
+
+use crate::{cfg_test, Vec3};
+
+/// VecN represents an N-dimensional vector, offered with the same
+/// operations as Vec3 except the cross product, which only makes
+/// sense in three dimensions.
+#[derive(Copy, Clone, Debug)]
+pub struct VecN<T, const N: usize>(pub [T; N]);
+
+impl<T, const N: usize> VecN<T, N> {
+	#[inline(always)]
+	pub fn new(v: [T; N]) -> Self {
+		Self(v)
+	}
+}
+
+impl<T, const N: usize> From<[T; N]> for VecN<T, N> {
+	#[inline(always)]
+	fn from(v: [T; N]) -> VecN<T, N> {
+		Self(v)
+	}
+}
+
+impl<T, const N: usize> From<VecN<T, N>> for [T; N] {
+	#[inline(always)]
+	fn from(v: VecN<T, N>) -> [T; N] {
+		v.0
+	}
+}
+
+impl<T> From<Vec3<T>> for VecN<T, 3> {
+	#[inline(always)]
+	fn from(v: Vec3<T>) -> VecN<T, 3> {
+		Self([v.0, v.1, v.2])
+	}
+}
+
+impl<T> From<VecN<T, 3>> for Vec3<T> {
+	#[inline(always)]
+	fn from(VecN([x, y, z]): VecN<T, 3>) -> Vec3<T> {
+		Vec3::new(x, y, z)
+	}
+}
+
+/// BitOr is bitwise mapping of each components.
+impl<T, U, F: FnMut(T) -> U, const N: usize> BitOr<F> for VecN<T, N> {
+	type Output = VecN<U, N>;
+	#[inline(always)]
+	fn bitor(self, f: F) -> Self::Output {
+		VecN(self.0.map(f))
+	}
+}
+
+/// BitAnd is failfast bitwise mapping of each components.
+impl<T, U, F: FnMut(T) -> Option<U>, const N: usize> BitAnd<F>
+	for VecN<T, N>
+{
+	type Output = Option<VecN<U, N>>;
+	#[inline(always)]
+	fn bitand(self, mut f: F) -> Self::Output {
+		let mut v = self.0.into_iter();
+		let mut failed = false;
+		let result: [Option<U>; N] = array::from_fn(|_| {
+			if failed {
+				return None;
+			}
+			let x = f(v.next().unwrap());
+			failed = x.is_none();
+			x
+		});
+		match failed {
+			true => None,
+			false => Some(VecN(result.map(Option::unwrap))),
+		}
+	}
+}
+
+/// Div defines the zip operation for joining vector components of two
+/// vectors into tuples bitwisely.
+impl<T, U, const N: usize> Div<VecN<U, N>> for VecN<T, N> {
+	type Output = VecN<(T, U), N>;
+	#[inline(always)]
+	fn div(self, a: VecN<U, N>) -> Self::Output {
+		let (mut v, mut w) = (self.0.into_iter(), a.0.into_iter());
+		VecN(array::from_fn(|_| (v.next().unwrap(), w.next().unwrap())))
+	}
+}
+
+impl<T, U, const N: usize> VecN<(T, U), N> {
+	/// unzip separate the vector of two components back to two vectors.
+	#[inline(always)]
+	pub fn unzip(self) -> (VecN<T, N>, VecN<U, N>) {
+		let mut v = self.0.map(|(x, y)| (Some(x), Some(y)));
+		let x = array::from_fn(|i| v[i].0.take().unwrap());
+		let y = array::from_fn(|i| v[i].1.take().unwrap());
+		(VecN(x), VecN(y))
+	}
+}
+
+/// Add defines the vector add for vectors.
+impl<U, T: Add<S, Output = U>, S, const N: usize> Add<VecN<S, N>>
+	for VecN<T, N>
+{
+	type Output = VecN<U, N>;
+	#[inline(always)]
+	fn add(self, a: VecN<S, N>) -> Self::Output {
+		self / a | (|(x, y)| x + y)
+	}
+}
+
+/// Sub defines the vector sub for vectors.
+impl<U, T: Sub<S, Output = U>, S, const N: usize> Sub<VecN<S, N>>
+	for VecN<T, N>
+{
+	type Output = VecN<U, N>;
+	#[inline(always)]
+	fn sub(self, a: VecN<S, N>) -> Self::Output {
+		self / a | (|(x, y)| x - y)
+	}
+}
+
+/// Neg defines the vector negation.
+impl<U, T: Neg<Output = U>, const N: usize> Neg for VecN<T, N> {
+	type Output = VecN<U, N>;
+	#[inline(always)]
+	fn neg(self) -> Self::Output {
+		self | (|x: T| -x)
+	}
+}
+
+/// BitXor defines the vector dot product operation.
+///
+/// The products are summed up starting from the default value, so
+/// the dot product of zero-dimensional vectors is the default.
+impl<U, T, S, const N: usize> BitXor<VecN<S, N>> for VecN<T, N>
+where
+	U: Add<Output = U> + Default,
+	T: Mul<S, Output = U>,
+{
+	type Output = U;
+	#[inline(always)]
+	fn bitxor(self, a: VecN<S, N>) -> Self::Output {
+		let v = self / a | (|(x, y)| x * y);
+		v.0.into_iter().fold(U::default(), |x, y| x + y)
+	}
+}
+
+// XXX: the scalar operations are implemented for every primitive
+// scalar type, for the same reason as Vec3.
+macro_rules! impl_vecn_scalar {
+	($($t:ty),*) => {
+		$(
+			/// Mul with a scalar scales each components.
+			impl<U, T: Mul<$t, Output = U>, const N: usize> Mul<$t>
+				for VecN<T, N>
+			{
+				type Output = VecN<U, N>;
+				#[inline(always)]
+				fn mul(self, s: $t) -> Self::Output {
+					VecN(self.0.map(|x| x * s))
+				}
+			}
+
+			/// Div with a scalar divides each components.
+			impl<U, T: Div<$t, Output = U>, const N: usize> Div<$t>
+				for VecN<T, N>
+			{
+				type Output = VecN<U, N>;
+				#[inline(always)]
+				fn div(self, s: $t) -> Self::Output {
+					VecN(self.0.map(|x| x / s))
+				}
+			}
+		)*
+	};
+}
+
+impl_vecn_scalar!(i8, i16, i32, i64, i128, isize);
+impl_vecn_scalar!(u8, u16, u32, u64, u128, usize);
+impl_vecn_scalar!(f32, f64);
+
+/// Index retrieves the component on the axis, and panics if the axis
+/// is not less than N.
+impl<T, const N: usize> Index<usize> for VecN<T, N> {
+	type Output = T;
+	#[inline(always)]
+	fn index(&self, axis: usize) -> &T {
+		&self.0[axis]
+	}
+}
+
+impl<T, const N: usize> IndexMut<usize> for VecN<T, N> {
+	#[inline(always)]
+	fn index_mut(&mut self, axis: usize) -> &mut T {
+		&mut self.0[axis]
+	}
+}
+
+impl<T: Default, const N: usize> Default for VecN<T, N> {
+	#[inline(always)]
+	fn default() -> Self {
+		VecN(array::from_fn(|_| T::default()))
+	}
+}
+
+impl<T: Copy + Ord + Default, const N: usize> VecN<T, N> {
+	/// to_ordering evaluates the spatial orientation of an vector,
+	/// see also Vec3::to_ordering.
+	#[inline(always)]
+	pub fn to_ordering(self) -> VecN<Ordering, N> {
+		self / VecN::default() | (|(x, y): (T, T)| x.cmp(&y))
+	}
+}
+
+impl<T: PartialEq, const N: usize> PartialEq for VecN<T, N> {
+	fn eq(&self, a: &Self) -> bool {
+		self.0 == a.0
+	}
+}
+
+impl<T: Eq, const N: usize> Eq for VecN<T, N> {}
+
+cfg_test! {
+	#[test] fn test_vecn_i64_dot() {
+		let v1 = VecN::new([1, 2, 3, 4]);
+		let v2 = VecN::new([5, -6, 7, -8]);
+		assert_eq!(v1 ^ v2, 5 - 12 + 21 - 32);
+		assert_eq!(v1 ^ v1, 30);
+		assert_eq!(VecN::<i64, 0>::new([]) ^ VecN::<i64, 0>::new([]), 0);
+
+		let v = Vec3::new(1, 2, 3);
+		assert_eq!(VecN::from(v) ^ VecN::from(v), v ^ v);
+		assert_eq!(Vec3::from(VecN::from(v)), v);
+	}
+
+	#[test] fn test_vecn_i64_arith() {
+		let v1 = VecN::new([1, 2]);
+		let v2 = VecN::new([4, -5]);
+		assert_eq!(v1 + v2, VecN::new([5, -3]));
+		assert_eq!(v1 - v2, VecN::new([-3, 7]));
+		assert_eq!(-v1, VecN::new([-1, -2]));
+		assert_eq!(v1 * 3, VecN::new([3, 6]));
+		assert_eq!(v2 / 2, VecN::new([2, -2]));
+		assert_eq!((v1 / v2).unzip(), (v1, v2));
+		assert_eq!(v2[1], -5);
+		assert_eq!(
+			v2.to_ordering(),
+			VecN::new([Ordering::Greater, Ordering::Less]),
+		);
+		assert_eq!(
+			v1 / v2 & (|(x, y): (i64, i64)| (x < y).then_some(y - x)),
+			None,
+		);
+		assert_eq!(
+			v1 / v2 & (|(x, y): (i64, i64)| x.checked_sub(y)),
+			Some(VecN::new([-3, 7])),
+		);
+	}
+}
@@ -0,0 +1,137 @@
+use std::cmp::Ordering;
+
+use crate::{cfg_test, Vec3};
+
+/// RayVoxels walks the unit grid cells that a ray passes through.
+///
+/// The cells are yielded in the order the ray enters them, with the
+/// Amanatides-Woo DDA, which only performs comparisons and additions
+/// of parameters per step. Every two consecutive cells share a face,
+/// and when the ray passes exactly through an edge or a corner, the
+/// x axis is stepped before y, and y before z.
+///
+/// The traversal is endless for a non-zero direction unless a max
+/// distance is specified.
+pub struct RayVoxels {
+	cell: Vec3<i64>,
+	step: Vec3<i64>,
+	t_max: Vec3<f64>,
+	t_delta: Vec3<f64>,
+	limit: f64,
+	done: bool,
+}
+
+// ray_voxel_axis initializes the cell, step, crossing parameter and
+// delta of parameter on one axis.
+fn ray_voxel_axis(o: f64, d: f64) -> (i64, i64, f64, f64) {
+	let cell = o.floor();
+	if d > 0.0 {
+		(cell as i64, 1, (cell + 1.0 - o) / d, 1.0 / d)
+	} else if d < 0.0 {
+		(cell as i64, -1, (o - cell) / -d, -1.0 / d)
+	} else {
+		(cell as i64, 0, f64::INFINITY, f64::INFINITY)
+	}
+}
+
+/// ray_voxels creates the traversal of cells along the ray.
+pub fn ray_voxels(origin: Vec3<f64>, dir: Vec3<f64>) -> RayVoxels {
+	let x = ray_voxel_axis(origin.0, dir.0);
+	let y = ray_voxel_axis(origin.1, dir.1);
+	let z = ray_voxel_axis(origin.2, dir.2);
+	RayVoxels {
+		cell: Vec3::new(x.0, y.0, z.0),
+		step: Vec3::new(x.1, y.1, z.1),
+		t_max: Vec3::new(x.2, y.2, z.2),
+		t_delta: Vec3::new(x.3, y.3, z.3),
+		limit: f64::INFINITY,
+		done: false,
+	}
+}
+
+impl RayVoxels {
+	/// max_distance stops the traversal after the ray has gone
+	/// further than the specified distance.
+	///
+	/// The distance is measured in the ray's parameter, that is, in
+	/// the multiples of the direction vector. It is the euclidean
+	/// distance when the direction is normalized.
+	pub fn max_distance(mut self, limit: f64) -> Self {
+		self.limit = limit;
+		self
+	}
+}
+
+impl Iterator for RayVoxels {
+	type Item = Vec3<i64>;
+
+	fn next(&mut self) -> Option<Vec3<i64>> {
+		if self.done {
+			return None;
+		}
+		let cell = self.cell;
+		let (tx, ty, tz) = self.t_max.into();
+		let t = tx.min(ty).min(tz);
+		let beyond = t.partial_cmp(&self.limit);
+		if matches!(beyond, None | Some(Ordering::Greater)) {
+			// XXX: this also covers the case that the direction is
+			// zero, where all parameters are infinite, and the NaN
+			// parameters which are incomparable.
+			self.done = true;
+		} else if t == tx {
+			self.cell.0 += self.step.0;
+			self.t_max.0 += self.t_delta.0;
+		} else if t == ty {
+			self.cell.1 += self.step.1;
+			self.t_max.1 += self.t_delta.1;
+		} else {
+			self.cell.2 += self.step.2;
+			self.t_max.2 += self.t_delta.2;
+		}
+		Some(cell)
+	}
+}
+
+cfg_test! {
+	fn assert_ray_voxels(
+		origin: (f64, f64, f64), dir: (f64, f64, f64), limit: f64,
+		expected: &[(i64, i64, i64)],
+	) {
+		let actual: Vec<Vec3<i64>> =
+			ray_voxels(origin.into(), dir.into())
+				.max_distance(limit)
+				.collect();
+		let expected: Vec<Vec3<i64>> =
+			expected.iter().map(|x| (*x).into()).collect();
+		assert_eq!(actual, expected);
+	}
+
+	#[test] fn test_ray_voxels_short() {
+		assert_ray_voxels(
+			(0.5, 0.5, 0.5), (1.0, 0.5, 0.0), 2.0,
+			&[(0, 0, 0), (1, 0, 0), (1, 1, 0), (2, 1, 0)],
+		);
+		assert_ray_voxels(
+			(0.5, 0.5, 0.5), (-1.0, 0.0, 0.0), 1.7,
+			&[(0, 0, 0), (-1, 0, 0), (-2, 0, 0)],
+		);
+		assert_ray_voxels(
+			(-0.5, 2.5, 0.25), (0.0, 0.0, 0.0), 100.0,
+			&[(-1, 2, 0)],
+		);
+	}
+
+	#[test] fn test_ray_voxels_connected() {
+		let cells: Vec<Vec3<i64>> = ray_voxels(
+			Vec3::new(0.3, -7.1, 2.9),
+			Vec3::new(0.7, 1.3, -2.2),
+		).max_distance(50.0).collect();
+		assert!(cells.len() > 100);
+		for i in 1..cells.len() {
+			let d = cells[i] - cells[i - 1];
+			assert_eq!(d ^ d, 1, "{:?} -> {:?}", cells[i - 1], cells[i]);
+		}
+		let last = cells[cells.len() - 1];
+		assert_eq!(last, Vec3::new(35, 57, -108));
+	}
+}